@@ -19,10 +19,22 @@ fn main() {
   args.next().unwrap();
   let listen_url = args.next().unwrap_or_else(|| String::from("ipc:///tmp/client.ipc"));
   let server_url = args.next().unwrap_or_else(|| String::from("ipc:///tmp/server.ipc"));
+  let player_name =
+    args.next()
+    .or_else(|| {
+      // Player names can only have letters, digits, dashes and underscores.
+      env::var("USER").ok().map(|user| {
+        user.chars()
+          .map(|c| match c { 'a' ... 'z' | 'A' ... 'Z' | '0' ... '9' | '-' | '_' => c, _ => '_' })
+          .collect()
+      })
+    })
+    .unwrap_or_else(|| String::from("player"));
   assert!(args.next().is_none());
 
   info!("Sending to {}.", server_url);
   info!("Listening on {}.", listen_url);
+  info!("Playing as {}.", player_name);
 
-  client_lib::run(listen_url.borrow(), server_url.borrow());
+  client_lib::run(listen_url.borrow(), server_url.borrow(), player_name.borrow());
 }
@@ -21,13 +21,20 @@ use client_lib::server;
 use client_lib::update_thread::update_thread;
 
 #[allow(missing_docs)]
-pub fn run(listen_url: &str, server_url: &str) {
+pub fn run(listen_url: &str, server_url: &str, player_name: &str) {
   let quit = Mutex::new(false);
   let quit = &quit;
 
   let server = server::new(&server_url, &listen_url);
 
-  let client = connect_client(&listen_url, &server);
+  let client =
+    match connect_client(&listen_url, player_name, &server) {
+      Ok(client) => client,
+      Err(reason) => {
+        error!("Couldn't join the server: {}", reason);
+        return
+      },
+    };
   *client.load_position.lock().unwrap() = Some(cgmath::Point3::new(0.0, 512.0, 0.0));
   let client = &client;
 
@@ -57,7 +64,7 @@ pub fn run(listen_url: &str, server_url: &str) {
         update_thread(
           quit,
           client,
-          &mut || { server.listen.try() },
+          &mut || { server.listen.try().unwrap_or(None) },
           &mut |_| { },
           &mut |_| { },
           &mut |_| { },
@@ -86,18 +93,25 @@ pub fn run(listen_url: &str, server_url: &str) {
   update_thread.join();
 }
 
-fn connect_client(listen_url: &str, server: &server::T) -> client::T {
+/// Join the server as `player_name`, or say why the server turned us away.
+fn connect_client(listen_url: &str, player_name: &str, server: &server::T) -> Result<client::T, String> {
   // TODO: Consider using RPCs to solidify the request-response patterns.
   server.talk.tell(&protocol::ClientToServer::Init(listen_url.to_owned()));
   loop {
     match server.listen.wait() {
       protocol::ServerToClient::LeaseId(client_id) => {
-        server.talk.tell(&protocol::ClientToServer::AddPlayer(client_id));
+        server.talk.tell(&protocol::ClientToServer::AddPlayer(client_id, player_name.to_owned()));
         let client_id = client_id;
         loop {
           match server.listen.wait() {
-            protocol::ServerToClient::PlayerAdded(player_id, position) => {
-              return client::new(client_id, player_id, position);
+            protocol::ServerToClient::PlayerAdded(player_id, position, _) => {
+              return Ok(client::new(client_id, player_id, position));
+            },
+            protocol::ServerToClient::Kicked(reason) => {
+              return Err(format!("Kicked by the server: {}", reason));
+            },
+            protocol::ServerToClient::ShuttingDown { reason } => {
+              return Err(format!("The server shut down: {}", reason));
             },
             msg => {
               // Ignore other messages in the meantime.
@@ -0,0 +1,77 @@
+//! The player's choice of terrain brush.
+
+use rand;
+use rand::distributions::IndependentSample;
+
+use common::protocol;
+use common::voxel;
+
+/// How much the brush size changes with each adjustment.
+pub const SIZE_STEP: f32 = 1.0;
+
+/// The brush a player starts with.
+pub fn new() -> protocol::Brush {
+  protocol::Brush {
+    shape    : protocol::BrushShape::Sphere,
+    size     : 4.0,
+    material : voxel::Material::Terrain,
+    mode     : protocol::BrushMode::Add,
+  }
+}
+
+/// The shape after `shape`, for cycling through them.
+pub fn next_shape(shape: &protocol::BrushShape) -> protocol::BrushShape {
+  match *shape {
+    protocol::BrushShape::Sphere   => protocol::BrushShape::Cube,
+    protocol::BrushShape::Cube     => protocol::BrushShape::Cylinder,
+    protocol::BrushShape::Cylinder =>
+      protocol::BrushShape::Tree {
+        trunk_radius : 2.0,
+        trunk_height : 16.0,
+        leaf_radius  : 8.0,
+      },
+    protocol::BrushShape::Tree { .. } => protocol::BrushShape::Sphere,
+  }
+}
+
+/// The middle mouse button's mode after `mode`, for cycling through the ones that don't just add or remove terrain.
+pub fn next_middle_mode(mode: &protocol::BrushMode) -> protocol::BrushMode {
+  match *mode {
+    protocol::BrushMode::Paint   => protocol::BrushMode::Smooth,
+    protocol::BrushMode::Smooth  => protocol::BrushMode::Flatten,
+    protocol::BrushMode::Flatten => protocol::BrushMode::Paint,
+    protocol::BrushMode::Add | protocol::BrushMode::Subtract => protocol::BrushMode::Paint,
+  }
+}
+
+/// Randomize a tree's proportions, so that they don't all look the same.
+pub fn vary<Rng: rand::Rng>(rng: &mut Rng, shape: &protocol::BrushShape) -> protocol::BrushShape {
+  match *shape {
+    protocol::BrushShape::Tree { .. } => {},
+    shape => return shape,
+  }
+
+  let trunk_radius =
+    rand::distributions::normal::Normal::new(2.0, 0.5)
+    .ind_sample(rng);
+  let trunk_radius =
+    f64::max(1.0, f64::min(3.0, trunk_radius));
+
+  let trunk_height =
+    rand::distributions::normal::Normal::new(8.0 * trunk_radius, 2.0 * trunk_radius)
+    .ind_sample(rng);
+  let trunk_height =
+    f64::max(4.0 * trunk_radius, f64::min(12.0 * trunk_radius, trunk_height));
+
+  let leaf_radius =
+    rand::distributions::normal::Normal::new(4.0 * trunk_radius, trunk_radius)
+    .ind_sample(rng);
+  let leaf_radius =
+    f64::max(2.0 * trunk_radius, f64::min(6.0 * trunk_radius, leaf_radius));
+
+  protocol::BrushShape::Tree {
+    trunk_radius : trunk_radius as f32,
+    trunk_height : trunk_height as f32,
+    leaf_radius  : leaf_radius as f32,
+  }
+}
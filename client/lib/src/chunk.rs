@@ -11,6 +11,7 @@ pub mod position {
   use std::ops::Add;
 
   use common::voxel;
+  use common::world_position;
 
   use chunk;
 
@@ -62,8 +63,8 @@ pub mod position {
   }
 
   #[allow(missing_docs)]
-  pub fn of_world_position(world_position: &Point3<f32>) -> T {
-    fn convert_coordinate(x: f32) -> i32 {
+  pub fn of_world_position(world_position: &world_position::T) -> T {
+    fn convert_coordinate(x: f64) -> i32 {
       let x = x.floor() as i32;
       let x =
         if x < 0 {
@@ -1,6 +1,7 @@
 //! record stats about polygon counts in chunks
 
 use std;
+use std::io;
 use std::io::Write;
 
 const MAX_POLYGON_COUNT: usize = 1 << 13;
@@ -28,17 +29,15 @@ impl T {
     self.chunk_counts_by_polygon_count[polygon_count] += 1;
   }
 
-  /// print all the stats into a file
-  pub fn output_to(&self, file: &str) {
-    let mut file = std::fs::File::create(file).unwrap();
+  /// print all the stats into a CSV file, with the number of chunks loaded with each polygon count
+  pub fn output_to(&self, path: &str) -> io::Result<()> {
+    let file = try!(std::fs::File::create(path));
+    let mut file = io::BufWriter::new(file);
 
-    file.write_all(b"vram_chunk_loads = [").unwrap();
-    for (i, record) in self.chunk_counts_by_polygon_count.iter().enumerate() {
-      if i > 0 {
-        file.write_all(b", ").unwrap();
-      }
-      file.write_fmt(format_args!("{}", record)).unwrap();
+    try!(writeln!(file, "polygon_count,chunks"));
+    for (polygon_count, chunks) in self.chunk_counts_by_polygon_count.iter().enumerate() {
+      try!(writeln!(file, "{},{}", polygon_count, chunks));
     }
-    file.write_all(b"];\n").unwrap();
+    file.flush()
   }
 }
@@ -1,6 +1,5 @@
 //! Main Playform client state code.
 
-use cgmath::Point3;
 use num;
 use rand;
 use rand::{Rng, SeedableRng};
@@ -9,7 +8,10 @@ use std::sync::Mutex;
 use common::id_allocator;
 use common::protocol;
 use common::surroundings_loader;
+use common::voxel;
+use common::world_position;
 
+use brush;
 use lod;
 use terrain;
 use view;
@@ -24,11 +26,19 @@ pub struct T {
   /// id for the player in vram
   pub player_id                : view::entity::id::Player,
   /// position of the player in world coordinates
-  pub player_position          : Mutex<Point3<f32>>,
+  pub player_position          : Mutex<world_position::T>,
+  /// the brush used when editing terrain
+  pub brush                    : Mutex<protocol::Brush>,
+  /// what the middle mouse button does with the brush
+  pub middle_mode              : Mutex<protocol::BrushMode>,
+  /// how to reorient the clipboard when pasting
+  pub paste_transform          : Mutex<protocol::PasteTransform>,
+  /// the materials our player has collected
+  pub inventory                : Mutex<Vec<(voxel::Material, u32)>>,
   /// the location where we last played a footstep sound
-  pub last_footstep            : Mutex<Point3<f32>>,
+  pub last_footstep            : Mutex<world_position::T>,
   /// world position to center terrain loading around
-  pub load_position            : Mutex<Option<Point3<f32>>>,
+  pub load_position            : Mutex<Option<world_position::T>>,
   #[allow(missing_docs)]
   pub terrain_allocator        : Mutex<id_allocator::T<view::entity::id::Terrain>>,
   #[allow(missing_docs)]
@@ -43,6 +53,8 @@ pub struct T {
   pub pending_terrain_requests : Mutex<u32>,
   #[allow(missing_docs)]
   pub rng                      : Mutex<rand::XorShiftRng>,
+  /// why the server disconnected us, once it has
+  pub disconnected             : Mutex<Option<String>>,
 }
 
 fn load_distance(mut polygon_budget: i32) -> u32 {
@@ -91,7 +103,7 @@ fn load_distance(mut polygon_budget: i32) -> u32 {
 }
 
 #[allow(missing_docs)]
-pub fn new(client_id: protocol::ClientId, player_id: view::entity::id::Player, position: Point3<f32>) -> T {
+pub fn new(client_id: protocol::ClientId, player_id: view::entity::id::Player, position: world_position::T) -> T {
   let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
   let s1 = rng.next_u32();
   let s2 = rng.next_u32();
@@ -119,6 +131,10 @@ pub fn new(client_id: protocol::ClientId, player_id: view::entity::id::Player, p
     id                       : client_id,
     player_id                : player_id,
     player_position          : Mutex::new(position),
+    brush                    : Mutex::new(brush::new()),
+    middle_mode              : Mutex::new(protocol::BrushMode::Paint),
+    paste_transform          : Mutex::new(protocol::PasteTransform { quarter_turns: 0, mirror: false }),
+    inventory                : Mutex::new(Vec::new()),
     last_footstep            : Mutex::new(position),
     load_position            : Mutex::new(None),
     terrain_allocator        : Mutex::new(id_allocator::new()),
@@ -128,6 +144,7 @@ pub fn new(client_id: protocol::ClientId, player_id: view::entity::id::Player, p
     terrain                  : Mutex::new(terrain::new(load_distance as u32)),
     pending_terrain_requests : Mutex::new(0),
     rng                      : Mutex::new(rng),
+    disconnected             : Mutex::new(None),
   }
 }
 
@@ -34,6 +34,7 @@ extern crate yaglw;
 pub mod audio;
 pub mod audio_loader;
 pub mod audio_thread;
+pub mod brush;
 pub mod chunk;
 pub mod chunk_stats;
 pub mod client;
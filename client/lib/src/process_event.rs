@@ -5,14 +5,19 @@ use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::mouse::MouseButton;
 use std::f32::consts::PI;
-use stopwatch;
 
 use common::entity;
 use common::protocol;
+use common::trace;
+use common::voxel;
 
+use brush;
 use client;
 use view;
 
+/// The name the clipboard is saved under on the server.
+const QUICKSAVE_CLIPBOARD: &'static str = "quicksave";
+
 #[allow(missing_docs)]
 pub fn process_event<UpdateServer>(
   update_server: &mut UpdateServer,
@@ -40,7 +45,7 @@ pub fn process_event<UpdateServer>(
       mouse_move(client.player_id, update_server, view, xrel, yrel);
     },
     Event::MouseButtonDown{mouse_btn, ..} => {
-      mouse_press(client.player_id, update_server, mouse_btn);
+      mouse_press(client, update_server, mouse_btn);
     },
     _ => {},
   }
@@ -81,7 +86,7 @@ fn key_press<UpdateServer>(
     }
   };
 
-  stopwatch::time("event.key_press", || {
+  trace::time("event.key_press", || {
     match key {
       Keycode::A => {
         update_server(Walk(client.player_id, Vector3::new(-1.0, 0.0, 0.0)));
@@ -110,6 +115,79 @@ fn key_press<UpdateServer>(
       Keycode::Down => {
         ud(update_server, view, -1.0);
       },
+      Keycode::F => {
+        update_server(Attack(client.player_id));
+      },
+      Keycode::Num1 => {
+        client.brush.lock().unwrap().material = voxel::Material::Terrain;
+      },
+      Keycode::Num2 => {
+        client.brush.lock().unwrap().material = voxel::Material::Stone;
+      },
+      Keycode::Num3 => {
+        client.brush.lock().unwrap().material = voxel::Material::Marble;
+      },
+      Keycode::Num4 => {
+        client.brush.lock().unwrap().material = voxel::Material::Bark;
+      },
+      Keycode::Num5 => {
+        client.brush.lock().unwrap().material = voxel::Material::Leaves;
+      },
+      Keycode::Z => {
+        update_server(Undo(client.player_id));
+      },
+      Keycode::Y => {
+        update_server(Redo(client.player_id));
+      },
+      Keycode::K => {
+        update_server(Clipboard(client.player_id, protocol::ClipboardAction::SelectCorner(0)));
+      },
+      Keycode::L => {
+        update_server(Clipboard(client.player_id, protocol::ClipboardAction::SelectCorner(1)));
+      },
+      Keycode::C => {
+        update_server(Clipboard(client.player_id, protocol::ClipboardAction::Copy));
+      },
+      Keycode::V => {
+        let transform = *client.paste_transform.lock().unwrap();
+        update_server(Clipboard(client.player_id, protocol::ClipboardAction::Paste(transform)));
+      },
+      Keycode::R => {
+        let mut transform = client.paste_transform.lock().unwrap();
+        transform.quarter_turns = (transform.quarter_turns + 1) % 4;
+        info!("Paste transform: {:?}", *transform);
+      },
+      Keycode::X => {
+        let mut transform = client.paste_transform.lock().unwrap();
+        transform.mirror = !transform.mirror;
+        info!("Paste transform: {:?}", *transform);
+      },
+      Keycode::F5 => {
+        update_server(Clipboard(client.player_id, protocol::ClipboardAction::Save(QUICKSAVE_CLIPBOARD.to_owned())));
+      },
+      Keycode::F9 => {
+        update_server(Clipboard(client.player_id, protocol::ClipboardAction::Load(QUICKSAVE_CLIPBOARD.to_owned())));
+      },
+      Keycode::B => {
+        let mut brush = client.brush.lock().unwrap();
+        brush.shape = brush::next_shape(&brush.shape);
+        info!("Brush shape: {:?}", brush.shape);
+      },
+      Keycode::N => {
+        let mut mode = client.middle_mode.lock().unwrap();
+        *mode = brush::next_middle_mode(&*mode);
+        info!("Middle mouse button: {:?}", *mode);
+      },
+      Keycode::Equals => {
+        let mut brush = client.brush.lock().unwrap();
+        brush.size += brush::SIZE_STEP;
+        info!("Brush size: {}", brush.size);
+      },
+      Keycode::Minus => {
+        let mut brush = client.brush.lock().unwrap();
+        brush.size = f32::max(brush::SIZE_STEP, brush.size - brush::SIZE_STEP);
+        info!("Brush size: {}", brush.size);
+      },
       Keycode::H => {
         view.show_hud = !view.show_hud;
       },
@@ -133,25 +211,24 @@ fn key_press<UpdateServer>(
 }
 
 fn mouse_press<UpdateServer>(
-  player_id: entity::id::Player,
+  client: &client::T,
   update_server: &mut UpdateServer,
   mouse_btn: MouseButton,
 ) where UpdateServer: FnMut(protocol::ClientToServer)
 {
-  stopwatch::time("event.mouse_press", || {
-    match mouse_btn {
-      MouseButton::Left => {
-        update_server(
-          protocol::ClientToServer::Add(player_id)
-        );
-      },
-      MouseButton::Right => {
-        update_server(
-          protocol::ClientToServer::Remove(player_id)
-        );
-      },
-      _ => {},
-    }
+  trace::time("event.mouse_press", || {
+    let mode =
+      match mouse_btn {
+        MouseButton::Left => protocol::BrushMode::Add,
+        MouseButton::Right => protocol::BrushMode::Subtract,
+        MouseButton::Middle => *client.middle_mode.lock().unwrap(),
+        _ => return,
+      };
+
+    let mut brush = *client.brush.lock().unwrap();
+    brush.mode = mode;
+    brush.shape = brush::vary(&mut *client.rng.lock().unwrap(), &brush.shape);
+    update_server(protocol::ClientToServer::Brush(client.player_id, brush));
   })
 }
 
@@ -161,7 +238,7 @@ fn key_release<UpdateServer>(
   key: Keycode,
 ) where UpdateServer: FnMut(protocol::ClientToServer)
 {
-  stopwatch::time("event.key_release", || {
+  trace::time("event.key_release", || {
     match key {
       // accelerations are negated from those in key_press.
       Keycode::A => {
@@ -192,7 +269,7 @@ fn mouse_move<UpdateServer>(
   dx: i32, dy: i32,
 ) where UpdateServer: FnMut(protocol::ClientToServer)
 {
-  stopwatch::time("event.mouse_move", || {
+  trace::time("event.mouse_move", || {
     let d = Vector2::new(dx, dy);
     // To-radians coefficient. Numbers closer to zero dull the mouse movement more.
     let to_radians = Vector2::new(-1.0 / 1000.0, -1.0 / 1600.0);
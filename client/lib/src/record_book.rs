@@ -1,5 +1,9 @@
 //! Record stats about the program execution
 
+use std;
+use std::io;
+use std::io::Write;
+
 /// end-to-end timing of loading a single chunk
 #[derive(Debug, Clone, Copy)]
 pub struct ChunkLoad {
@@ -26,6 +30,25 @@ pub fn new() -> T {
   }
 }
 
+impl T {
+  /// Write the chunk loads as CSV, one row per load, in the order they're recorded.
+  pub fn output_chunk_loads_to(&self, path: &str) -> io::Result<()> {
+    let file = try!(std::fs::File::create(path));
+    let mut file = io::BufWriter::new(file);
+    try!(writeln!(file, "time_requested_ns,response_time_ns,stored_time_ns,loaded_time_ns"));
+    for record in &self.chunk_loads {
+      try!(
+        writeln!(
+          file,
+          "{},{},{},{}",
+          record.time_requested_ns, record.response_time_ns, record.stored_time_ns, record.loaded_time_ns,
+        )
+      );
+    }
+    file.flush()
+  }
+}
+
 /// to avoid locking, keep separate record books per thread
 pub mod thread_local {
   use super::*;
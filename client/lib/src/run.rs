@@ -1,12 +1,13 @@
 //! entry point
 
+use cgmath::{Vector2};
 use std;
-use std::io::Write;
 use std::sync::{Mutex};
 use stopwatch;
 use thread_scoped;
 
 use common::protocol;
+use common::trace;
 
 use audio_loader;
 use audio_thread;
@@ -15,10 +16,11 @@ use record_book;
 use server;
 use terrain;
 use update_thread::update_thread;
+use view;
 use view::thread::view_thread;
 
 #[allow(missing_docs)]
-pub fn run(listen_url: &str, server_url: &str) {
+pub fn run(listen_url: &str, server_url: &str, player_name: &str) {
   let view_updates0 = Mutex::new(std::collections::VecDeque::new());
   let view_updates1 = Mutex::new(std::collections::VecDeque::new());
   let audio_updates = Mutex::new(std::collections::VecDeque::new());
@@ -28,7 +30,18 @@ pub fn run(listen_url: &str, server_url: &str) {
 
   let server = server::new(&server_url, &listen_url);
 
-  let client = connect_client(&listen_url, &server);
+  let client =
+    match connect_client(&listen_url, player_name, &server) {
+      Ok((client, rotation)) => {
+        // Face wherever the server thinks we're facing.
+        view_updates0.lock().unwrap().push_back(view::update::RotateCamera(rotation));
+        client
+      },
+      Err(reason) => {
+        error!("Couldn't join the server: {}", reason);
+        return
+      },
+    };
   let client = &client;
 
   {
@@ -73,7 +86,15 @@ pub fn run(listen_url: &str, server_url: &str) {
           update_thread(
             quit,
             client,
-            &mut || { server.listen.try() },
+            &mut || {
+              server.listen.try().unwrap_or_else(|server::recv::Disconnected| {
+                let mut disconnected = client.disconnected.lock().unwrap();
+                if disconnected.is_none() {
+                  *disconnected = Some(String::from("Lost connection to the server."));
+                }
+                None
+              })
+            },
             &mut |up| { view_updates0.lock().unwrap().push_back(up) },
             &mut |up| { view_updates1.lock().unwrap().push_back(up) },
             &mut |up| { audio_updates.lock().unwrap().push_back(up) },
@@ -91,19 +112,9 @@ pub fn run(listen_url: &str, server_url: &str) {
 
           let mut recorded = record_book::thread_local::clone();
           recorded.chunk_loads.sort_by(|x, y| x.loaded_time_ns.cmp(&y.loaded_time_ns));
-
-          let mut file = std::fs::File::create("chunk_loads.out").unwrap();
-
-          file.write_all(b"records = [").unwrap();
-          for (i, record) in recorded.chunk_loads.iter().enumerate() {
-            if i > 0 {
-              file.write_all(b", ").unwrap();
-            }
-            let record_book::ChunkLoad { time_requested_ns, response_time_ns, stored_time_ns, loaded_time_ns } = *record;
-            file.write_fmt(format_args!("[{}; {}; {}; {}]", time_requested_ns, response_time_ns, stored_time_ns, loaded_time_ns)).unwrap();
+          if let Err(err) = recorded.output_chunk_loads_to("chunk_loads.csv") {
+            warn!("Couldn't write chunk loads: {}", err);
           }
-          file.write_all(b"];\n").unwrap();
-          file.write_fmt(format_args!("plot([1:{}], records);", recorded.chunk_loads.len())).unwrap();
 
           stopwatch::clone()
         })
@@ -133,20 +144,38 @@ pub fn run(listen_url: &str, server_url: &str) {
 
     stopwatch.print();
   }
+
+  match trace::write() {
+    Ok(None) => {},
+    Ok(Some(path)) => info!("Wrote a trace to {}", path.display()),
+    Err(err) => warn!("Couldn't write a trace: {}", err),
+  }
 }
 
-fn connect_client(listen_url: &str, server: &server::T) -> client::T {
+/// Join the server as `player_name`, or say why the server turned us away.
+/// Returns the client, and the player's lateral and vertical rotation.
+fn connect_client(
+  listen_url  : &str,
+  player_name : &str,
+  server      : &server::T,
+) -> Result<(client::T, Vector2<f32>), String> {
   // TODO: Consider using RPCs to solidify the request-response patterns.
   server.talk.tell(&protocol::ClientToServer::Init(listen_url.to_owned()));
   loop {
     match server.listen.wait() {
       protocol::ServerToClient::LeaseId(client_id) => {
-        server.talk.tell(&protocol::ClientToServer::AddPlayer(client_id));
+        server.talk.tell(&protocol::ClientToServer::AddPlayer(client_id, player_name.to_owned()));
         let client_id = client_id;
         loop {
           match server.listen.wait() {
-            protocol::ServerToClient::PlayerAdded(player_id, position) => {
-              return client::new(client_id, player_id, position);
+            protocol::ServerToClient::PlayerAdded(player_id, position, rotation) => {
+              return Ok((client::new(client_id, player_id, position), rotation));
+            },
+            protocol::ServerToClient::Kicked(reason) => {
+              return Err(format!("Kicked by the server: {}", reason));
+            },
+            protocol::ServerToClient::ShuttingDown { reason } => {
+              return Err(format!("The server shut down: {}", reason));
             },
             msg => {
               // Ignore other messages in the meantime.
@@ -30,7 +30,10 @@ pub mod send {
       use bincode::serialize;
       let msg = serialize(msg, bincode::Infinite).unwrap();
       *self.bytes_sent.lock().unwrap() += msg.len() as u64;
-      self.sender.send(msg).unwrap();
+      if self.sender.send(msg).is_err() {
+        // The send thread only stops once the server's gone, so there's nobody to tell.
+        debug!("Dropping a message to a disconnected server");
+      }
     }
   }
 }
@@ -47,15 +50,16 @@ pub mod recv {
   #[derive(Clone)]
   pub struct T (pub std::sync::Arc<Receiver<Vec<u8>>>);
 
+  /// The server has stopped talking to us.
+  #[derive(Debug, Clone, Copy)]
+  pub struct Disconnected;
+
   impl T {
-    pub fn try(&self) -> Option<protocol::ServerToClient> {
+    pub fn try(&self) -> Result<Option<protocol::ServerToClient>, Disconnected> {
       match self.0.try_recv() {
-        Ok(msg) => Some(bincode::deserialize(&msg).unwrap()),
-        Err(TryRecvError::Empty) => None,
-        e => {
-          e.unwrap();
-          unreachable!();
-        },
+        Ok(msg) => Ok(Some(bincode::deserialize(&msg).unwrap())),
+        Err(TryRecvError::Empty) => Ok(None),
+        Err(TryRecvError::Disconnected) => Err(Disconnected),
       }
     }
 
@@ -94,7 +98,11 @@ pub fn new(
         );
       loop {
         match listen_socket.read() {
-          None => break,
+          None => {
+            // The socket timed out or was closed; either way the server's gone.
+            warn!("Lost connection to the server");
+            break
+          },
           Some(msg) => {
             recv_send.send(msg).unwrap()
           },
@@ -116,7 +124,10 @@ pub fn new(
           Err(_) => break,
           Ok(msg) => {
             let msg: Vec<u8> = msg;
-            talk_socket.write(msg.as_ref()).unwrap();
+            if let Err(err) = talk_socket.write(msg.as_ref()) {
+              warn!("Error sending to the server: {:?}", err);
+              break
+            }
           },
         }
       }
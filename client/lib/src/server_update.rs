@@ -2,25 +2,19 @@
 
 use cgmath;
 use cgmath::{Point3, EuclideanSpace, InnerSpace, ElementWise};
-use collision::{Aabb3};
 use rand::Rng;
-use stopwatch;
 use time;
 
 use common::color::Color4;
 use common::protocol;
+use common::trace;
 
 use audio_loader;
 use audio_thread;
 use client;
 use terrain;
-use vertex::ColoredVertex;
 use view;
 
-const TRIANGLES_PER_BOX: u32 = 12;
-const VERTICES_PER_TRIANGLE: u32 = 3;
-const TRIANGLE_VERTICES_PER_BOX: u32 = TRIANGLES_PER_BOX * VERTICES_PER_TRIANGLE;
-
 /// dispatch a server message among the subsystems
 pub fn apply_server_update<UpdateView, UpdateAudio, UpdateServer, EnqueueTerrainLoad>(
   client               : &client::T,
@@ -35,7 +29,7 @@ pub fn apply_server_update<UpdateView, UpdateAudio, UpdateServer, EnqueueTerrain
   UpdateServer       : FnMut(protocol::ClientToServer),
   EnqueueTerrainLoad : FnMut(terrain::Load),
 {
-  stopwatch::time("apply_server_update", move || {
+  trace::time("apply_server_update", move || {
     match update {
       protocol::ServerToClient::LeaseId(_) => {
         warn!("Client ID has already been leased.");
@@ -43,12 +37,11 @@ pub fn apply_server_update<UpdateView, UpdateAudio, UpdateServer, EnqueueTerrain
       protocol::ServerToClient::Ping => {
         update_server(protocol::ClientToServer::Ping(client.id));
       },
-      protocol::ServerToClient::PlayerAdded(id, _) => {
+      protocol::ServerToClient::PlayerAdded(id, _, _) => {
         warn!("Unexpected PlayerAdded event: {:?}.", id);
       },
       protocol::ServerToClient::UpdatePlayer(player_id, bounds) => {
-        let mesh = to_triangles(&bounds, &Color4::of_rgba(0.0, 0.0, 1.0, 1.0));
-        update_view(view::update::UpdatePlayer(player_id, mesh));
+        update_view(view::update::UpdatePlayer(player_id, bounds, Color4::of_rgba(0.0, 0.0, 1.0, 1.0)));
 
         // We "lock" the client to client.player_id, so for updates to that player only,
         // there is more client-specific logic.
@@ -65,8 +58,10 @@ pub fn apply_server_update<UpdateView, UpdateAudio, UpdateServer, EnqueueTerrain
         update_view(view::update::MoveCamera(position));
       },
       protocol::ServerToClient::UpdateMob(id, bounds) => {
-        let mesh = to_triangles(&bounds, &Color4::of_rgba(1.0, 0.0, 0.0, 1.0));
-        update_view(view::update::UpdateMob(id, mesh));
+        update_view(view::update::UpdateMob(id, bounds, Color4::of_rgba(1.0, 0.0, 0.0, 1.0)));
+      },
+      protocol::ServerToClient::RemoveMob(id) => {
+        update_view(view::update::RemoveMob(id));
       },
       protocol::ServerToClient::UpdateSun(fraction) => {
         update_view(view::update::SetSun(
@@ -105,45 +100,40 @@ pub fn apply_server_update<UpdateView, UpdateAudio, UpdateServer, EnqueueTerrain
             update_audio(audio_thread::Message::PlayOneShot(audio_loader::SoundId::Footstep(idx)));
           }
         }
-      }
+      },
+      protocol::ServerToClient::Damaged(target, health) => {
+        if target == protocol::Combatant::Player(client.player_id) {
+          info!("Took damage; {} health left", health);
+        }
+      },
+      protocol::ServerToClient::Died(target) => {
+        match target {
+          protocol::Combatant::Player(id) if id == client.player_id => {
+            info!("You died.");
+          },
+          _ => {
+            debug!("{:?} died", target);
+          },
+        }
+      },
+      protocol::ServerToClient::Respawned(id, health) => {
+        if id == client.player_id {
+          info!("Respawned with {} health", health);
+        }
+      },
+      protocol::ServerToClient::UpdateInventory(mut materials) => {
+        materials.sort_by_key(|&(material, _)| material as u32);
+        info!("Inventory: {:?}", materials);
+        *client.inventory.lock().unwrap() = materials;
+      },
+      protocol::ServerToClient::Kicked(reason) => {
+        warn!("Kicked by the server: {}", reason);
+        *client.disconnected.lock().unwrap() = Some(format!("Kicked by the server: {}", reason));
+      },
+      protocol::ServerToClient::ShuttingDown { reason } => {
+        info!("The server is shutting down: {}", reason);
+        *client.disconnected.lock().unwrap() = Some(format!("The server shut down: {}", reason));
+      },
     }
   })
 }
-
-fn to_triangles(
-  bounds: &Aabb3<f32>,
-  c: &Color4<f32>,
-) -> [ColoredVertex; TRIANGLE_VERTICES_PER_BOX as usize] {
-  let (x1, y1, z1) = (bounds.min.x, bounds.min.y, bounds.min.z);
-  let (x2, y2, z2) = (bounds.max.x, bounds.max.y, bounds.max.z);
-
-  let vtx = |x, y, z| {
-    ColoredVertex {
-      position: Point3::new(x, y, z),
-      color: *c,
-    }
-  };
-
-  // Remember: x increases to the right, y increases up, and z becomes more
-  // negative as depth from the viewer increases.
-  [
-    // front
-    vtx(x1, y1, z2), vtx(x2, y2, z2), vtx(x1, y2, z2),
-    vtx(x1, y1, z2), vtx(x2, y1, z2), vtx(x2, y2, z2),
-    // left
-    vtx(x1, y1, z1), vtx(x1, y2, z2), vtx(x1, y2, z1),
-    vtx(x1, y1, z1), vtx(x1, y1, z2), vtx(x1, y2, z2),
-    // top
-    vtx(x1, y2, z1), vtx(x2, y2, z2), vtx(x2, y2, z1),
-    vtx(x1, y2, z1), vtx(x1, y2, z2), vtx(x2, y2, z2),
-    // back
-    vtx(x1, y1, z1), vtx(x2, y2, z1), vtx(x2, y1, z1),
-    vtx(x1, y1, z1), vtx(x1, y2, z1), vtx(x2, y2, z1),
-    // right
-    vtx(x2, y1, z1), vtx(x2, y2, z2), vtx(x2, y1, z2),
-    vtx(x2, y1, z1), vtx(x2, y2, z1), vtx(x2, y2, z2),
-    // bottom
-    vtx(x1, y1, z1), vtx(x2, y1, z2), vtx(x1, y1, z2),
-    vtx(x1, y1, z1), vtx(x2, y1, z1), vtx(x2, y1, z2),
-  ]
-}
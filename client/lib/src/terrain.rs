@@ -10,6 +10,7 @@ use common::{fnv_set, fnv_map};
 use common::id_allocator;
 use common::surroundings_loader;
 use common::voxel;
+use common::world_position;
 
 use chunk;
 use chunk_stats;
@@ -95,7 +96,7 @@ impl T {
     rng               : &mut Rng,
     chunk_stats       : &mut chunk_stats::T,
     update_view       : &mut UpdateView,
-    player_position   : &cgmath::Point3<f32>,
+    player_position   : &world_position::T,
   ) where
     UpdateView : FnMut(view::update::T),
     Rng        : rand::Rng,
@@ -224,7 +225,7 @@ impl T {
   #[inline(never)]
   fn load_voxel<UpdateChunk>(
     &mut self,
-    player_position  : &cgmath::Point3<f32>,
+    player_position  : &world_position::T,
     voxel            : voxel::T,
     bounds           : &voxel::bounds::T,
     mut update_chunk : UpdateChunk,
@@ -312,7 +313,7 @@ impl T {
     rng               : &mut Rng,
     chunk_stats       : &mut chunk_stats::T,
     update_view       : &mut UpdateView,
-    player_position   : &cgmath::Point3<f32>,
+    player_position   : &world_position::T,
     voxel_updates     : Vec<(voxel::bounds::T, voxel::T)>,
     time_requested    : Option<u64>,
   ) where
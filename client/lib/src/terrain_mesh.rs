@@ -6,9 +6,9 @@ use isosurface_extraction::dual_contouring;
 use num::iter::range_inclusive;
 use rand;
 use std::sync::Mutex;
-use stopwatch;
 
 use common::id_allocator;
+use common::trace;
 use common::voxel;
 // TODO: Move the server-only parts to the server, like BLOCK_WIDTH and sample_info.
 
@@ -126,7 +126,7 @@ pub fn generate<Rng: rand::Rng>(
   rng             : &mut Rng,
 ) -> view::chunked_terrain::T
 {
-  stopwatch::time("terrain_mesh::generate", || {
+  trace::time("terrain_mesh::generate", || {
     let lg_edge_samples = lod.lg_edge_samples();
     let lg_sample_size = lod.lg_sample_size();
 
@@ -135,6 +135,19 @@ pub fn generate<Rng: rand::Rng>(
     let mut materials = Vec::new();
     let mut grass = terrain_mesh::Grass::new();
 
+    // Vertices are stored relative to the chunk's low corner, so the renderer doesn't lose any more precision
+    // far from the origin. Dual contouring still produces them as f32 world coordinates, so the terrain itself
+    // stays quantized to f32 precision out there.
+    let anchor =
+      Point3::new(
+        chunk_position.as_pnt().x << chunk::LG_WIDTH,
+        chunk_position.as_pnt().y << chunk::LG_WIDTH,
+        chunk_position.as_pnt().z << chunk::LG_WIDTH,
+      );
+    let anchored = |p: Point3<f32>| {
+      Point3::new(p.x - anchor.x as f32, p.y - anchor.y as f32, p.z - anchor.z as f32)
+    };
+
     let low = *chunk_position.as_pnt();
     let high = low + (&Vector3::new(1, 1, 1));
     let low =
@@ -172,7 +185,9 @@ pub fn generate<Rng: rand::Rng>(
               &edge,
               &mut |polygon: dual_contouring::polygon::T<voxel::Material>| {
                 let polygon_offset = vertex_coordinates.len();
-                vertex_coordinates.push(tri(polygon.vertices[0], polygon.vertices[1], polygon.vertices[2]));
+                vertex_coordinates.push(
+                  tri(anchored(polygon.vertices[0]), anchored(polygon.vertices[1]), anchored(polygon.vertices[2])),
+                );
                 normals.push(tri(polygon.normals[0], polygon.normals[1], polygon.normals[2]));
                 materials.push(polygon.material as i32);
 
@@ -208,7 +223,7 @@ pub fn generate<Rng: rand::Rng>(
 
     let chunk_allocator = &mut *chunk_allocator.lock().unwrap();
     chunk_stats.add(vertex_coordinates.len());
-    chunked_terrain::of_parts(chunk_allocator, vertex_coordinates, normals, materials, grass)
+    chunked_terrain::of_parts(chunk_allocator, anchor, vertex_coordinates, normals, materials, grass)
   })
 }
 
@@ -1,12 +1,12 @@
 //! The main thread that processes updates from the client and the server and dispatches updates to other systems.
 
 use std::sync::Mutex;
-use stopwatch;
 use time;
 
 use common::protocol;
 use common::surroundings_loader;
 use common::surroundings_loader::LoadType;
+use common::trace;
 
 use audio_thread;
 use chunk;
@@ -44,16 +44,16 @@ pub fn update_thread<RecvServer, UpdateView0, UpdateView1, UpdateAudio, UpdateSe
     if should_quit {
       break 'update_loop
     } else {
-      stopwatch::time("update_iteration", || {
-        stopwatch::time("process_server_updates", || {
+      trace::time("update_iteration", || {
+        trace::time("process_server_updates", || {
           process_server_updates(client, recv_server, update_view0, update_audio, update_server, enqueue_terrain_load);
         });
 
-        stopwatch::time("update_surroundings", || {
+        trace::time("update_surroundings", || {
           update_surroundings(client, &mut chunk_stats, update_view1, update_server);
         });
 
-        stopwatch::time("process_voxel_updates", || {
+        trace::time("process_voxel_updates", || {
           process_voxel_updates(client, &mut chunk_stats, update_view1);
         });
       })
@@ -61,7 +61,9 @@ pub fn update_thread<RecvServer, UpdateView0, UpdateView1, UpdateAudio, UpdateSe
   }
 
   debug!("Printing chunk stats");
-  chunk_stats.output_to("vram_chunk_loads.out");
+  if let Err(err) = chunk_stats.output_to("vram_chunk_loads.csv") {
+    warn!("Couldn't write chunk stats: {}", err);
+  }
 }
 
 #[inline(never)]
@@ -107,7 +109,7 @@ fn update_surroundings<UpdateView, UpdateServer>(
       );
     match load_type {
       LoadType::Load => {
-        stopwatch::time("update_thread.load_chunk", || {
+        trace::time("update_thread.load_chunk", || {
           trace!("Loading distance {}", distance);
           let new_lod = lod::of_distance(distance as u32);
           let load_state = client.terrain.lock().unwrap().load_state(&chunk_position);
@@ -119,7 +121,7 @@ fn update_surroundings<UpdateView, UpdateServer>(
         })
       },
       LoadType::Downgrade => {
-        stopwatch::time("update_thread.update_chunk", || {
+        trace::time("update_thread.update_chunk", || {
           let new_lod = lod::of_distance(distance as u32);
           let load_state = client.terrain.lock().unwrap().load_state(&chunk_position);
           let is_downgrade = load_state.map(|lod| new_lod < lod) == Some(true);
@@ -131,7 +133,7 @@ fn update_surroundings<UpdateView, UpdateServer>(
         })
       },
       LoadType::Unload => {
-        stopwatch::time("update_thread.unload", || {
+        trace::time("update_thread.unload", || {
           client.terrain.lock().unwrap().unload(update_view, &chunk_position);
         })
       },
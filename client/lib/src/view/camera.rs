@@ -8,10 +8,19 @@ use std::f32::consts::PI;
 use yaglw::gl_context::GLContext;
 use yaglw::shader::Shader;
 
+use common::world_position;
+
+/// The origin everything is drawn relative to snaps to a grid this wide, as a power of two,
+/// so the camera's never more than half this far from it in any dimension.
+const LG_ORIGIN_SPACING: u32 = 10;
+
 /// T representation as 3 distinct matrices, as well as a position + two rotations.
 pub struct T {
   #[allow(missing_docs)]
-  pub position: Point3<f32>,
+  pub position: world_position::T,
+  /// Everything's drawn relative to this point near the camera, since `f32`s far from the world's origin are too
+  /// imprecise to draw with.
+  pub origin: Point3<i32>,
   #[allow(missing_docs)]
   lateral_rotation: f32,
   #[allow(missing_docs)]
@@ -34,6 +43,7 @@ pub struct T {
 pub fn unit() -> T {
   T {
     position          : Point3::new(0.0, 0.0, 0.0),
+    origin            : Point3::new(0, 0, 0),
     lateral_rotation  : 0.0,
     vertical_rotation : 0.0,
 
@@ -49,10 +59,27 @@ impl T {
     self.fov * self.rotation * self.translation
   }
 
-  #[allow(missing_docs)]
-  pub fn translate_to(&mut self, p: Point3<f32>) {
+  /// Move the camera, returning true if the origin moved with it.
+  pub fn translate_to(&mut self, p: world_position::T) -> bool {
+    let spacing = (1 << LG_ORIGIN_SPACING) as f64;
+    let snap = |x: f64| ((x / spacing).round() * spacing) as i32;
+    let origin = Point3::new(snap(p.x), snap(p.y), snap(p.z));
+    let origin_moved = origin != self.origin;
+
     self.position = p;
-    self.translation = Matrix4::from_translation(-p.to_vec());
+    self.origin = origin;
+    self.translation = Matrix4::from_translation(-self.relative_position().to_vec());
+    origin_moved
+  }
+
+  #[allow(missing_docs)]
+  pub fn origin_position(&self) -> world_position::T {
+    Point3::new(self.origin.x as f64, self.origin.y as f64, self.origin.z as f64)
+  }
+
+  /// The camera's position relative to the origin.
+  pub fn relative_position(&self) -> Point3<f32> {
+    world_position::relative(&self.origin_position(), &self.position)
   }
 
   /// Rotate about a given vector, by `r` radians.
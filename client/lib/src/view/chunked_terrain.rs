@@ -35,7 +35,9 @@ impl Grass {
 pub struct T {
   // Every vector should be the same length
 
-  /// Position of each vertex.
+  /// The integer world position every vertex is relative to.
+  pub anchor: Point3<i32>,
+  /// Position of each vertex, relative to `anchor`.
   pub vertex_coordinates: Vec<Chunk<terrain_mesh::Triangle<Point3<f32>>>>,
   /// Vertex normals. These should be normalized!
   pub normals: Vec<terrain_buffers::Chunk<terrain_mesh::Triangle<Vector3<f32>>>>,
@@ -75,6 +77,7 @@ fn zero_pad_to<T>(v: &mut Vec<T>, len: usize) {
 #[allow(missing_docs)]
 pub fn of_parts(
   id_allocator  : &mut id_allocator::T<entity::id::Terrain>,
+  anchor        : Point3<i32>,
   mut vertices  : Vec<terrain_mesh::Triangle<Point3<GLfloat>>>,
   mut normals   : Vec<terrain_mesh::Triangle<Vector3<GLfloat>>>,
   mut materials : Vec<GLint>,
@@ -109,6 +112,7 @@ pub fn of_parts(
     }
   };
   T {
+    anchor             : anchor,
     vertex_coordinates : unsafe { Vec::from_raw_parts(vertices_ptr  as *mut _, vec_len, vec_len) },
     normals            : unsafe { Vec::from_raw_parts(normals_ptr   as *mut _, vec_len, vec_len) },
     materials          : unsafe { Vec::from_raw_parts(materials_ptr as *mut _, vec_len, vec_len) },
@@ -5,7 +5,9 @@ use yaglw::vertex_buffer::{GLArray, GLBuffer, VertexAttribData};
 use yaglw::vertex_buffer::{DrawMode, GLType};
 use yaglw::gl_context::GLContext;
 
+use common::color::Color4;
 use common::fnv_map;
+use common::world_position;
 
 use vertex::ColoredVertex;
 use view;
@@ -17,6 +19,8 @@ pub const VERTICES_PER_MOB: usize = 36;
 pub struct T<'a> {
   id_to_index: fnv_map::T<view::entity::id::Mob, usize>,
   index_to_id: Vec<view::entity::id::Mob>,
+  /// The bounds and color of each mob, by index, to redraw them when the origin moves.
+  index_to_box: Vec<(world_position::Bounds, Color4<f32>)>,
 
   triangles: GLArray<'a, ColoredVertex>,
 }
@@ -32,6 +36,7 @@ pub fn new<'a, 'b>(
   T {
     id_to_index: fnv_map::new(),
     index_to_id: Vec::new(),
+    index_to_box: Vec::new(),
 
     triangles: GLArray::new(
       gl,
@@ -53,12 +58,16 @@ impl<'a> T<'a> {
     &mut self,
     gl: &mut GLContext,
     id: view::entity::id::Mob,
-    triangles: &[ColoredVertex; VERTICES_PER_MOB],
+    bounds: &world_position::Bounds,
+    color: Color4<f32>,
+    origin: &world_position::T,
   ) -> bool {
+    let triangles = &ColoredVertex::cuboid(&world_position::relative_bounds(origin, bounds), color);
     match self.id_to_index.entry(id) {
       Entry::Vacant(entry) => {
         entry.insert(self.index_to_id.len());
         self.index_to_id.push(id);
+        self.index_to_box.push((*bounds, color));
 
         self.triangles.buffer.byte_buffer.bind(gl);
         self.triangles.push(gl, triangles);
@@ -66,6 +75,7 @@ impl<'a> T<'a> {
       },
       Entry::Occupied(entry) => {
         let idx = *entry.get();
+        self.index_to_box[idx] = (*bounds, color);
         self.triangles.buffer.byte_buffer.bind(gl);
         self.triangles.buffer.update(gl, idx * VERTICES_PER_MOB, triangles);
         false
@@ -73,6 +83,34 @@ impl<'a> T<'a> {
     }
   }
 
+  /// Remove a mob from VRAM, if it's loaded.
+  pub fn swap_remove(&mut self, gl: &mut GLContext, id: view::entity::id::Mob) {
+    let idx =
+      match self.id_to_index.remove(&id) {
+        None => return,
+        Some(idx) => idx,
+      };
+    let swapped_id = self.index_to_id[self.index_to_id.len() - 1];
+    self.index_to_id.swap_remove(idx);
+    self.index_to_box.swap_remove(idx);
+
+    if id != swapped_id {
+      self.id_to_index.insert(swapped_id, idx);
+    }
+
+    self.triangles.buffer.byte_buffer.bind(gl);
+    self.triangles.buffer.swap_remove(gl, idx * VERTICES_PER_MOB, VERTICES_PER_MOB);
+  }
+
+  /// Redraw every mob relative to a new origin.
+  pub fn rebase(&mut self, gl: &mut GLContext, origin: &world_position::T) {
+    self.triangles.buffer.byte_buffer.bind(gl);
+    for (idx, &(ref bounds, color)) in self.index_to_box.iter().enumerate() {
+      let triangles = ColoredVertex::cuboid(&world_position::relative_bounds(origin, bounds), color);
+      self.triangles.buffer.update(gl, idx * VERTICES_PER_MOB, &triangles);
+    }
+  }
+
   /// Draw all the mobs.
   /// N.B. This does not bind any shaders.
   pub fn draw(&self, gl: &mut GLContext) {
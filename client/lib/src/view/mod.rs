@@ -139,6 +139,11 @@ pub fn new<'a>(
     &mut texture_unit_alloc,
     &mut shaders.terrain_shader.shader,
   );
+  terrain_buffers.bind_anchors(
+    &mut gl,
+    &mut texture_unit_alloc,
+    &mut shaders.terrain_shader.shader,
+  );
   terrain_buffers.bind_vertex_positions(
     &mut gl,
     &mut texture_unit_alloc,
@@ -149,6 +154,11 @@ pub fn new<'a>(
     &mut texture_unit_alloc,
     &mut shaders.grass_billboard.shader,
   );
+  terrain_buffers.bind_anchors(
+    &mut gl,
+    &mut texture_unit_alloc,
+    &mut shaders.grass_billboard.shader,
+  );
 
   let mob_buffers = mob_buffers::new(&mut gl, &shaders.mob_shader);
   let player_buffers = player_buffers::new(&mut gl, &shaders.mob_shader);
@@ -233,8 +243,7 @@ pub fn new<'a>(
       let mut camera = camera::unit();
       // Initialize the projection matrix.
       camera.fov = cgmath::perspective(fovy, aspect, near_clip, far_clip);
-      // TODO: This should use player rotation from the server.
-      camera.rotate_lateral(std::f32::consts::PI / 2.0);
+      // The server tells us which way to face when we join.
       camera
     },
 
@@ -6,7 +6,9 @@ use yaglw::vertex_buffer::{DrawMode, GLType};
 use yaglw::gl_context::GLContext;
 
 use common::entity;
+use common::color::Color4;
 use common::fnv_map;
+use common::world_position;
 
 use vertex::ColoredVertex;
 use view;
@@ -18,6 +20,8 @@ pub const VERTICES_PER_PLAYER: usize = 36;
 pub struct T<'a> {
   id_to_index: fnv_map::T<entity::id::Player, usize>,
   index_to_id: Vec<entity::id::Player>,
+  /// The bounds and color of each player, by index, to redraw them when the origin moves.
+  index_to_box: Vec<(world_position::Bounds, Color4<f32>)>,
 
   triangles: GLArray<'a, ColoredVertex>,
 }
@@ -33,6 +37,7 @@ pub fn new<'a, 'b>(
   T {
     id_to_index: fnv_map::new(),
     index_to_id: Vec::new(),
+    index_to_box: Vec::new(),
 
     triangles: GLArray::new(
       gl,
@@ -54,12 +59,16 @@ impl<'a> T<'a> {
     &mut self,
     gl: &mut GLContext,
     id: entity::id::Player,
-    triangles: &[ColoredVertex; VERTICES_PER_PLAYER],
+    bounds: &world_position::Bounds,
+    color: Color4<f32>,
+    origin: &world_position::T,
   ) -> bool {
+    let triangles = &ColoredVertex::cuboid(&world_position::relative_bounds(origin, bounds), color);
     match self.id_to_index.entry(id) {
       Entry::Vacant(entry) => {
         entry.insert(self.index_to_id.len());
         self.index_to_id.push(id);
+        self.index_to_box.push((*bounds, color));
 
         self.triangles.buffer.byte_buffer.bind(gl);
         assert!(self.triangles.push(gl, triangles));
@@ -67,6 +76,7 @@ impl<'a> T<'a> {
       },
       Entry::Occupied(entry) => {
         let idx = *entry.get();
+        self.index_to_box[idx] = (*bounds, color);
         self.triangles.buffer.byte_buffer.bind(gl);
         self.triangles.buffer.update(gl, idx * VERTICES_PER_PLAYER, triangles);
         false
@@ -74,6 +84,15 @@ impl<'a> T<'a> {
     }
   }
 
+  /// Redraw every player relative to a new origin.
+  pub fn rebase(&mut self, gl: &mut GLContext, origin: &world_position::T) {
+    self.triangles.buffer.byte_buffer.bind(gl);
+    for (idx, &(ref bounds, color)) in self.index_to_box.iter().enumerate() {
+      let triangles = ColoredVertex::cuboid(&world_position::relative_bounds(origin, bounds), color);
+      self.triangles.buffer.update(gl, idx * VERTICES_PER_PLAYER, &triangles);
+    }
+  }
+
   /// Draw all the mobs.
   /// N.B. This does not bind any shaders.
   pub fn draw(&self, gl: &mut GLContext) {
@@ -12,11 +12,19 @@ use view::light::{set_sun, set_ambient_light};
 fn set_eye_position(shader: &mut yaglw::shader::Shader, camera: &view::camera::T) {
   unsafe {
     let uniform = shader.get_uniform_location("eye_position");
-    let ptr = &camera.position as *const _ as *const _;
+    let position = camera.relative_position();
+    let ptr = &position as *const _ as *const _;
     gl::Uniform3fv(uniform, 1, ptr);
   }
 }
 
+fn set_origin(shader: &mut yaglw::shader::Shader, camera: &view::camera::T) {
+  unsafe {
+    let uniform = shader.get_uniform_location("origin");
+    gl::Uniform3i(uniform, camera.origin.x, camera.origin.y, camera.origin.z);
+  }
+}
+
 fn set_clip(shader: &mut yaglw::shader::Shader, near: f32, far: f32) {
   unsafe {
     let uniform = shader.get_uniform_location("near_clip");
@@ -37,6 +45,7 @@ fn draw_backdrop(
 
   set_sun(&mut rndr.shaders.sky.shader, &mut rndr.gl, &rndr.sun);
   set_eye_position(&mut rndr.shaders.sky.shader, &rndr.camera);
+  set_origin(&mut rndr.shaders.sky.shader, &rndr.camera);
 
   unsafe {
     let time_ms_uniform = rndr.shaders.sky.shader.get_uniform_location("time_ms");
@@ -70,6 +79,7 @@ fn draw_grass_billboards(
   set_camera(&mut rndr.shaders.grass_billboard.shader, &mut rndr.gl, &rndr.camera);
   set_clip(&mut rndr.shaders.grass_billboard.shader, rndr.near_clip, rndr.far_clip);
   set_eye_position(&mut rndr.shaders.grass_billboard.shader, &rndr.camera);
+  set_origin(&mut rndr.shaders.grass_billboard.shader, &rndr.camera);
   set_sun(&mut rndr.shaders.grass_billboard.shader, &mut rndr.gl, &rndr.sun);
   let alpha_threshold_uniform =
     rndr.shaders.grass_billboard.shader.get_uniform_location("alpha_threshold");
@@ -100,6 +110,7 @@ pub fn render(
   set_camera(&mut rndr.shaders.terrain_shader.shader, &mut rndr.gl, &rndr.camera);
   set_clip(&mut rndr.shaders.terrain_shader.shader, rndr.near_clip, rndr.far_clip);
   set_eye_position(&mut rndr.shaders.terrain_shader.shader, &rndr.camera);
+  set_origin(&mut rndr.shaders.terrain_shader.shader, &rndr.camera);
   set_sun(&mut rndr.shaders.terrain_shader.shader, &mut rndr.gl, &rndr.sun);
   rndr.terrain_buffers.draw(&mut rndr.gl);
 
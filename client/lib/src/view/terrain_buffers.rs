@@ -57,6 +57,11 @@ pub struct T<'a> {
   vertex_positions: BufferTexture<'a, Chunk<Triangle<Point3<GLfloat>>>>,
   normals: BufferTexture<'a, Chunk<Triangle<Vector3<GLfloat>>>>,
   materials: BufferTexture<'a, Chunk<GLint>>,
+
+  // Per-chunk buffers
+
+  /// The integer world position each chunk's vertices are relative to.
+  anchors: BufferTexture<'a, Point3<GLint>>,
 }
 
 /// Phantom type for this buffer.
@@ -98,6 +103,7 @@ pub fn new<'a, 'b>(
     vertex_positions: BufferTexture::new(gl, gl::R32F, CHUNK_BUDGET),
     normals: BufferTexture::new(gl, gl::R32F, CHUNK_BUDGET),
     materials: BufferTexture::new(gl, gl::R32UI, CHUNK_BUDGET),
+    anchors: BufferTexture::new(gl, gl::R32I, CHUNK_BUDGET),
   }
 }
 
@@ -161,11 +167,23 @@ impl<'a> T<'a> {
     self.bind(texture_unit_alloc, shader, "materials", self.materials.handle.gl_id);
   }
 
+  #[allow(missing_docs)]
+  pub fn bind_anchors(
+    &self,
+    gl: &mut GLContext,
+    texture_unit_alloc: &mut id_allocator::T<TextureUnit>,
+    shader: &mut yaglw::shader::Shader,
+  ) {
+    shader.use_shader(gl);
+    self.bind(texture_unit_alloc, shader, "anchors", self.anchors.handle.gl_id);
+  }
+
   /// Add a series of entites into VRAM.
   pub fn push(
     &mut self,
     gl        : &mut GLContext,
     chunk_id  : entity::id::Terrain,
+    anchor    : &Point3<GLint>,
     vertices  : &Chunk<Triangle<Point3<GLfloat>>>,
     normals   : &Chunk<Triangle<Vector3<GLfloat>>>,
     materials : &Chunk<GLint>,
@@ -193,6 +211,10 @@ impl<'a> T<'a> {
     let success = self.materials.buffer.push(gl, materials);
     assert!(success);
 
+    self.anchors.buffer.byte_buffer.bind(gl);
+    let success = self.anchors.buffer.push(gl, &[*anchor]);
+    assert!(success);
+
     self.length += 1;
   }
 
@@ -231,6 +253,9 @@ impl<'a> T<'a> {
     self.materials.buffer.byte_buffer.bind(gl);
     self.materials.buffer.swap_remove(gl, idx, 1);
 
+    self.anchors.buffer.byte_buffer.bind(gl);
+    self.anchors.buffer.swap_remove(gl, idx, 1);
+
     r
   }
 
@@ -7,12 +7,12 @@ use sdl2::event::Event;
 use sdl2::video;
 use sdl2_sys;
 use std;
-use stopwatch;
 use time;
 use yaglw::gl_context::GLContext;
 
 use common::interval_timer::IntervalTimer;
 use common::protocol;
+use common::trace;
 
 use client;
 use hud::make_hud;
@@ -103,13 +103,18 @@ pub fn view_thread<Recv0, Recv1, UpdateServer>(
 
   loop {
     let view_iteration =
-      stopwatch::time("view_iteration", || {
+      trace::time("view_iteration", || {
         let now = time::precise_time_ns();
         if now - last_update >= render_interval {
           warn!("{:?}ms since last view update", (now - last_update) / 1000000);
         }
         last_update = now;
 
+        if let Some(ref reason) = *client.disconnected.lock().unwrap() {
+          println!("Disconnected. {}", reason);
+          return ViewIteration::Quit
+        }
+
         event_pump.pump_events();
         let events: Vec<Event> = sdl_event.peek_events(1 << 6);
         sdl_event.flush_events(0, std::u32::MAX);
@@ -136,7 +141,7 @@ pub fn view_thread<Recv0, Recv1, UpdateServer>(
           sdl.mouse().warp_mouse_in_window(&window, window_size.x / 2, window_size.y / 2);
         }
 
-        stopwatch::time("apply_updates", || {
+        trace::time("apply_updates", || {
           let start = time::precise_time_ns();
           loop {
             if let Some(update) = recv0() {
@@ -156,7 +161,7 @@ pub fn view_thread<Recv0, Recv1, UpdateServer>(
 
         let renders = render_timer.update(time::precise_time_ns());
         if renders > 0 {
-          stopwatch::time("render", || {
+          trace::time("render", || {
             view::render::render(&mut view);
             // swap buffers
             window.gl_swap_window();
@@ -1,29 +1,32 @@
 //! Define the updates passed from the client to the view.
 
-use cgmath::Point3;
-use stopwatch;
+use cgmath::{Vector2};
 
 use terrain_mesh;
-use vertex::ColoredVertex;
 use view;
 
+use common::color::Color4;
 use common::index;
+use common::trace;
+use common::world_position;
 
 use super::chunked_terrain;
 use super::entity;
 use super::light;
-use super::mob_buffers::VERTICES_PER_MOB;
-use super::player_buffers::VERTICES_PER_PLAYER;
 
 /// Messages from the client to the view.
 pub enum T {
   /// Set the camera location.
-  MoveCamera(Point3<f32>),
+  MoveCamera(world_position::T),
+  /// Turn the camera by a lateral and a vertical rotation, in radians.
+  RotateCamera(Vector2<f32>),
 
   /// Update a player mesh.
-  UpdatePlayer(entity::id::Player, [ColoredVertex; VERTICES_PER_PLAYER]),
+  UpdatePlayer(entity::id::Player, world_position::Bounds, Color4<f32>),
   /// Update a mob mesh.
-  UpdateMob(entity::id::Mob, [ColoredVertex; VERTICES_PER_MOB]),
+  UpdateMob(entity::id::Mob, world_position::Bounds, Color4<f32>),
+  /// Remove a mob mesh.
+  RemoveMob(entity::id::Mob),
 
   /// Update the sun.
   SetSun(light::Sun),
@@ -44,13 +47,27 @@ pub use self::T::*;
 pub fn apply_client_to_view(view: &mut view::T, up: T) {
   match up {
     T::MoveCamera(position) => {
-      view.camera.translate_to(position);
+      if view.camera.translate_to(position) {
+        // Mobs and players are drawn relative to the origin, so they need to be redrawn when it moves.
+        let origin = view.camera.origin_position();
+        view.mob_buffers.rebase(&mut view.gl, &origin);
+        view.player_buffers.rebase(&mut view.gl, &origin);
+      }
+    },
+    T::RotateCamera(rotation) => {
+      view.camera.rotate_lateral(rotation.x);
+      view.camera.rotate_vertical(rotation.y);
+    },
+    T::UpdateMob(id, bounds, color) => {
+      let origin = view.camera.origin_position();
+      view.mob_buffers.insert(&mut view.gl, id, &bounds, color, &origin);
     },
-    T::UpdateMob(id, triangles) => {
-      view.mob_buffers.insert(&mut view.gl, id, &triangles);
+    T::RemoveMob(id) => {
+      view.mob_buffers.swap_remove(&mut view.gl, id);
     },
-    T::UpdatePlayer(id, triangles) => {
-      view.player_buffers.insert(&mut view.gl, id, &triangles);
+    T::UpdatePlayer(id, bounds, color) => {
+      let origin = view.camera.origin_position();
+      view.player_buffers.insert(&mut view.gl, id, &bounds, color, &origin);
     },
     T::SetSun(sun) => {
       match view.input_mode {
@@ -61,12 +78,13 @@ pub fn apply_client_to_view(view: &mut view::T, up: T) {
       }
     },
     T::LoadMesh(mesh) => {
-      stopwatch::time("add_chunk", move || {
+      trace::time("add_chunk", move || {
         let mesh = *mesh;
         for i in 0 .. mesh.len() {
           view.terrain_buffers.push(
             &mut view.gl,
             mesh.ids[i],
+            &mesh.anchor,
             &mesh.vertex_coordinates[i],
             &mesh.normals[i],
             &mesh.materials[i],
@@ -0,0 +1,16 @@
+// Terrain positions are stored relative to an integer anchor near them, one anchor per VRAM chunk of polygons,
+// so they stay precise however far they are from the world origin.
+// This has to match terrain_buffers::CHUNK_LENGTH.
+const int POLYGONS_PER_ANCHOR = 32;
+
+uniform isamplerBuffer anchors;
+
+ivec3 anchor_of(int polygon_id) {
+  int anchor_id = (polygon_id / POLYGONS_PER_ANCHOR) * 3;
+  return
+    ivec3(
+      texelFetch(anchors, anchor_id + 0).r,
+      texelFetch(anchors, anchor_id + 1).r,
+      texelFetch(anchors, anchor_id + 2).r
+    );
+}
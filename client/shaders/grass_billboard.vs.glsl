@@ -2,10 +2,13 @@
 
 include(noise.glsl)
 include(adjust_depth_precision.glsl)
+include(anchor.glsl)
 
 uniform float near_clip;
 uniform float far_clip;
 uniform mat4 projection_matrix;
+// The floating origin everything is drawn relative to, near the camera.
+uniform ivec3 origin;
 uniform vec3 eye_position;
 uniform float time_ms;
 
@@ -81,7 +84,10 @@ void main() {
       length(vertices[1] - vertices[2]),
       length(vertices[2] - vertices[0])
     );
-  vec3 root = vertices * vec3(1.0/3.0);
+  ivec3 anchor = anchor_of(polygon_id);
+  vec3 anchored_root = vertices * vec3(1.0/3.0);
+  // Only precise enough for noise.
+  vec3 root = vec3(anchor) + anchored_root;
 
   // Find the normal for the grass by barycentrically interpolating the
   // vertex normals to the root.
@@ -95,7 +101,7 @@ void main() {
   vec3 normal = vertex_normals * vec3(1.0/3.0);
 
   mat4 translation = mat4(1.0);
-  translation[3].xyz = root;
+  translation[3].xyz = vec3(anchor - origin) + anchored_root;
 
   mat3 rotate_normal = between(vec3(0, 1, 0), normal);
   mat4 rotation = mat4(rotate_normal);
@@ -13,6 +13,9 @@ uniform struct Sun {
 const float sun_angular_radius = 3.14/32;
 
 uniform mat4 projection_matrix;
+// The floating origin everything is drawn relative to, near the camera.
+uniform ivec3 origin;
+// Relative to the origin.
 uniform vec3 eye_position;
 
 uniform float time_ms;
@@ -46,15 +49,18 @@ void main() {
   float heights[HEIGHTS] = float[](150, 1000);
   vec3 offsets[HEIGHTS] = vec3[](vec3(12,553,239), vec3(-10, 103, 10004));
 
+  // Clouds are far enough away that this is plenty precise.
+  vec3 world_eye_position = vec3(origin) + eye_position;
+
   vec3 c = vec3(0);
   float alpha = 1;
   for (int i = 0; i < HEIGHTS; ++i) {
     float cloud_height = heights[i];
-    float dist = (cloud_height - eye_position.y) / direction.y;
+    float dist = (cloud_height - world_eye_position.y) / direction.y;
     if (dist <= 0 || dist > 1000000) {
       continue;
     } else {
-      vec3 seed = (eye_position + dist * direction + offsets[i]) / 1000 * vec3(1, 4, 1);
+      vec3 seed = (world_eye_position + dist * direction + offsets[i]) / 1000 * vec3(1, 4, 1);
 
       float depth_alpha = fog_density(dist / 16);
 
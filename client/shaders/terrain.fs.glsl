@@ -11,6 +11,7 @@ uniform vec3 eye_position;
 uniform samplerBuffer positions;
 
 in vec3 world_position;
+in vec3 relative_position;
 in vec3 vs_normal;
 flat in int material;
 
@@ -86,7 +87,7 @@ void main() {
     world_fragment(
       sun.direction,
       sun.intensity,
-      normalize(world_position - eye_position),
+      normalize(relative_position - eye_position),
       ambient_light,
       base_color,
       shininess,
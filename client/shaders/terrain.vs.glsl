@@ -1,34 +1,45 @@
 #version 330 core
 
 include(adjust_depth_precision.glsl)
+include(anchor.glsl)
 
 uniform float near_clip;
 uniform float far_clip;
 uniform mat4 projection_matrix;
+// The floating origin everything is drawn relative to, near the camera.
+uniform ivec3 origin;
 
 uniform samplerBuffer positions;
 uniform samplerBuffer normals;
 uniform isamplerBuffer materials;
 
+// Only precise enough for texturing.
 out vec3 world_position;
+// Relative to the origin.
+out vec3 relative_position;
 out vec3 vs_normal;
 flat out int material;
 
 void main() {
   // Mutiply by 3 because there are 3 components for each normal vector.
   int position_id = gl_VertexID * 3;
-  world_position.x = texelFetch(positions, position_id + 0).r;
-  world_position.y = texelFetch(positions, position_id + 1).r;
-  world_position.z = texelFetch(positions, position_id + 2).r;
+  vec3 anchored_position;
+  anchored_position.x = texelFetch(positions, position_id + 0).r;
+  anchored_position.y = texelFetch(positions, position_id + 1).r;
+  anchored_position.z = texelFetch(positions, position_id + 2).r;
+
+  int face_id = gl_VertexID / 3;
+  ivec3 anchor = anchor_of(face_id);
+  // The integer subtraction is exact, so this is as precise as the anchored position.
+  relative_position = vec3(anchor - origin) + anchored_position;
+  world_position = vec3(anchor) + anchored_position;
 
   int normal_id = position_id;
   vs_normal.x = texelFetch(normals, normal_id + 0).r;
   vs_normal.y = texelFetch(normals, normal_id + 1).r;
   vs_normal.z = texelFetch(normals, normal_id + 2).r;
 
-  int face_id = gl_VertexID / 3;
-
   material = texelFetch(materials, face_id).r;
 
-  gl_Position = adjust_depth_precision(near_clip, far_clip, projection_matrix * vec4(world_position, 1.0));
+  gl_Position = adjust_depth_precision(near_clip, far_clip, projection_matrix * vec4(relative_position, 1.0));
 }
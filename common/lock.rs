@@ -0,0 +1,312 @@
+//! Locks that know their place in a lock hierarchy.
+//!
+//! Every lock is made with a `Level`, and a thread may only wait on a lock whose rank is higher than the rank of
+//! every lock it already holds. If every thread follows that rule, no set of threads can deadlock on these locks.
+//!
+//! Debug builds check the rule: each thread keeps track of the locks it holds, and panics as soon as it's broken,
+//! naming both locks, whether or not a deadlock actually happened this time. Release builds don't check anything,
+//! and these are as cheap as the `std::sync` locks they wrap.
+
+use std;
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+
+/// A place in a lock hierarchy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Level {
+  /// Locks have to be taken in increasing order of rank. Locks with the same rank can't be held together.
+  pub rank : u32,
+  /// What the lock protects, for error messages.
+  pub name : &'static str,
+}
+
+#[cfg(debug_assertions)]
+mod held {
+  use std::cell::RefCell;
+
+  use super::Level;
+
+  thread_local!(
+    /// The locks this thread holds, by address, in the order they were taken.
+    static HELD: RefCell<Vec<(usize, Level)>> = RefCell::new(Vec::new())
+  );
+
+  /// Panic if this thread holds a lock that has to be taken after `level`.
+  pub fn check(level: &Level) {
+    let conflict =
+      HELD.with(|held| {
+        held.borrow().iter()
+          .find(|&&(_, ref held)| held.rank >= level.rank)
+          .map(|&(_, held)| held)
+      });
+    // Panic with `HELD` unborrowed, since the guards being unwound need it.
+    if let Some(held) = conflict {
+      panic!(
+        "Lock order violation: locking {} (rank {}) while holding {} (rank {}) could deadlock",
+        level.name, level.rank, held.name, held.rank,
+      );
+    }
+  }
+
+  pub fn push(id: usize, level: &Level) {
+    HELD.with(|held| held.borrow_mut().push((id, *level)));
+  }
+
+  pub fn pop(id: usize) {
+    HELD.with(|held| {
+      let mut held = held.borrow_mut();
+      // Guards aren't necessarily dropped in the reverse order they were taken.
+      match held.iter().rposition(|&(held, _)| held == id) {
+        None => {},
+        Some(i) => { held.remove(i); },
+      }
+    });
+  }
+}
+
+#[cfg(not(debug_assertions))]
+mod held {
+  use super::Level;
+
+  #[inline(always)]
+  pub fn check(_: &Level) {}
+  #[inline(always)]
+  pub fn push(_: usize, _: &Level) {}
+  #[inline(always)]
+  pub fn pop(_: usize) {}
+}
+
+/// Records a lock as held by this thread until it's dropped.
+struct Held {
+  id : usize,
+}
+
+impl Held {
+  fn new(id: usize, level: &Level) -> Held {
+    held::push(id, level);
+    Held {
+      id : id,
+    }
+  }
+}
+
+impl Drop for Held {
+  fn drop(&mut self) {
+    held::pop(self.id);
+  }
+}
+
+fn wrap<Inner, Outer, Wrap>(result: LockResult<Inner>, wrap: Wrap) -> LockResult<Outer> where
+  Wrap: FnOnce(Inner) -> Outer,
+{
+  match result {
+    Ok(guard) => Ok(wrap(guard)),
+    Err(err) => Err(PoisonError::new(wrap(err.into_inner()))),
+  }
+}
+
+fn wrap_try<Inner, Outer, Wrap>(result: TryLockResult<Inner>, wrap: Wrap) -> TryLockResult<Outer> where
+  Wrap: FnOnce(Inner) -> Outer,
+{
+  match result {
+    Ok(guard) => Ok(wrap(guard)),
+    Err(TryLockError::Poisoned(err)) => Err(TryLockError::Poisoned(PoisonError::new(wrap(err.into_inner())))),
+    Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+  }
+}
+
+/// A `std::sync::Mutex` with a place in the lock hierarchy.
+pub struct Mutex<T: ?Sized> {
+  level : Level,
+  inner : std::sync::Mutex<T>,
+}
+
+#[allow(missing_docs)]
+pub struct MutexGuard<'a, T: ?Sized + 'a> {
+  guard : std::sync::MutexGuard<'a, T>,
+  _held : Held,
+}
+
+impl<T> Mutex<T> {
+  #[allow(missing_docs)]
+  pub fn new(level: Level, t: T) -> Mutex<T> {
+    Mutex {
+      level : level,
+      inner : std::sync::Mutex::new(t),
+    }
+  }
+}
+
+impl<T: ?Sized> Mutex<T> {
+  fn id(&self) -> usize {
+    self as *const Mutex<T> as *const () as usize
+  }
+
+  #[allow(missing_docs)]
+  pub fn level(&self) -> Level {
+    self.level
+  }
+
+  /// Like `std::sync::Mutex::lock`, but checks the lock order in debug builds.
+  pub fn lock(&self) -> LockResult<MutexGuard<T>> {
+    held::check(&self.level);
+    wrap(self.inner.lock(), |guard| MutexGuard { guard: guard, _held: Held::new(self.id(), &self.level) })
+  }
+
+  /// Like `std::sync::Mutex::try_lock`. This never waits, so it can't deadlock, and the lock order isn't checked;
+  /// locks taken while this is held still have to come after it, though.
+  pub fn try_lock(&self) -> TryLockResult<MutexGuard<T>> {
+    wrap_try(self.inner.try_lock(), |guard| MutexGuard { guard: guard, _held: Held::new(self.id(), &self.level) })
+  }
+}
+
+impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
+  type Target = T;
+  fn deref(&self) -> &T {
+    &*self.guard
+  }
+}
+
+impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    &mut *self.guard
+  }
+}
+
+/// A `std::sync::RwLock` with a place in the lock hierarchy. Readers are held to the same order as writers,
+/// since a waiting writer can block new readers.
+pub struct RwLock<T: ?Sized> {
+  level : Level,
+  inner : std::sync::RwLock<T>,
+}
+
+#[allow(missing_docs)]
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+  guard : std::sync::RwLockReadGuard<'a, T>,
+  _held : Held,
+}
+
+#[allow(missing_docs)]
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+  guard : std::sync::RwLockWriteGuard<'a, T>,
+  _held : Held,
+}
+
+impl<T> RwLock<T> {
+  #[allow(missing_docs)]
+  pub fn new(level: Level, t: T) -> RwLock<T> {
+    RwLock {
+      level : level,
+      inner : std::sync::RwLock::new(t),
+    }
+  }
+}
+
+impl<T: ?Sized> RwLock<T> {
+  fn id(&self) -> usize {
+    self as *const RwLock<T> as *const () as usize
+  }
+
+  #[allow(missing_docs)]
+  pub fn level(&self) -> Level {
+    self.level
+  }
+
+  /// Like `std::sync::RwLock::read`, but checks the lock order in debug builds.
+  pub fn read(&self) -> LockResult<RwLockReadGuard<T>> {
+    held::check(&self.level);
+    wrap(self.inner.read(), |guard| RwLockReadGuard { guard: guard, _held: Held::new(self.id(), &self.level) })
+  }
+
+  /// Like `std::sync::RwLock::write`, but checks the lock order in debug builds.
+  pub fn write(&self) -> LockResult<RwLockWriteGuard<T>> {
+    held::check(&self.level);
+    wrap(self.inner.write(), |guard| RwLockWriteGuard { guard: guard, _held: Held::new(self.id(), &self.level) })
+  }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+  type Target = T;
+  fn deref(&self) -> &T {
+    &*self.guard
+  }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+  type Target = T;
+  fn deref(&self) -> &T {
+    &*self.guard
+  }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    &mut *self.guard
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const LOW: Level = Level { rank: 1, name: "low" };
+  const HIGH: Level = Level { rank: 2, name: "high" };
+
+  #[test]
+  fn in_order() {
+    let low = Mutex::new(LOW, 0);
+    let high = RwLock::new(HIGH, 0);
+    let mut low = low.lock().unwrap();
+    let mut high = high.write().unwrap();
+    *low += 1;
+    *high += 1;
+  }
+
+  #[test]
+  fn relock_after_release() {
+    let low = Mutex::new(LOW, ());
+    let high = Mutex::new(HIGH, ());
+    drop(high.lock().unwrap());
+    let _low = low.lock().unwrap();
+    let _high = high.lock().unwrap();
+  }
+
+  #[test]
+  fn released_out_of_order() {
+    let low = Mutex::new(LOW, ());
+    let high = Mutex::new(HIGH, ());
+    let low_guard = low.lock().unwrap();
+    let high_guard = high.lock().unwrap();
+    drop(low_guard);
+    drop(high_guard);
+    let _low = low.lock().unwrap();
+  }
+
+  #[test]
+  fn try_lock_out_of_order() {
+    let low = Mutex::new(LOW, ());
+    let high = Mutex::new(HIGH, ());
+    let _high = high.lock().unwrap();
+    assert!(low.try_lock().is_ok());
+  }
+
+  #[cfg(debug_assertions)]
+  #[test]
+  #[should_panic(expected = "Lock order violation")]
+  fn out_of_order() {
+    let low = Mutex::new(LOW, ());
+    let high = Mutex::new(HIGH, ());
+    let _high = high.lock().unwrap();
+    let _low = low.lock().unwrap();
+  }
+
+  #[cfg(debug_assertions)]
+  #[test]
+  #[should_panic(expected = "Lock order violation")]
+  fn same_rank() {
+    let a = RwLock::new(LOW, ());
+    let b = RwLock::new(LOW, ());
+    let _a = a.read().unwrap();
+    let _b = b.read().unwrap();
+  }
+}
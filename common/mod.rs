@@ -32,8 +32,11 @@ pub mod fnv_set;
 pub mod id_allocator;
 pub mod index;
 pub mod interval_timer;
+pub mod lock;
 pub mod protocol;
 pub mod range_abs;
 pub mod socket;
 pub mod surroundings_loader;
+pub mod trace;
 pub mod voxel;
+pub mod world_position;
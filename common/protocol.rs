@@ -1,12 +1,12 @@
 //! Defines the messages passed between client and server.
 
-use cgmath::{Vector2, Vector3, Point3};
-use collision::{Aabb3};
+use cgmath::{Vector2, Vector3};
 use std::default::Default;
 use std::ops::Add;
 
 use entity;
 use voxel;
+use world_position;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
 /// Unique client ID.
@@ -34,8 +34,9 @@ pub enum ClientToServer {
   Init(String),
   /// Ping
   Ping(ClientId),
-  /// Ask the server to create a new player.
-  AddPlayer(ClientId),
+  /// Ask the server for a player. Players are identified by name, so a returning player
+  /// gets back the body they left.
+  AddPlayer(ClientId, String),
   /// Add a vector the player's acceleration.
   Walk(entity::id::Player, Vector3<f32>),
   /// Rotate the player by some amount.
@@ -53,10 +54,86 @@ pub enum ClientToServer {
     /// The bounds of the voxels to fetch.
     voxels          : Vec<voxel::bounds::T>,
   },
-  /// Brush-remove where the player's looking.
-  Add(entity::id::Player),
-  /// Brush-add at where the player's looking.
-  Remove(entity::id::Player),
+  /// Apply a brush where the player's looking.
+  Brush(entity::id::Player, Brush),
+  /// Revert the player's most recent terrain edit.
+  Undo(entity::id::Player),
+  /// Reapply the player's most recently undone terrain edit.
+  Redo(entity::id::Player),
+  /// Select, copy and paste regions of terrain.
+  Clipboard(entity::id::Player, ClipboardAction),
+  /// Attack whatever the player's looking at.
+  Attack(entity::id::Player),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// The shape of a brush.
+pub enum BrushShape {
+  /// A ball with radius `Brush::size`.
+  Sphere,
+  /// A cube with half-width `Brush::size`.
+  Cube,
+  /// An upright cylinder with radius and half-height `Brush::size`.
+  Cylinder,
+  /// A tree, grown up from where the player's looking. Made of bark and leaves when added.
+  #[allow(missing_docs)]
+  Tree {
+    trunk_radius : f32,
+    trunk_height : f32,
+    leaf_radius  : f32,
+  },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// How a brush changes the terrain.
+pub enum BrushMode {
+  /// Fill the brush with its material.
+  Add,
+  /// Empty out the brush.
+  Subtract,
+  /// Change the material of the terrain inside the brush, but not its shape.
+  Paint,
+  /// Relax the shape of the terrain inside a sphere, rounding off rough edges.
+  Smooth,
+  /// Level the terrain inside an upright cylinder to the top of the targeted voxel.
+  Flatten,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// A terrain edit.
+pub struct Brush {
+  #[allow(missing_docs)]
+  pub shape    : BrushShape,
+  /// The scale of the shape; what exactly this means depends on the shape.
+  pub size     : f32,
+  /// The material to add or paint with.
+  pub material : voxel::Material,
+  #[allow(missing_docs)]
+  pub mode     : BrushMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// How a clipboard is reoriented when it's pasted. It's mirrored first, then rotated.
+pub struct PasteTransform {
+  /// Quarter turns counterclockwise around the y axis.
+  pub quarter_turns : u8,
+  /// Flip along the x axis.
+  pub mirror        : bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Things a player can do with their clipboard.
+pub enum ClipboardAction {
+  /// Mark corner 0 or 1 of the region to copy where the player's looking.
+  SelectCorner(u8),
+  /// Copy the selected region.
+  Copy,
+  /// Paste on top of where the player's looking.
+  Paste(PasteTransform),
+  /// Save the clipboard on the server, under a name.
+  Save(String),
+  /// Replace the clipboard with one saved on the server.
+  Load(String),
 }
 
 /// Why a block is being sent to a client.
@@ -79,6 +156,14 @@ pub enum Collision {
   PlayerMisc(entity::id::Player),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Anything that has health, and can be attacked.
+#[allow(missing_docs)]
+pub enum Combatant {
+  Player(entity::id::Player),
+  Mob(entity::id::Mob),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 /// Messages the server sends to the client.
 pub enum ServerToClient {
@@ -87,13 +172,15 @@ pub enum ServerToClient {
   /// Ping
   Ping,
 
-  /// Complete an AddPlayer request.
-  PlayerAdded(entity::id::Player, Point3<f32>),
+  /// Complete an AddPlayer request, with the player's position and its lateral and vertical rotation.
+  PlayerAdded(entity::id::Player, world_position::T, Vector2<f32>),
 
   /// Update a player's position.
-  UpdatePlayer(entity::id::Player, Aabb3<f32>),
+  UpdatePlayer(entity::id::Player, world_position::Bounds),
   /// Update the client's view of a mob with a given mesh.
-  UpdateMob(entity::id::Mob, Aabb3<f32>),
+  UpdateMob(entity::id::Mob, world_position::Bounds),
+  /// A mob has left the world.
+  RemoveMob(entity::id::Mob),
   /// The sun as a [0, 1) portion of its cycle.
   UpdateSun(f32),
 
@@ -106,4 +193,19 @@ pub enum ServerToClient {
   },
   /// A collision happened.
   Collision(Collision),
+  /// Something was hurt, and has this much health left.
+  Damaged(Combatant, u32),
+  /// Something's health ran out.
+  Died(Combatant),
+  /// A player that died is back in the world, with this much health.
+  Respawned(entity::id::Player, u32),
+  /// The full contents of the client's player's inventory.
+  UpdateInventory(Vec<(voxel::Material, u32)>),
+  /// The server has disconnected this client, for the given reason.
+  Kicked(String),
+  /// The server is going away; nothing more will be sent.
+  ShuttingDown {
+    /// Why, as the operator put it.
+    reason : String,
+  },
 }
@@ -5,9 +5,9 @@
 use cgmath::Point3;
 use std::cmp::max;
 use std::collections::VecDeque;
-use stopwatch;
 
 use cube_shell::cube_diff;
+use trace;
 
 mod surroundings_iter {
   use cgmath::{Point3};
@@ -91,7 +91,7 @@ impl T {
   pub fn updates(&mut self, position: &Point3<i32>) -> Updates {
     let position_changed = self.last_position != Some(*position);
     if position_changed {
-      stopwatch::time("surroundings_loader.extend", || {
+      trace::time("surroundings_loader.extend", || {
         self.to_load = Some(surroundings_iter::new(&position, self.max_load_distance as i32));
         self.last_position.map(|last_position| {
           for &distance in &self.lod_thresholds {
@@ -113,6 +113,16 @@ impl T {
       position: *position,
     }
   }
+
+  /// Every position that might currently be loaded on this loader's behalf,
+  /// e.g. so that an owner going away can unload them all.
+  pub fn loaded_positions(&self) -> Vec<Point3<i32>> {
+    let mut positions: Vec<Point3<i32>> = self.to_recheck.iter().cloned().collect();
+    if let Some(position) = self.last_position {
+      positions.extend(surroundings_iter::new(&position, self.max_load_distance as i32));
+    }
+    positions
+  }
 }
 
 unsafe impl Send for T {}
@@ -135,7 +145,7 @@ impl<'a> Iterator for Updates<'a> {
   type Item = (Point3<i32>, LoadType);
 
   fn next(&mut self) -> Option<Self::Item> {
-    stopwatch::time("surroundings_loader.next", || {
+    trace::time("surroundings_loader.next", || {
       if let Some(position) = self.loader.to_recheck.pop_front() {
         let distance = distance_between(&self.position, &position);
         if distance > self.loader.max_load_distance {
@@ -0,0 +1,239 @@
+//! Optional tracing of timed spans, so stalls show up on a timeline instead of only in `stopwatch`'s averages.
+//!
+//! `time` times a span with `stopwatch` as usual. If the `PLAYFORM_TRACE` environment variable names a file, it also
+//! records the span with its thread and timestamps, and `write` saves everything recorded in Chrome's `trace_event`
+//! JSON format, which chrome://tracing and most flamegraph tools can open. The client and server each write on
+//! shutdown; when they share a process, they share a trace, and whichever finishes last writes the whole thing.
+//! Only the most recent `MAX_SPANS` spans are kept, so tracing a long session doesn't use up all the memory.
+
+use std;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once, ONCE_INIT};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use stopwatch;
+use time;
+
+/// Set this to a file name to turn tracing on.
+pub const ENV_VAR: &'static str = "PLAYFORM_TRACE";
+/// Each thread keeps this many spans to itself before handing them over, so recording rarely takes a lock.
+const THREAD_BUFFER_LENGTH: usize = 1 << 12;
+/// The most spans kept at once, about 32 MB of them. Past this, the oldest are dropped to make room.
+const MAX_SPANS: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy)]
+struct Span {
+  name        : &'static str,
+  thread      : usize,
+  /// Since tracing started.
+  start_ns    : u64,
+  duration_ns : u64,
+}
+
+/// The most recent spans handed over by each thread.
+struct Spans {
+  recent  : VecDeque<Span>,
+  /// How many older spans were dropped to stay under `MAX_SPANS`.
+  dropped : u64,
+}
+
+impl Spans {
+  /// Keep `spans`, dropping the oldest to keep at most `max`.
+  fn extend<I: IntoIterator<Item = Span>>(&mut self, spans: I, max: usize) {
+    self.recent.extend(spans);
+    while self.recent.len() > max {
+      self.recent.pop_front();
+      self.dropped += 1;
+    }
+  }
+}
+
+struct State {
+  path     : PathBuf,
+  start_ns : u64,
+  spans    : Mutex<Spans>,
+}
+
+static INIT: Once = ONCE_INIT;
+/// Set once by `INIT`, if tracing is on, and never freed.
+static mut STATE: *const State = 0 as *const State;
+static NEXT_THREAD: AtomicUsize = ATOMIC_USIZE_INIT;
+
+fn state() -> Option<&'static State> {
+  INIT.call_once(|| {
+    if let Some(path) = std::env::var_os(ENV_VAR) {
+      let state =
+        State {
+          path     : PathBuf::from(path),
+          start_ns : time::precise_time_ns(),
+          spans    : Mutex::new(Spans { recent: VecDeque::new(), dropped: 0 }),
+        };
+      unsafe {
+        STATE = Box::into_raw(Box::new(state));
+      }
+    }
+  });
+  unsafe {
+    STATE.as_ref()
+  }
+}
+
+/// This thread's id in the trace, and the spans it hasn't handed over yet.
+struct ThreadSpans {
+  thread : usize,
+  spans  : Vec<Span>,
+}
+
+impl ThreadSpans {
+  fn hand_over(&mut self) {
+    if let Some(state) = state() {
+      state.spans.lock().unwrap().extend(self.spans.drain(..), MAX_SPANS);
+    }
+  }
+}
+
+impl Drop for ThreadSpans {
+  fn drop(&mut self) {
+    self.hand_over();
+  }
+}
+
+thread_local!(
+  static THREAD_SPANS: RefCell<ThreadSpans> =
+    RefCell::new(
+      ThreadSpans {
+        thread : NEXT_THREAD.fetch_add(1, Ordering::Relaxed),
+        spans  : Vec::new(),
+      }
+    )
+);
+
+/// Time `f` with `stopwatch` under `name`, and trace it if tracing is on.
+pub fn time<T, F: FnOnce() -> T>(name: &'static str, f: F) -> T {
+  let state =
+    match state() {
+      None => return stopwatch::time(name, f),
+      Some(state) => state,
+    };
+
+  let start = time::precise_time_ns();
+  let r = stopwatch::time(name, f);
+  let end = time::precise_time_ns();
+
+  THREAD_SPANS.with(|spans| {
+    let mut spans = spans.borrow_mut();
+    let span =
+      Span {
+        name        : name,
+        thread      : spans.thread,
+        start_ns    : start - state.start_ns,
+        duration_ns : end - start,
+      };
+    spans.spans.push(span);
+    if spans.spans.len() >= THREAD_BUFFER_LENGTH {
+      spans.hand_over();
+    }
+  });
+
+  r
+}
+
+/// Write a `trace_event` JSON object with a complete ("X") event for each span,
+/// noting how many earlier spans were `dropped`, if any.
+fn write_json<W: Write>(w: &mut W, spans: &[Span], dropped: u64) -> io::Result<()> {
+  try!(write!(w, "{{\"displayTimeUnit\":\"ms\","));
+  if dropped > 0 {
+    try!(write!(w, "\"otherData\":{{\"dropped_spans\":\"{}\"}},", dropped));
+  }
+  try!(write!(w, "\"traceEvents\":[\n"));
+  for (i, span) in spans.iter().enumerate() {
+    if i > 0 {
+      try!(write!(w, ",\n"));
+    }
+    try!(
+      write!(
+        w,
+        "{{\"name\":\"{}\",\"cat\":\"stopwatch\",\"ph\":\"X\",\"pid\":0,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}}",
+        span.name.replace('\\', "\\\\").replace('"', "\\\""),
+        span.thread,
+        span.start_ns as f64 / 1000.0,
+        span.duration_ns as f64 / 1000.0,
+      )
+    );
+  }
+  write!(w, "\n]}}\n")
+}
+
+/// If tracing is on, write every span recorded so far, and return where they were written.
+/// Spans still buffered by other threads that haven't finished yet are left out.
+pub fn write() -> io::Result<Option<&'static Path>> {
+  let state =
+    match state() {
+      None => return Ok(None),
+      Some(state) => state,
+    };
+  THREAD_SPANS.with(|spans| spans.borrow_mut().hand_over());
+
+  let (mut spans, dropped) = {
+    let spans = state.spans.lock().unwrap();
+    (spans.recent.iter().cloned().collect::<Vec<_>>(), spans.dropped)
+  };
+  spans.sort_by_key(|span| span.start_ns);
+
+  let file = try!(std::fs::File::create(&state.path));
+  let mut file = io::BufWriter::new(file);
+  try!(write_json(&mut file, &spans, dropped));
+  try!(file.flush());
+  Ok(Some(&state.path))
+}
+
+#[cfg(test)]
+mod test {
+  use std::collections::VecDeque;
+
+  use super::{Span, Spans, write_json};
+
+  #[test]
+  fn oldest_spans_dropped() {
+    let span = |start_ns| Span { name: "span", thread: 0, start_ns: start_ns, duration_ns: 1 };
+    let mut spans = Spans { recent: VecDeque::new(), dropped: 0 };
+    spans.extend((0 .. 3).map(&span), 4);
+    assert_eq!(spans.dropped, 0);
+    spans.extend((3 .. 6).map(&span), 4);
+    assert_eq!(spans.dropped, 2);
+    assert_eq!(spans.recent.iter().map(|span| span.start_ns).collect::<Vec<_>>(), vec!(2, 3, 4, 5));
+  }
+
+  #[test]
+  fn chrome_json() {
+    let spans = [
+      Span { name: "outer", thread: 0, start_ns: 1000, duration_ns: 5500 },
+      Span { name: "in\"ner", thread: 1, start_ns: 2000, duration_ns: 1000 },
+    ];
+    let mut json = Vec::new();
+    write_json(&mut json, &spans, 0).unwrap();
+    assert_eq!(
+      String::from_utf8(json).unwrap(),
+      "{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n\
+       {\"name\":\"outer\",\"cat\":\"stopwatch\",\"ph\":\"X\",\"pid\":0,\"tid\":0,\"ts\":1.000,\"dur\":5.500},\n\
+       {\"name\":\"in\\\"ner\",\"cat\":\"stopwatch\",\"ph\":\"X\",\"pid\":0,\"tid\":1,\"ts\":2.000,\"dur\":1.000}\n\
+       ]}\n"
+    );
+  }
+
+  #[test]
+  fn dropped_spans() {
+    let spans = [Span { name: "kept", thread: 0, start_ns: 0, duration_ns: 1000 }];
+    let mut json = Vec::new();
+    write_json(&mut json, &spans, 3).unwrap();
+    assert_eq!(
+      String::from_utf8(json).unwrap(),
+      "{\"displayTimeUnit\":\"ms\",\"otherData\":{\"dropped_spans\":\"3\"},\"traceEvents\":[\n\
+       {\"name\":\"kept\",\"cat\":\"stopwatch\",\"ph\":\"X\",\"pid\":0,\"tid\":0,\"ts\":0.000,\"dur\":1.000}\n\
+       ]}\n"
+    );
+  }
+}
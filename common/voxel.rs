@@ -11,7 +11,7 @@ pub use voxel_data::impls::surface_vertex::unwrap;
 #[allow(missing_docs)]
 pub type T = voxel_data::impls::surface_vertex::T<Material>;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[allow(missing_docs)]
 /// Terrain materials
 pub enum Material {
@@ -30,6 +30,7 @@ pub mod tree {
   pub type T = voxel_data::tree::T<super::T>;
   pub type Inner = voxel_data::tree::Inner<super::T>;
   pub type Branches = voxel_data::tree::Branches<super::T>;
+  pub type Node = voxel_data::tree::Node<super::T>;
 
   pub fn new() -> T {
     voxel_data::tree::new()
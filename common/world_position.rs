@@ -0,0 +1,76 @@
+//! Absolute positions in the world.
+//!
+//! An `f32` only has 24 bits of precision, so a few thousand blocks from the origin it can't represent small movements,
+//! and anything moving, colliding or being drawn out there jitters. Positions and bounds in the world are `f64`s
+//! instead. Anything relative, like a speed, or the offset between two things near each other, is still an `f32`;
+//! the functions here convert between the two.
+
+use cgmath::{Point3, Vector3};
+use collision::{Aabb3};
+
+/// A position in the world.
+pub type T = Point3<f64>;
+/// The bounds of something in the world.
+pub type Bounds = Aabb3<f64>;
+
+#[allow(missing_docs)]
+pub fn of_f32(p: &Point3<f32>) -> T {
+  Point3::new(p.x as f64, p.y as f64, p.z as f64)
+}
+
+/// Round to the nearest `f32` position. Only for things that are about to be rounded anyway, e.g. terrain lookups.
+pub fn to_f32(p: &T) -> Point3<f32> {
+  Point3::new(p.x as f32, p.y as f32, p.z as f32)
+}
+
+#[allow(missing_docs)]
+pub fn bounds_of_f32(bounds: &Aabb3<f32>) -> Bounds {
+  Aabb3::new(of_f32(&bounds.min), of_f32(&bounds.max))
+}
+
+/// Widen a relative vector, e.g. to move a position by it.
+pub fn vector_of_f32(v: &Vector3<f32>) -> Vector3<f64> {
+  Vector3::new(v.x as f64, v.y as f64, v.z as f64)
+}
+
+/// The vector from `from` to `to`, which should be near each other.
+pub fn offset(from: &T, to: &T) -> Vector3<f32> {
+  let v = *to - *from;
+  Vector3::new(v.x as f32, v.y as f32, v.z as f32)
+}
+
+/// `p` relative to `origin`, which it should be near.
+pub fn relative(origin: &T, p: &T) -> Point3<f32> {
+  let v = offset(origin, p);
+  Point3::new(v.x, v.y, v.z)
+}
+
+/// `bounds` relative to `origin`, which they should be near.
+pub fn relative_bounds(origin: &T, bounds: &Bounds) -> Aabb3<f32> {
+  Aabb3::new(relative(origin, &bounds.min), relative(origin, &bounds.max))
+}
+
+#[cfg(test)]
+mod test {
+  use cgmath::{Point3, Vector3};
+
+  use super::*;
+
+  #[test]
+  fn small_moves_far_away() {
+    let far = Point3::new(1.0e7, -3.0e6, 2.5e7);
+    let moved = far + vector_of_f32(&Vector3::new(0.01, 0.0, -0.01));
+    let v = offset(&far, &moved);
+    assert!((v.x - 0.01).abs() < 1e-6);
+    assert!((v.z + 0.01).abs() < 1e-6);
+    // Whereas in `f32`, the move would be lost entirely.
+    assert_eq!(to_f32(&moved), to_f32(&far));
+  }
+
+  #[test]
+  fn relative_to_nearby_origin() {
+    let origin = Point3::new(16777216.0, 0.0, -16777216.0);
+    let p = Point3::new(origin.x + 0.25, origin.y + 1.5, origin.z - 0.125);
+    assert_eq!(relative(&origin, &p), Point3::new(0.25, 1.5, -0.125));
+  }
+}
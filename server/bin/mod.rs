@@ -15,42 +15,109 @@ use std::borrow::Borrow;
 use std::env;
 use std::sync::Mutex;
 
+use server_lib::admin;
+use server_lib::config;
+
+const USAGE: &'static str =
+  "Usage: server [listen_url] [--config <file>] [--<setting> <value>]...
+
+Settings are read from the config file (server.conf by default), then overridden by any given here:
+  --world <directory>             where the world is saved
+  --seed <number>                 terrain seed for a new world
+  --updates_per_second <number>
+  --sun_tick_ns <number>
+  --player_load_distance <number>
+  --autosave_seconds <number>
+  --terrain_threads <number>      threads generating terrain; around the number of cores is best
+  --terrain_memory_mb <number>    terrain memory budget; regions far from players are dropped beyond it
+  --metrics_port <port>           serve live metrics at http://127.0.0.1:<port>/; 0 (the default) for none
+  --metrics_csv <file>            also append metrics to a CSV file every second";
+
+/// Read the listen URL and settings from the command line.
+fn parse_args() -> Result<(String, config::T), String> {
+  let (config, mut rest) = try!(config::of_args(env::args().skip(1)));
+  if rest.len() > 1 {
+    return Err(format!("Unexpected argument {:?}", rest[1]))
+  }
+
+  let listen_url = rest.pop().unwrap_or_else(|| String::from("ipc:///tmp/server.ipc"));
+  Ok((listen_url, config))
+}
+
 fn main() {
   env_logger::init().unwrap();
 
-  let mut args = env::args();
-  args.next().unwrap();
-  let listen_url = args.next().unwrap_or_else(|| String::from("ipc:///tmp/server.ipc"));
-  assert!(args.next().is_none());
+  let (listen_url, config) =
+    match parse_args() {
+      Ok(args) => args,
+      Err(err) => {
+        println!("{}\n\n{}", err, USAGE);
+        std::process::exit(1);
+      },
+    };
 
   info!("Listening on {}.", listen_url);
+  info!("Using {:?}.", config);
 
   let quit_signal = Mutex::new(false);
+  let admin = admin::new();
 
-  let _quit_thread =
+  let _console_thread =
     unsafe {
       let quit_signal = &quit_signal;
+      let admin = &admin;
       thread_scoped::scoped(move || {
-        wait_for_quit();
+        console(admin);
         *quit_signal.lock().unwrap() = true;
-        // Close all sockets.
-        nanomsg::Socket::terminate();
       })
     };
 
-  server_lib::run(listen_url.borrow(), &quit_signal);
+  if let Err(err) = server_lib::run(listen_url.borrow(), &config, &admin, &quit_signal) {
+    println!("{}", err);
+    std::process::exit(1);
+  }
+  // Everything's saved and every client's been told, so close all sockets.
+  nanomsg::Socket::terminate();
 }
 
-fn wait_for_quit() {
+/// Read admin commands from stdin and pass them to the server, until we're told to quit.
+fn console(admin: &admin::T) {
+  println!("Type `help` for a list of commands.");
   loop {
     let mut line = String::new();
-    std::io::stdin().read_line(&mut line).unwrap();
+    match std::io::stdin().read_line(&mut line) {
+      Ok(0) => {
+        // stdin is closed, so nobody can tell us to quit; keep running until we're killed.
+        info!("Console closed");
+        loop {
+          std::thread::park();
+        }
+      },
+      Ok(_) => {},
+      Err(err) => {
+        println!("Error reading command: {}", err);
+        continue
+      },
+    }
 
-    if line == "quit\n" {
+    let line = line.trim();
+    if line == "quit" || line.starts_with("quit ") {
+      let reason = line["quit".len() ..].trim();
+      if !reason.is_empty() {
+        admin.set_shutdown_reason(String::from(reason));
+      }
       println!("Quitting");
       return
-    } else {
-      println!("Unrecognized command: {:?}", line);
+    }
+
+    match line {
+      "help" => println!("{}", admin::HELP),
+      line => {
+        match admin::parse(line) {
+          Ok(command) => admin.push(command),
+          Err(err) => println!("{}", err),
+        }
+      },
     }
   }
 }
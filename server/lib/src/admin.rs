@@ -0,0 +1,333 @@
+//! Commands an operator can give a running server from its console.
+//! Commands are queued by whoever reads the console, and carried out on a server thread of their own,
+//! so the simulation threads only ever wait on the same locks they'd wait on anyway.
+
+use cgmath::{Point3};
+use std;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use common::protocol;
+use common::trace;
+use common::world_position;
+
+use entity;
+use gaia_pool;
+use mob;
+use mob_behavior;
+use player;
+use server;
+use spawner;
+
+/// What each command does, for the console's `help`.
+pub const HELP: &'static str =
+  "Commands:
+  clients                      list connected clients
+  players                      list players, with their positions
+  kick <player> [reason]       disconnect the client controlling a player
+  tp <player> <x> <y> <z>      teleport a player
+  time <fraction>              set the time of day, as a [0, 1) portion of the sun's cycle
+  time freeze | time unfreeze  stop or restart the sun
+  save                         save changed terrain and players now
+  mobs                         list mobs, with their positions
+  spawn <x> <y> <z> [hostile]  spawn a mob with its lower corner at a point
+  despawn <mob id> | all       remove a mob, or every mob
+  stats                        print timing, LOD and queue statistics
+  quit [reason]                save and shut down, telling players why";
+
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+pub enum Command {
+  Clients,
+  Players,
+  Kick { player: String, reason: String },
+  Teleport(String, world_position::T),
+  SetTime(f32),
+  FreezeTime(bool),
+  Save,
+  Mobs,
+  Spawn { low_corner: world_position::T, hostile: bool },
+  /// Remove the mob with this id, or every mob.
+  Despawn(Option<u32>),
+  Stats,
+}
+
+/// Parse one line typed into the console.
+pub fn parse(line: &str) -> Result<Command, String> {
+  let words: Vec<&str> = line.split_whitespace().collect();
+  let args = if words.is_empty() { &words[..] } else { &words[1..] };
+
+  let number = |word: &str| -> Result<f32, String> {
+    word.parse().map_err(|_| format!("{:?} isn't a number", word))
+  };
+  let coordinate = |word: &str| -> Result<f64, String> {
+    word.parse().map_err(|_| format!("{:?} isn't a number", word))
+  };
+  let point = |words: &[&str]| -> Result<world_position::T, String> {
+    Ok(Point3::new(try!(coordinate(words[0])), try!(coordinate(words[1])), try!(coordinate(words[2]))))
+  };
+  let usage = |usage: &str| -> Result<Command, String> { Err(format!("Usage: {}", usage)) };
+
+  match words.first().map(|word| *word) {
+    None => Err(String::from("Type `help` for a list of commands")),
+    Some("clients") => Ok(Command::Clients),
+    Some("players") => Ok(Command::Players),
+    Some("kick") => {
+      if args.is_empty() {
+        return usage("kick <player> [reason]")
+      }
+      let reason = if args.len() > 1 { args[1..].join(" ") } else { String::from("Kicked by an admin") };
+      Ok(Command::Kick { player: String::from(args[0]), reason: reason })
+    },
+    Some("tp") => {
+      if args.len() != 4 {
+        return usage("tp <player> <x> <y> <z>")
+      }
+      Ok(Command::Teleport(String::from(args[0]), try!(point(&args[1..]))))
+    },
+    Some("time") => {
+      if args.len() != 1 {
+        return usage("time <fraction> | time freeze | time unfreeze")
+      }
+      match args[0] {
+        "freeze" => Ok(Command::FreezeTime(true)),
+        "unfreeze" => Ok(Command::FreezeTime(false)),
+        fraction => {
+          let fraction = try!(number(fraction));
+          if fraction < 0.0 || fraction >= 1.0 {
+            return Err(format!("The time of day must be in [0, 1), not {}", fraction))
+          }
+          Ok(Command::SetTime(fraction))
+        },
+      }
+    },
+    Some("save") => Ok(Command::Save),
+    Some("mobs") => Ok(Command::Mobs),
+    Some("spawn") => {
+      match args.len() {
+        3 => Ok(Command::Spawn { low_corner: try!(point(args)), hostile: false }),
+        4 if args[3] == "hostile" => Ok(Command::Spawn { low_corner: try!(point(&args[.. 3])), hostile: true }),
+        _ => usage("spawn <x> <y> <z> [hostile]"),
+      }
+    },
+    Some("despawn") => {
+      if args.len() != 1 {
+        return usage("despawn <mob id> | despawn all")
+      }
+      match args[0] {
+        "all" => Ok(Command::Despawn(None)),
+        id => id.parse().map(|id| Command::Despawn(Some(id))).map_err(|_| format!("{:?} isn't a mob id", id)),
+      }
+    },
+    Some("stats") => Ok(Command::Stats),
+    Some(command) => Err(format!("Unknown command {:?}; type `help` for a list of commands", command)),
+  }
+}
+
+/// Commands waiting to be carried out, and requests for the server threads to report on themselves.
+pub struct T {
+  commands          : Mutex<VecDeque<Command>>,
+  /// Bumped whenever the server threads should print their stopwatches.
+  stopwatch_request : Mutex<u32>,
+  /// What players are told when the server shuts down.
+  shutdown_reason   : Mutex<Option<String>>,
+}
+
+#[allow(missing_docs)]
+pub fn new() -> T {
+  T {
+    commands          : Mutex::new(VecDeque::new()),
+    stopwatch_request : Mutex::new(0),
+    shutdown_reason   : Mutex::new(None),
+  }
+}
+
+impl T {
+  /// Queue a command to be carried out by the server.
+  pub fn push(&self, command: Command) {
+    self.commands.lock().unwrap().push_back(command);
+  }
+
+  #[allow(missing_docs)]
+  pub fn pop(&self) -> Option<Command> {
+    self.commands.lock().unwrap().pop_front()
+  }
+
+  /// Say why the server is about to shut down.
+  pub fn set_shutdown_reason(&self, reason: String) {
+    *self.shutdown_reason.lock().unwrap() = Some(reason);
+  }
+
+  #[allow(missing_docs)]
+  pub fn shutdown_reason(&self) -> Option<String> {
+    self.shutdown_reason.lock().unwrap().clone()
+  }
+
+  /// The number of stopwatch reports requested so far. A thread should print its stopwatch whenever this changes.
+  pub fn stopwatch_requests(&self) -> u32 {
+    *self.stopwatch_request.lock().unwrap()
+  }
+}
+
+/// The client controlling the player named `name`.
+fn client_of(server: &server::T, name: &str) -> Option<protocol::ClientId> {
+  server.players.lock().unwrap().values()
+    .find(|player| player.name == name)
+    .map(|player| player.client_id)
+}
+
+fn broadcast_sun(server: &server::T) {
+  let fraction = server.sun.lock().unwrap().fraction();
+  for (_, client) in server.clients.lock().unwrap().iter_mut() {
+    client.send(protocol::ServerToClient::UpdateSun(fraction));
+  }
+}
+
+/// Carry out a command, and print the results to the console.
+pub fn execute(server: &server::T, admin: &T, gaia: &gaia_pool::T, command: Command) {
+  match command {
+    Command::Clients => {
+      let players = server.players.lock().unwrap();
+      let clients = server.clients.lock().unwrap();
+      println!("{} clients:", clients.len());
+      for id in clients.keys() {
+        match players.values().find(|player| player.client_id == *id) {
+          None => println!("  {:?} (no player)", id),
+          Some(player) => println!("  {:?} playing {:?}", id, player.name),
+        }
+      }
+    },
+    Command::Players => {
+      let players = server.players.lock().unwrap();
+      println!("{} players:", players.len());
+      for player in players.values() {
+        println!(
+          "  {:?} ({:?}, {:?}) at ({:.1}, {:.1}, {:.1}) with {} health",
+          player.name, player.entity_id, player.client_id,
+          player.position.x, player.position.y, player.position.z,
+          player.health.current,
+        );
+      }
+    },
+    Command::Kick { player: name, reason } => {
+      let client_id =
+        match client_of(server, &name) {
+          None => return println!("No player named {:?}", name),
+          Some(client_id) => client_id,
+        };
+      match server.clients.lock().unwrap().remove(&client_id) {
+        None => println!("{:?} isn't connected", name),
+        Some(mut client) => {
+          client.send(protocol::ServerToClient::Kicked(reason.clone()));
+          info!("Kicked {:?} ({:?}): {}", name, client_id, reason);
+          println!("Kicked {:?}", name);
+        },
+      }
+      // Their body stays in the world until they rejoin, like any other disconnected player's.
+      player::save_all(server);
+    },
+    Command::Teleport(name, position) => {
+      let mut players = server.players.lock().unwrap();
+      match players.values_mut().find(|player| player.name == name) {
+        None => println!("No player named {:?}", name),
+        Some(player) => {
+          player.teleport(&server.physics, &position);
+          println!("Teleported {:?} to {:?}", name, position);
+        },
+      }
+    },
+    Command::SetTime(fraction) => {
+      server.sun.lock().unwrap().set_fraction(fraction);
+      broadcast_sun(server);
+      println!("The sun is {:.1}% through its cycle", fraction * 100.0);
+    },
+    Command::FreezeTime(frozen) => {
+      server.sun.lock().unwrap().frozen = frozen;
+      println!("The sun is {}", if frozen { "frozen" } else { "moving" });
+    },
+    Command::Save => {
+      trace::time("admin.save", || {
+        server.regions.save_dirty(&server.terrain_loader.terrain);
+        player::save_all(server);
+      });
+      println!("Saved");
+    },
+    Command::Mobs => {
+      let mobs = server.mobs.lock().unwrap();
+      println!("{} mobs:", mobs.len());
+      for mob in mobs.values() {
+        println!(
+          "  {} at ({:.1}, {:.1}, {:.1}) with {} health",
+          mob.entity_id.to_u32(), mob.position.x, mob.position.y, mob.position.z, mob.health.current,
+        );
+      }
+    },
+    Command::Spawn { low_corner, hostile } => {
+      let behavior: mob::Behavior = if hostile { mob_behavior::hostile } else { mob_behavior::mob_behavior };
+      let id = spawner::add_mob(server, low_corner, behavior);
+      println!("Spawned mob {}", id.to_u32());
+    },
+    Command::Despawn(id) => {
+      let despawned: Vec<mob::Mob> = {
+        let mut mobs = server.mobs.lock().unwrap();
+        let ids: Vec<entity::id::Mob> =
+          mobs.keys()
+          .filter(|mob| id.map_or(true, |id| mob.to_u32() == id))
+          .cloned()
+          .collect();
+        ids.iter().map(|id| mobs.remove(id).unwrap()).collect()
+      };
+      if despawned.is_empty() {
+        return println!("No mobs to despawn")
+      }
+
+      let ids: Vec<entity::id::Mob> = despawned.iter().map(|mob| mob.entity_id).collect();
+      for mob in despawned {
+        spawner::release(server, mob);
+      }
+      let mut clients = server.clients.lock().unwrap();
+      for id in &ids {
+        for (_, client) in clients.iter_mut() {
+          client.send(protocol::ServerToClient::RemoveMob(*id));
+        }
+      }
+      println!("Despawned {} mobs", ids.len());
+    },
+    Command::Stats => {
+      // The simulation threads each print their own stopwatch when they see this.
+      *admin.stopwatch_request.lock().unwrap() += 1;
+      println!("Players: {}", server.players.lock().unwrap().len());
+      println!("Mobs: {}", server.mobs.lock().unwrap().len());
+      println!("Clients: {}", server.clients.lock().unwrap().len());
+      println!("Blocks in the LOD map: {}", server.terrain_loader.lod_map.lock().unwrap().len());
+      println!("Blocks being generated: {}", server.terrain_loader.in_progress_terrain.lock().unwrap().blocks.len());
+      println!("Blocks loaded into physics: {}", server.terrain_loader.loaded.read().unwrap().len());
+      println!(
+        "Terrain memory: about {} of {} MB",
+        server.terrain_loader.terrain.ram_usage() >> 20, server.config.terrain_memory_mb,
+      );
+      let (loads, edits) = gaia.queued();
+      println!("Outstanding gaia updates: {} loads, {} edits", loads, edits);
+      println!("Outstanding admin commands: {}", admin.commands.lock().unwrap().len());
+      println!("Sun: {:.1}%", server.sun.lock().unwrap().fraction() * 100.0);
+    },
+  }
+}
+
+/// Carry out queued commands until `quit_signal` is set.
+pub fn run(
+  server: &server::T,
+  admin: &T,
+  gaia: &gaia_pool::T,
+  quit_signal: &Mutex<bool>,
+) {
+  while !*quit_signal.lock().unwrap() {
+    match admin.pop() {
+      None => std::thread::sleep(std::time::Duration::from_millis(50)),
+      Some(command) => {
+        debug!("Admin command: {:?}", command);
+        execute(server, admin, gaia, command);
+      },
+    }
+  }
+}
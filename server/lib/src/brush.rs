@@ -0,0 +1,248 @@
+//! Turn clients' brush requests into terrain edits.
+
+use cgmath::{Point3, Vector3, EuclideanSpace};
+use collision::{Aabb3};
+use rand;
+use std::f32::consts::PI;
+
+use common::fnv_map;
+use common::protocol;
+use common::voxel;
+
+use inventory;
+use terrain;
+use terrain::sculpt;
+use terrain::shape;
+use voxel_data;
+
+pub const MIN_SIZE: f32 = 1.0;
+pub const MAX_SIZE: f32 = 12.0;
+
+const MIN_TRUNK_RADIUS: f32 = 1.0;
+const MAX_TRUNK_RADIUS: f32 = 3.0;
+const MIN_TRUNK_HEIGHT: f32 = 4.0;
+const MAX_TRUNK_HEIGHT: f32 = 36.0;
+const MIN_LEAF_RADIUS: f32 = 2.0;
+const MAX_LEAF_RADIUS: f32 = 18.0;
+
+pub type Mosaic = Box<voxel_data::mosaic::T<voxel::Material> + Send>;
+
+fn clamp(x: f32, min: f32, max: f32) -> f32 {
+  f32::max(min, f32::min(max, x))
+}
+
+/// Restrict a requested brush to something reasonable.
+pub fn clamped(brush: &protocol::Brush) -> protocol::Brush {
+  let shape =
+    match brush.shape {
+      protocol::BrushShape::Tree { trunk_radius, trunk_height, leaf_radius } => {
+        let trunk_radius = clamp(trunk_radius, MIN_TRUNK_RADIUS, MAX_TRUNK_RADIUS);
+        protocol::BrushShape::Tree {
+          trunk_radius : trunk_radius,
+          trunk_height : clamp(trunk_height, f32::max(MIN_TRUNK_HEIGHT, 2.0 * trunk_radius), MAX_TRUNK_HEIGHT),
+          leaf_radius  : clamp(leaf_radius, MIN_LEAF_RADIUS, MAX_LEAF_RADIUS),
+        }
+      },
+      shape => shape,
+    };
+  let material =
+    match brush.material {
+      // Adding or painting nothing is just subtracting.
+      voxel::Material::Empty => voxel::Material::Terrain,
+      material => material,
+    };
+  protocol::Brush {
+    shape    : shape,
+    size     : clamp(brush.size, MIN_SIZE, MAX_SIZE),
+    material : material,
+    mode     : brush.mode,
+  }
+}
+
+/// A rough upper bound on the materials a brush would use, assuming everything it touches is empty.
+/// Sculpting brushes reuse material that's already there, so they're costed by `sculpt` instead.
+pub fn cost(brush: &protocol::Brush) -> Vec<(voxel::Material, u32)> {
+  match brush.mode {
+    protocol::BrushMode::Smooth | protocol::BrushMode::Flatten => return Vec::new(),
+    protocol::BrushMode::Add | protocol::BrushMode::Subtract | protocol::BrushMode::Paint => {},
+  }
+
+  let r = brush.size;
+  let volume =
+    match brush.shape {
+      protocol::BrushShape::Sphere => 4.0 / 3.0 * PI * r * r * r,
+      protocol::BrushShape::Cube => 8.0 * r * r * r,
+      protocol::BrushShape::Cylinder => 2.0 * PI * r * r * r,
+      protocol::BrushShape::Tree { trunk_radius, trunk_height, leaf_radius } => {
+        let bark = PI * trunk_radius * trunk_radius * trunk_height;
+        let leaves = 4.0 / 3.0 * PI * leaf_radius * leaf_radius * leaf_radius;
+        if brush.mode == protocol::BrushMode::Add {
+          return vec!(
+            (voxel::Material::Bark, bark.ceil() as u32),
+            (voxel::Material::Leaves, leaves.ceil() as u32),
+          )
+        }
+        bark + leaves
+      },
+    };
+
+  match brush.mode {
+    protocol::BrushMode::Add | protocol::BrushMode::Paint => vec!((brush.material, volume.ceil() as u32)),
+    protocol::BrushMode::Subtract | protocol::BrushMode::Smooth | protocol::BrushMode::Flatten => Vec::new(),
+  }
+}
+
+fn aabb(center: &Point3<f32>, r: f32) -> Aabb3<i32> {
+  let low = *center + (&-Vector3::new(r, r, r));
+  let high = *center + (&Vector3::new(r, r, r));
+  Aabb3::new(
+    Point3::new(low.x.floor() as i32, low.y.floor() as i32, low.z.floor() as i32),
+    Point3::new(high.x.ceil() as i32, high.y.ceil() as i32, high.z.ceil() as i32),
+  )
+}
+
+/// Build the terrain edit for a brush aimed at `target`.
+/// Paint brushes are built the same way; their mosaic says where and with what to paint.
+/// Sculpting brushes depend on the terrain, and are built by `sculpt`.
+pub fn new<Rng>(
+  rng: &mut Rng,
+  brush: &protocol::Brush,
+  target: &voxel::bounds::T,
+) -> voxel_data::brush::T<Mosaic>
+  where Rng: rand::Rng,
+{
+  let material =
+    match brush.mode {
+      protocol::BrushMode::Subtract => voxel::Material::Empty,
+      protocol::BrushMode::Add | protocol::BrushMode::Paint => brush.material,
+      protocol::BrushMode::Smooth | protocol::BrushMode::Flatten =>
+        panic!("{:?} brushes are built by brush::sculpt", brush.mode),
+    };
+
+  let r = brush.size;
+  let (center, extent, shape) =
+    match brush.shape {
+      protocol::BrushShape::Sphere => {
+        (target.center(), r, shape::T::Sphere(voxel_data::field::sphere::T { radius: r }))
+      },
+      protocol::BrushShape::Cube => {
+        // Far enough to reach the cube's corners.
+        (target.center(), r * 3.0_f32.sqrt(), shape::T::Cube(shape::cube::T { half_width: r }))
+      },
+      protocol::BrushShape::Cylinder => {
+        (target.center(), r * 2.0_f32.sqrt(), shape::T::Cylinder(shape::cylinder::T { radius: r, half_height: r }))
+      },
+      protocol::BrushShape::Tree { trunk_radius, trunk_height, leaf_radius } => {
+        let (low, high) = target.corners();
+        let mut bottom = (low + high.to_vec()) / 2.0;
+        bottom.y = low.y;
+
+        let tree = terrain::tree::new(rng, trunk_height, trunk_radius, leaf_radius);
+        let center = bottom + (&Vector3::new(0.0, trunk_height / 2.0, 0.0));
+        let r = trunk_height / 2.0 + leaf_radius + 20.0;
+
+        if brush.mode == protocol::BrushMode::Add {
+          let tree =
+            voxel_data::mosaic::translation::T {
+              translation: bottom.to_vec(),
+              mosaic: tree,
+            };
+          return voxel_data::brush::T {
+            bounds: aabb(&center, r),
+            mosaic: Box::new(tree) as Mosaic,
+            min_lg_size: 0,
+          }
+        }
+
+        // The tree's origin is its base, not its center.
+        let shape = shape::T::Tree(tree);
+        return voxel_data::brush::T {
+          bounds: aabb(&center, r),
+          mosaic:
+            Box::new(
+              voxel_data::mosaic::solid::T {
+                field: voxel_data::field::translation::T {
+                  translation: bottom.to_vec(),
+                  field: shape,
+                },
+                material: material,
+              }
+            ) as Mosaic,
+          min_lg_size: 0,
+        }
+      },
+    };
+
+  let solid =
+    voxel_data::mosaic::solid::T {
+      field: voxel_data::field::translation::T {
+        translation: center.to_vec(),
+        field: shape,
+      },
+      material: material,
+    };
+
+  voxel_data::brush::T {
+    bounds: aabb(&center, extent + 1.0),
+    mosaic: Box::new(solid) as Mosaic,
+    min_lg_size: 0,
+  }
+}
+
+/// All the terrain a sculpting brush looks at. This reaches a little past what it changes,
+/// so the smoothing near the edges has neighbors to look at.
+pub fn reach(brush: &protocol::Brush, target: &voxel::bounds::T) -> Aabb3<i32> {
+  aabb(&target.center(), brush.size + 3.0)
+}
+
+/// Build a smoothing or flattening brush aimed at `target`, from the terrain as it is now.
+/// Also returns the materials it would use to fill in empty space.
+pub fn sculpt(
+  terrain: &terrain::T,
+  brush: &protocol::Brush,
+  target: &voxel::bounds::T,
+) -> (voxel_data::brush::T<Mosaic>, Vec<(voxel::Material, u32)>) {
+  let r = brush.size;
+  let center = target.center();
+  let bounds = aabb(&center, r + 1.0);
+  let region = reach(brush, target);
+
+  let mut mosaic =
+    match brush.mode {
+      protocol::BrushMode::Smooth => {
+        let snapshot = sculpt::snapshot(terrain, &region);
+        Box::new(sculpt::smooth::new(snapshot, center, r)) as Mosaic
+      },
+      protocol::BrushMode::Flatten => {
+        let (_, high) = target.corners();
+        let snapshot = sculpt::snapshot(terrain, &region);
+        Box::new(sculpt::flatten::new(snapshot, center, r, high.y)) as Mosaic
+      },
+      protocol::BrushMode::Add | protocol::BrushMode::Subtract | protocol::BrushMode::Paint =>
+        panic!("{:?} brushes are built by brush::new", brush.mode),
+    };
+
+  // Charge for every empty voxel whose center ends up solid.
+  let mut cost = fnv_map::new();
+  for x in bounds.min.x .. bounds.max.x {
+  for y in bounds.min.y .. bounds.max.y {
+  for z in bounds.min.z .. bounds.max.z {
+    let p = Point3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+    let material =
+      match voxel_data::mosaic::T::material(&mut *mosaic, &p) {
+        None | Some(voxel::Material::Empty) => continue,
+        Some(material) => material,
+      };
+    if inventory::material_of(&terrain.load(&voxel::bounds::new(x, y, z, 0))) == voxel::Material::Empty {
+      *cost.entry(material).or_insert(0) += 1;
+    }
+  }}}
+
+  let brush =
+    voxel_data::brush::T {
+      bounds: bounds,
+      mosaic: mosaic,
+      min_lg_size: 0,
+    };
+  (brush, cost.into_iter().collect())
+}
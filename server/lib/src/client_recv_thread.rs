@@ -1,30 +1,23 @@
-use cgmath::{Point3, Vector3, EuclideanSpace};
-use collision::{Aabb3};
-use rand;
-use rand::distributions::IndependentSample;
+use cgmath::{Point3, Vector2};
 use std::convert::AsRef;
-use std::f32::consts::PI;
-use std::ops::DerefMut;
 use std::time::Duration;
-use stopwatch;
 
 use common::protocol;
 use common::socket::SendSocket;
+use common::trace;
 use common::voxel;
 
+use brush;
+use clipboard;
+use combat;
 use entity;
 use player;
+use save_file;
 use server;
 use server::Client;
-use terrain;
-use voxel_data;
 use update_gaia;
 use update_gaia::LoadDestination;
 
-fn center(bounds: &Aabb3<f32>) -> Point3<f32> {
-  (bounds.min + bounds.max.to_vec()) * 0.5
-}
-
 fn cast(
   server: &server::T,
   player_id: entity::id::Player,
@@ -36,7 +29,7 @@ fn cast(
     ray = player.forward_ray();
   }
 
-  server.terrain_loader.terrain.voxels.lock().unwrap().cast_ray(
+  server.terrain_loader.terrain.voxels.read().unwrap().cast_ray(
     &ray,
     &mut |bounds, voxel| {
       match voxel {
@@ -54,14 +47,17 @@ pub fn apply_client_update<UpdateGaia>(
 ) where
   UpdateGaia: FnMut(update_gaia::Message),
 {
-  stopwatch::time("apply_client_update", move || {
+  trace::time("apply_client_update", move || {
     match update {
       protocol::ClientToServer::Init(client_url) => {
         info!("Sending to {}.", client_url);
 
         let mut client =
           Client {
-            socket: SendSocket::new(client_url.as_ref(), Some(Duration::from_secs(30))),
+            socket        : SendSocket::new(client_url.as_ref(), Some(Duration::from_secs(30))),
+            bytes_sent    : 0,
+            messages_sent : 0,
+            lost          : false,
           };
 
         let client_id = server.client_allocator.lock().unwrap().allocate();
@@ -70,38 +66,91 @@ pub fn apply_client_update<UpdateGaia>(
         server.clients.lock().unwrap().insert(client_id, client);
       },
       protocol::ClientToServer::Ping(client_id) => {
+        // Clients that were kicked may not have noticed yet.
         server.clients.lock().unwrap()
           .get_mut(&client_id)
-          .unwrap()
-          .send(protocol::ServerToClient::Ping);
+          .map(|client| client.send(protocol::ServerToClient::Ping));
       },
-      protocol::ClientToServer::AddPlayer(client_id) => {
-        let mut player =
-          player::new(
-            server.player_allocator.lock().unwrap().allocate(),
-            server.misc_allocator.lock().unwrap().allocate(),
-            &server.owner_allocator,
-          );
-
-        // TODO: shift upward until outside terrain
-        let min = Point3::new(0.0, 64.0, 4.0);
-        let max = min + (&Vector3::new(1.0, 2.0, 1.0));
-        let bounds = Aabb3::new(min, max);
-        server.physics.lock().unwrap().insert_misc(player.physics_id, &bounds);
-
-        player.position = center(&bounds);
-        player.rotate_lateral(PI / 2.0);
-
-        let id = player.entity_id;
-        let pos = player.position;
+      protocol::ClientToServer::AddPlayer(client_id, name) => {
+        if !save_file::is_valid_name(&name) {
+          server::kick(server, client_id, format!("{:?} isn't a valid player name", name));
+          return
+        }
 
-        server.players.lock().unwrap().insert(id, player);
+        // Someone reconnecting while their body is still in the world just takes it back,
+        // as long as nobody's still playing as them.
+        let existing = {
+          let mut players = server.players.lock().unwrap();
+          match players.values_mut().find(|player| player.name == name) {
+            None => Ok(None),
+            Some(player) => {
+              let mut clients = server.clients.lock().unwrap();
+              let in_use = clients.get(&player.client_id).map_or(false, |client| !client.lost);
+              if in_use {
+                Err(format!("Someone is already playing as {:?}", name))
+              } else {
+                clients.remove(&player.client_id);
+                player.client_id = client_id;
+                let rotation = Vector2::new(player.lateral_rotation, player.vertical_rotation);
+                Ok(Some((player.entity_id, player.position, rotation)))
+              }
+            },
+          }
+        };
+        let existing =
+          match existing {
+            Ok(existing) => existing,
+            Err(reason) => {
+              server::kick(server, client_id, reason);
+              return
+            },
+          };
 
-        let mut clients = server.clients.lock().unwrap();
-        let client = clients.get_mut(&client_id).unwrap();
-        client.send(
-          protocol::ServerToClient::PlayerAdded(id, pos)
-        );
+        match existing {
+          Some((id, pos, rotation)) => {
+            info!("{:?} rejoined", name);
+            server.clients.lock().unwrap().get_mut(&client_id).map(|client| {
+              client.send(protocol::ServerToClient::PlayerAdded(id, pos, rotation));
+            });
+          },
+          None => {
+            let saved =
+              match player::load(server.regions.directory(), &name) {
+                Ok(saved) => saved,
+                Err(err) => {
+                  error!("Not adding player: {}", err);
+                  server::kick(server, client_id, err);
+                  return
+                },
+              };
+
+            let entity_id = server.player_allocator.lock().unwrap().allocate();
+            let physics_id = server.misc_allocator.lock().unwrap().allocate();
+            let mut player =
+              player::new(
+                name,
+                entity_id,
+                physics_id,
+                client_id,
+                &server.owner_allocator,
+                server.config.player_load_distance,
+              );
+
+            match saved {
+              Some(saved) => {
+                info!("{:?} returned", player.name);
+                player.restore(&saved);
+                let bounds = player::bounds_around(&player.position);
+                player::add(server, player, &bounds);
+              },
+              None => {
+                // Finding somewhere to put them can generate a lot of terrain, so gaia does it.
+                info!("{:?} joined for the first time", player.name);
+                update_gaia(update_gaia::Message::SpawnPlayer(Box::new(player)));
+              },
+            }
+          },
+        }
       },
       protocol::ClientToServer::StartJump(player_id) => {
         let mut players = server.players.lock().unwrap();
@@ -135,105 +184,61 @@ pub fn apply_client_update<UpdateGaia>(
       protocol::ClientToServer::RequestVoxels { time_requested_ns, client_id, voxels } => {
         update_gaia(update_gaia::Message::Load(time_requested_ns, voxels, LoadDestination::Client(client_id)));
       },
-      protocol::ClientToServer::Add(player_id) => {
-        let bounds = cast(server, player_id);
-
-        bounds.map(|bounds| {
-          let mut rng = server.rng.lock().unwrap();
-          let rng = rng.deref_mut();
-
-          let trunk_radius =
-            rand::distributions::normal::Normal::new(2.0, 0.5)
-            .ind_sample(rng);
-          let trunk_radius =
-            f64::max(1.0, f64::min(3.0, trunk_radius));
-
-          let trunk_height =
-            rand::distributions::normal::Normal::new(8.0 * trunk_radius, 2.0 * trunk_radius)
-            .ind_sample(rng);
-          let trunk_height =
-            f64::max(4.0 * trunk_radius, f64::min(12.0 * trunk_radius, trunk_height));
-
-          let leaf_radius =
-            rand::distributions::normal::Normal::new(4.0 * trunk_radius, trunk_radius)
-            .ind_sample(rng);
-          let leaf_radius =
-            f64::max(2.0 * trunk_radius, f64::min(6.0 * trunk_radius, leaf_radius));
-
-          let (low, high) = bounds.corners();
-          let mut bottom = (low + high.to_vec()) / 2.0;
-          bottom.y = low.y;
-
-          let trunk_height = trunk_height as f32;
-          let trunk_radius = trunk_radius as f32;
-          let leaf_radius = leaf_radius as f32;
-
-          let tree =
-            voxel_data::mosaic::translation::T {
-              translation: bottom.to_vec(),
-              mosaic: terrain::tree::new(rng, trunk_height, trunk_radius, leaf_radius),
-            };
-
-          let center =
-            bottom + (&Vector3::new(0.0, trunk_height / 2.0, 0.0));
-          let r = trunk_height / 2.0 + leaf_radius + 20.0;
-          let brush =
-            voxel_data::brush::T {
-              bounds:
-                Aabb3::new(
-                  {
-                    let low = center + (&-Vector3::new(r, r, r));
-                    Point3::new(low.x.floor() as i32, low.y.floor() as i32, low.z.floor() as i32)
-                  },
-                  {
-                    let high = center + (&Vector3::new(r, r, r));
-                    Point3::new(high.x.ceil() as i32, high.y.ceil() as i32, high.z.ceil() as i32)
-                  },
-                ),
-              mosaic: Box::new(tree) as Box<voxel_data::mosaic::T<voxel::Material> + Send>,
-              min_lg_size: 0,
-            };
+      protocol::ClientToServer::Brush(player_id, request) => {
+        let request = brush::clamped(&request);
+        let target =
+          match cast(server, player_id) {
+            None => return,
+            Some(target) => target,
+          };
 
-          update_gaia(update_gaia::Message::Brush(brush));
-        });
+        debug!("brush {:?} at {:?}", request, target);
+        match request.mode {
+          protocol::BrushMode::Smooth | protocol::BrushMode::Flatten => {
+            update_gaia(update_gaia::Message::Sculpt(player_id, request, target));
+          },
+          protocol::BrushMode::Add | protocol::BrushMode::Subtract | protocol::BrushMode::Paint => {
+            update_gaia(update_gaia::Message::Brush(player_id, request, target));
+          },
+        }
       },
-      protocol::ClientToServer::Remove(player_id) => {
-        let bounds = cast(server, player_id);
-
-        bounds.map(|bounds| {
-          debug!("remove bounds {:?}", bounds);
-          let center = bounds.center();
-          let r = 8.0;
-          let sphere =
-            voxel_data::mosaic::solid::T {
-              field: voxel_data::field::translation::T {
-                translation: center.to_vec(),
-                field: voxel_data::field::sphere::T {
-                  radius: r,
-                },
-              },
-              material: voxel::Material::Empty,
-            };
-          let r = sphere.field.field.radius + 1.0;
-          let brush =
-            voxel_data::brush::T {
-              bounds:
-                Aabb3::new(
-                  {
-                    let low = sphere.field.translation + (&-Vector3::new(r, r, r));
-                    Point3::new(low.x.floor() as i32, low.y.floor() as i32, low.z.floor() as i32)
-                  },
-                  {
-                    let high = sphere.field.translation + (&Vector3::new(r, r, r));
-                    Point3::new(high.x.ceil() as i32, high.y.ceil() as i32, high.z.ceil() as i32)
-                  },
-                ),
-              mosaic: Box::new(sphere) as Box<voxel_data::mosaic::T<voxel::Material> + Send>,
-              min_lg_size: 0,
-            };
-          let brush: voxel_data::brush::T<Box<voxel_data::mosaic::T<voxel::Material> + Send>> = brush;
-          update_gaia(update_gaia::Message::Brush(brush));
-        });
+      protocol::ClientToServer::Undo(player_id) => {
+        update_gaia(update_gaia::Message::Undo(player_id));
+      },
+      protocol::ClientToServer::Redo(player_id) => {
+        update_gaia(update_gaia::Message::Redo(player_id));
+      },
+      protocol::ClientToServer::Clipboard(player_id, action) => {
+        let action =
+          match action {
+            protocol::ClipboardAction::SelectCorner(corner) => {
+              let corner = corner as usize;
+              if corner > 1 {
+                warn!("{:?} selected invalid corner {}", player_id, corner);
+                return
+              }
+              cast(server, player_id).map(|bounds| {
+                let mut players = server.players.lock().unwrap();
+                let player = players.get_mut(&player_id).unwrap();
+                player.selection[corner] = Some(Point3::new(bounds.x, bounds.y, bounds.z));
+                debug!("{:?} selection {:?}", player_id, player.selection);
+              });
+              return
+            },
+            protocol::ClipboardAction::Copy => clipboard::Action::Copy,
+            protocol::ClipboardAction::Paste(transform) => {
+              match cast(server, player_id) {
+                None => return,
+                Some(target) => clipboard::Action::Paste(target, transform),
+              }
+            },
+            protocol::ClipboardAction::Save(name) => clipboard::Action::Save(name),
+            protocol::ClipboardAction::Load(name) => clipboard::Action::Load(name),
+          };
+        update_gaia(update_gaia::Message::Clipboard(player_id, action));
+      },
+      protocol::ClientToServer::Attack(player_id) => {
+        combat::player_attack(server, player_id);
       },
     };
   })
@@ -0,0 +1,302 @@
+//! Copy regions of terrain, and paste them elsewhere.
+
+use bincode;
+use cgmath::{Point3, Vector3, InnerSpace};
+use collision::{Aabb3};
+use std;
+use std::io;
+use std::path::PathBuf;
+
+use common::fnv_map;
+use common::protocol;
+use common::voxel;
+
+use inventory;
+use save_file;
+use terrain;
+use voxel_data;
+
+/// The largest region, in unit voxels, that can be copied at once.
+pub const MAX_VOLUME: i32 = 1 << 18;
+/// Named clipboards are saved in here.
+const DIRECTORY: &'static str = "clipboards";
+const EXTENSION: &'static str = "clipboard";
+/// More than any saved clipboard takes up: its size, its length, and four bytes for each of up to `MAX_VOLUME`
+/// materials. Loading gives up past this, rather than trusting a corrupt file about how much to read.
+const MAX_FILE_BYTES: u64 = 64 + 4 * MAX_VOLUME as u64;
+
+/// Clipboard operations, to be run in order with other terrain edits.
+pub enum Action {
+  /// Copy the player's selection.
+  Copy,
+  /// Paste on top of a voxel.
+  Paste(voxel::bounds::T, protocol::PasteTransform),
+  Save(String),
+  Load(String),
+}
+
+/// A copied region of terrain, at unit-voxel resolution.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct T {
+  /// The size of the region in each dimension.
+  pub size      : Vector3<i32>,
+  /// The material of each voxel, in x-major, then y, then z order.
+  pub materials : Vec<voxel::Material>,
+}
+
+impl T {
+  fn index(&self, p: &Point3<i32>) -> Option<usize> {
+    if p.x < 0 || p.y < 0 || p.z < 0 || p.x >= self.size.x || p.y >= self.size.y || p.z >= self.size.z {
+      return None
+    }
+    Some(((p.x * self.size.y + p.y) * self.size.z + p.z) as usize)
+  }
+
+  fn material(&self, p: &Point3<i32>) -> voxel::Material {
+    match self.index(p) {
+      None => voxel::Material::Empty,
+      Some(i) => self.materials[i],
+    }
+  }
+
+  /// The materials it would take to paste this clipboard into empty space.
+  pub fn cost(&self) -> Vec<(voxel::Material, u32)> {
+    let mut cost = fnv_map::new();
+    for &material in &self.materials {
+      if material != voxel::Material::Empty {
+        *cost.entry(material).or_insert(0) += 1;
+      }
+    }
+    cost.into_iter().collect()
+  }
+}
+
+/// Copy the unit voxels in `region` out of the terrain.
+pub fn copy(terrain: &terrain::T, region: &Aabb3<i32>) -> Result<T, String> {
+  let size = region.max - region.min;
+  if size.x <= 0 || size.y <= 0 || size.z <= 0 {
+    return Err(format!("{:?} is empty", region))
+  }
+  if size.x as i64 * size.y as i64 * size.z as i64 > MAX_VOLUME as i64 {
+    return Err(format!("{:?} is larger than {} voxels", region, MAX_VOLUME))
+  }
+
+  let mut materials = Vec::with_capacity((size.x * size.y * size.z) as usize);
+  for x in region.min.x .. region.max.x {
+  for y in region.min.y .. region.max.y {
+  for z in region.min.z .. region.max.z {
+    let voxel = terrain.load(&voxel::bounds::new(x, y, z, 0));
+    materials.push(inventory::material_of(&voxel));
+  }}}
+
+  Ok(T {
+    size      : size,
+    materials : materials,
+  })
+}
+
+/// Only allow names that can't escape the clipboard directory.
+fn path_of(name: &str) -> Result<PathBuf, String> {
+  if !save_file::is_valid_name(name) {
+    return Err(format!("{:?} isn't a valid clipboard name", name))
+  }
+  let mut path = PathBuf::from(DIRECTORY);
+  path.push(name);
+  path.set_extension(EXTENSION);
+  Ok(path)
+}
+
+pub fn save(clipboard: &T, name: &str) -> Result<(), String> {
+  let path = try!(path_of(name));
+  let write = || -> io::Result<()> {
+    try!(std::fs::create_dir_all(DIRECTORY));
+    let mut file = try!(std::fs::File::create(&path));
+    bincode::serialize_into(&mut file, clipboard, bincode::Infinite)
+      .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))
+  };
+  write().map_err(|err| format!("Error saving clipboard to {:?}: {}", path, err))
+}
+
+pub fn load(name: &str) -> Result<T, String> {
+  let path = try!(path_of(name));
+  let mut file =
+    try!(std::fs::File::open(&path).map_err(|err| format!("Error opening {:?}: {}", path, err)));
+  let clipboard: T =
+    try!(
+      bincode::deserialize_from(&mut file, bincode::Bounded(MAX_FILE_BYTES))
+      .map_err(|err| format!("Error loading clipboard from {:?}: {:?}", path, err))
+    );
+  let size = clipboard.size;
+  if size.x < 0 || size.y < 0 || size.z < 0 {
+    return Err(format!("{:?} is corrupt", path))
+  }
+  let volume = size.x as i64 * size.y as i64 * size.z as i64;
+  if volume > MAX_VOLUME as i64 {
+    return Err(format!("{:?} is larger than {} voxels", path, MAX_VOLUME))
+  }
+  if volume as usize != clipboard.materials.len() {
+    return Err(format!("{:?} is corrupt", path))
+  }
+  Ok(clipboard)
+}
+
+/// A clipboard placed in the world, as a mosaic to brush with.
+pub struct Mosaic {
+  clipboard : T,
+  /// Where the clipboard's low corner ends up, before rotation.
+  origin    : Point3<f32>,
+  transform : protocol::PasteTransform,
+}
+
+unsafe impl Send for Mosaic {}
+
+impl Mosaic {
+  /// Map a world position into the untransformed clipboard's coordinates.
+  fn to_clipboard(&self, p: &Point3<f32>) -> Point3<f32> {
+    let quarter_turns = self.transform.quarter_turns % 4;
+    let size = &self.clipboard.size;
+    let mut q = *p - self.origin;
+    // The dimensions of the rotated region in x and z.
+    let (mut w, mut d) =
+      if quarter_turns % 2 == 0 {
+        (size.x as f32, size.z as f32)
+      } else {
+        (size.z as f32, size.x as f32)
+      };
+    // A quarter turn takes (x, z) in [0, w) x [0, d) to (d - z, x) in [0, d) x [0, w).
+    for _ in 0 .. quarter_turns {
+      let (x, z) = (q.z, w - q.x);
+      q.x = x;
+      q.z = z;
+      std::mem::swap(&mut w, &mut d);
+    }
+    if self.transform.mirror {
+      q.x = size.x as f32 - q.x;
+    }
+    Point3::new(q.x, q.y, q.z)
+  }
+
+  /// Is the clipboard voxel at `p` (in clipboard coordinates) solid?
+  fn occupancy(&self, p: &Point3<i32>) -> f32 {
+    if self.clipboard.material(p) == voxel::Material::Empty { -1.0 } else { 1.0 }
+  }
+
+  /// Interpolate the occupancy of voxels around `p` (in clipboard coordinates).
+  fn clipboard_density(&self, p: &Point3<f32>) -> f32 {
+    // Voxel centers are at half-integer coordinates.
+    let p = Point3::new(p.x - 0.5, p.y - 0.5, p.z - 0.5);
+    let low = Point3::new(p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+    let t = Vector3::new(p.x - low.x as f32, p.y - low.y as f32, p.z - low.z as f32);
+    let mut density = 0.0;
+    for &dx in &[0, 1] {
+    for &dy in &[0, 1] {
+    for &dz in &[0, 1] {
+      let weight =
+        (if dx == 0 { 1.0 - t.x } else { t.x }) *
+        (if dy == 0 { 1.0 - t.y } else { t.y }) *
+        (if dz == 0 { 1.0 - t.z } else { t.z });
+      density += weight * self.occupancy(&Point3::new(low.x + dx, low.y + dy, low.z + dz));
+    }}}
+    density
+  }
+
+  fn contains(&self, p: &Point3<f32>) -> bool {
+    let size = &self.clipboard.size;
+    p.x >= 0.0 && p.y >= 0.0 && p.z >= 0.0 &&
+    p.x < size.x as f32 && p.y < size.y as f32 && p.z < size.z as f32
+  }
+}
+
+impl voxel_data::field::T for Mosaic {
+  fn density(&mut self, p: &Point3<f32>) -> f32 {
+    let p = self.to_clipboard(p);
+    self.clipboard_density(&p)
+  }
+
+  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+    // Use density differential in each dimension as an approximation of the normal.
+    let delta = 0.01;
+
+    macro_rules! differential(($d:ident) => {{
+      let high: f32 = {
+        let mut p = *p;
+        p.$d += delta;
+        voxel_data::field::T::density(self, &p)
+      };
+      let low: f32 = {
+        let mut p = *p;
+        p.$d -= delta;
+        voxel_data::field::T::density(self, &p)
+      };
+      high - low
+    }});
+
+    let v = Vector3::new(differential!(x), differential!(y), differential!(z));
+    if v.magnitude2() == 0.0 {
+      return Vector3::new(0.0, 1.0, 0.0)
+    }
+    // Negate because we're leaving the volume when density is decreasing.
+    -v.normalize()
+  }
+}
+
+impl voxel_data::mosaic::T<voxel::Material> for Mosaic {
+  fn material(&mut self, p: &Point3<f32>) -> Option<voxel::Material> {
+    let p = self.to_clipboard(p);
+    if !self.contains(&p) {
+      // Leave everything outside the clipboard alone.
+      return None
+    }
+    if self.clipboard_density(&p) < 0.0 {
+      return Some(voxel::Material::Empty)
+    }
+
+    let voxel = Point3::new(p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+    match self.clipboard.material(&voxel) {
+      voxel::Material::Empty => {},
+      material => return Some(material),
+    }
+    // We're solid, but in an empty voxel; use a solid neighbour's material.
+    for &(dx, dy, dz) in &[(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)] {
+      match self.clipboard.material(&Point3::new(voxel.x + dx, voxel.y + dy, voxel.z + dz)) {
+        voxel::Material::Empty => {},
+        material => return Some(material),
+      }
+    }
+    Some(voxel::Material::Terrain)
+  }
+}
+
+/// A brush that pastes `clipboard` with its low corner on top of `target`.
+pub fn paste(
+  clipboard: T,
+  target: &voxel::bounds::T,
+  transform: protocol::PasteTransform,
+) -> voxel_data::brush::T<Box<voxel_data::mosaic::T<voxel::Material> + Send>> {
+  let (low, _) = target.corners();
+  let origin = Point3::new(low.x, low.y + 1.0, low.z);
+  let (w, d) =
+    if transform.quarter_turns % 2 == 0 {
+      (clipboard.size.x, clipboard.size.z)
+    } else {
+      (clipboard.size.z, clipboard.size.x)
+    };
+  let bounds =
+    Aabb3::new(
+      Point3::new(origin.x as i32, origin.y as i32, origin.z as i32),
+      Point3::new(origin.x as i32 + w, origin.y as i32 + clipboard.size.y, origin.z as i32 + d),
+    );
+
+  let mosaic =
+    Mosaic {
+      clipboard : clipboard,
+      origin    : origin,
+      transform : transform,
+    };
+
+  voxel_data::brush::T {
+    bounds: bounds,
+    mosaic: Box::new(mosaic) as Box<voxel_data::mosaic::T<voxel::Material> + Send>,
+    min_lg_size: 0,
+  }
+}
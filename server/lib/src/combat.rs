@@ -0,0 +1,232 @@
+//! Health, and attacks between players and mobs.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+use collision::{Aabb3, Ray3};
+use std;
+
+use common::protocol;
+use common::voxel;
+use common::world_position;
+
+use entity;
+use player;
+use server;
+use spawner;
+
+pub const PLAYER_HEALTH: u32 = 20;
+pub const MOB_HEALTH: u32 = 10;
+
+const PLAYER_ATTACK_DAMAGE: u32 = 4;
+pub const MOB_ATTACK_DAMAGE: u32 = 2;
+
+/// How far away players can hit things.
+const ATTACK_RANGE: f32 = 4.0;
+/// How far away mobs can hit things, from center to center.
+pub const MOB_ATTACK_RANGE: f32 = 1.8;
+/// World updates between consecutive attacks by the same attacker.
+pub const ATTACK_COOLDOWN_TICKS: u32 = 10;
+/// World updates a mob spends reeling (i.e. not following its behavior) after it's hit.
+const STAGGER_TICKS: u32 = 8;
+
+const KNOCKBACK_SPEED: f32 = 0.5;
+const KNOCKBACK_LIFT: f32 = 0.4;
+
+pub struct Health {
+  pub current : u32,
+  pub max     : u32,
+}
+
+impl Health {
+  pub fn new(max: u32) -> Health {
+    Health {
+      current : max,
+      max     : max,
+    }
+  }
+
+  /// Take `amount` damage. Returns true if that was fatal.
+  pub fn damage(&mut self, amount: u32) -> bool {
+    self.current = self.current.saturating_sub(amount);
+    self.current == 0
+  }
+
+  pub fn restore(&mut self) {
+    self.current = self.max;
+  }
+}
+
+/// Distance along `ray` to the point where it enters `bounds`, if it does.
+fn ray_distance(ray: &Ray3<f32>, bounds: &Aabb3<f32>) -> Option<f32> {
+  let mut near = 0.0;
+  let mut far = std::f32::INFINITY;
+  for i in 0 .. 3 {
+    let origin = ray.origin[i];
+    let direction = ray.direction[i];
+    if direction == 0.0 {
+      if origin < bounds.min[i] || origin > bounds.max[i] {
+        return None
+      }
+      continue
+    }
+
+    let t1 = (bounds.min[i] - origin) / direction;
+    let t2 = (bounds.max[i] - origin) / direction;
+    let (t1, t2) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+    near = f32::max(near, t1);
+    far = f32::min(far, t2);
+    if near > far {
+      return None
+    }
+  }
+  Some(near)
+}
+
+/// Distance along `ray` to the first non-empty voxel.
+fn terrain_distance(server: &server::T, ray: &Ray3<f32>) -> Option<f32> {
+  let bounds =
+    server.terrain_loader.terrain.voxels.read().unwrap().cast_ray(
+      ray,
+      &mut |bounds, voxel| {
+        match voxel {
+          &voxel::Volume(voxel::Material::Empty) => None,
+          _ => Some(bounds),
+        }
+      }
+    );
+  bounds.and_then(|bounds| {
+    let (low, high) = bounds.corners();
+    ray_distance(ray, &Aabb3::new(low, high))
+  })
+}
+
+/// Resolve an attack by a player on whatever it's looking at.
+pub fn player_attack(server: &server::T, attacker: entity::id::Player) {
+  let ray;
+  let eye;
+  {
+    let mut players = server.players.lock().unwrap();
+    let player = players.get_mut(&attacker).unwrap();
+    if player.attack_cooldown > 0 {
+      return
+    }
+    player.attack_cooldown = ATTACK_COOLDOWN_TICKS;
+    ray = player.forward_ray();
+    eye = player.position;
+  }
+
+  // Don't hit through walls.
+  let range =
+    match terrain_distance(server, &ray) {
+      None => ATTACK_RANGE,
+      Some(d) => f32::min(d, ATTACK_RANGE),
+    };
+
+  let mut closest: Option<(f32, protocol::Combatant)> = None;
+  {
+    // Bodies are checked relative to the attacker, so aim is just as good far from the origin.
+    let relative_ray = Ray3::new(Point3::new(0.0, 0.0, 0.0), ray.direction);
+    let mut consider = |bounds: &world_position::Bounds, target| {
+      match ray_distance(&relative_ray, &world_position::relative_bounds(&eye, bounds)) {
+        Some(d) if d <= range && closest.as_ref().map(|&(c, _)| d < c) != Some(false) => {
+          closest = Some((d, target));
+        },
+        _ => {},
+      }
+    };
+
+    {
+      let players = server.players.lock().unwrap();
+      let physics = server.physics.lock().unwrap();
+      for player in players.values() {
+        if player.entity_id != attacker {
+          consider(physics.get_bounds(player.physics_id).unwrap(), protocol::Combatant::Player(player.entity_id));
+        }
+      }
+    }
+
+    {
+      let mobs = server.mobs.lock().unwrap();
+      let physics = server.physics.lock().unwrap();
+      for mob in mobs.values() {
+        consider(physics.get_bounds(mob.physics_id).unwrap(), protocol::Combatant::Mob(mob.entity_id));
+      }
+    }
+  }
+
+  closest.map(|(_, target)| {
+    debug!("{:?} hit {:?}", attacker, target);
+    hit(server, target, PLAYER_ATTACK_DAMAGE, &ray.direction);
+  });
+}
+
+/// Damage and knock back `target`, handling its death if necessary.
+/// Mobs must not be locked if `target` is a mob.
+pub fn hit(
+  server    : &server::T,
+  target    : protocol::Combatant,
+  damage    : u32,
+  direction : &Vector3<f32>,
+) {
+  let mut knockback = Vector3::new(direction.x, 0.0, direction.z);
+  if knockback.magnitude2() > 0.0 {
+    knockback = knockback.normalize() * KNOCKBACK_SPEED;
+  }
+  knockback.y = KNOCKBACK_LIFT;
+
+  let mut events = Vec::new();
+  match target {
+    protocol::Combatant::Player(id) => {
+      let died = {
+        let mut players = server.players.lock().unwrap();
+        let player =
+          match players.get_mut(&id) {
+            None => return,
+            Some(player) => player,
+          };
+        player.speed += knockback;
+        let died = player.health.damage(damage);
+        events.push(protocol::ServerToClient::Damaged(target, player.health.current));
+        died
+      };
+      if died {
+        info!("{:?} died", id);
+        let bounds = player::respawn_bounds(server);
+        let mut players = server.players.lock().unwrap();
+        players.get_mut(&id).map(|player| {
+          player.respawn(&server.physics, &bounds);
+          events.push(protocol::ServerToClient::Died(target));
+          events.push(protocol::ServerToClient::Respawned(id, player.health.current));
+        });
+      }
+    },
+    protocol::Combatant::Mob(id) => {
+      let mut mobs = server.mobs.lock().unwrap();
+      let died =
+        match mobs.get_mut(&id) {
+          None => return,
+          Some(mob) => {
+            mob.speed += knockback;
+            mob.stagger_ticks = STAGGER_TICKS;
+            mob.path = None;
+            let died = mob.health.damage(damage);
+            events.push(protocol::ServerToClient::Damaged(target, mob.health.current));
+            died
+          },
+        };
+      if died {
+        debug!("{:?} died", id);
+        let mob = mobs.remove(&id).unwrap();
+        spawner::release(server, mob);
+        events.push(protocol::ServerToClient::Died(target));
+        events.push(protocol::ServerToClient::RemoveMob(id));
+      }
+    },
+  }
+
+  let mut clients = server.clients.lock().unwrap();
+  for (_, client) in clients.iter_mut() {
+    for event in &events {
+      client.send(event.clone());
+    }
+  }
+}
@@ -0,0 +1,283 @@
+//! Server settings, read from a config file and overridden on the command line.
+//! The config file has one `key = value` setting per line; blank lines and lines starting with `#` are ignored.
+
+use std;
+use std::io;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The config file that's read if one isn't specified.
+pub const DEFAULT_PATH: &'static str = "server.conf";
+
+/// Everything an operator can configure about a server.
+#[derive(Debug, Clone)]
+pub struct T {
+  /// Where the world is saved.
+  pub world                : PathBuf,
+  /// The terrain seed for new worlds. Existing worlds keep the seed they were created with.
+  pub seed                 : u32,
+  /// How many times a second the world is simulated.
+  pub updates_per_second   : u64,
+  /// Nanoseconds between each step of the sun; smaller is a faster day.
+  pub sun_tick_ns          : u64,
+  /// How many blocks around each player are loaded on the server.
+  pub player_load_distance : u32,
+  /// Seconds between saves of changed terrain and players.
+  pub autosave_seconds     : u64,
+  /// How many threads generate terrain and apply edits.
+  pub terrain_threads      : u32,
+  /// Roughly how much memory, in megabytes, terrain can use before regions nobody's near are dropped.
+  pub terrain_memory_mb    : u32,
+  /// The local port to serve metrics on, as a plain-text web page; 0 for none.
+  pub metrics_port         : u16,
+  /// A CSV file to append metrics to every second, if any.
+  pub metrics_csv          : Option<PathBuf>,
+}
+
+/// The settings used where nothing else is specified.
+pub fn default() -> T {
+  T {
+    world                : PathBuf::from("world"),
+    seed                 : 0,
+    updates_per_second   : 30,
+    sun_tick_ns          : 1600000,
+    player_load_distance : 8,
+    autosave_seconds     : 60,
+    terrain_threads      : 4,
+    terrain_memory_mb    : 512,
+    metrics_port         : 0,
+    metrics_csv          : None,
+  }
+}
+
+fn parse<V: FromStr>(key: &str, value: &str) -> Result<V, String> where V::Err: std::fmt::Display {
+  value.parse().map_err(|err| format!("Invalid value {:?} for {}: {}", value, key, err))
+}
+
+impl T {
+  /// Change the setting named `key`.
+  pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+    match key {
+      "world"                => self.world = PathBuf::from(value),
+      "seed"                 => self.seed = try!(parse(key, value)),
+      "updates_per_second"   => self.updates_per_second = try!(parse(key, value)),
+      "sun_tick_ns"          => self.sun_tick_ns = try!(parse(key, value)),
+      "player_load_distance" => self.player_load_distance = try!(parse(key, value)),
+      "autosave_seconds"     => self.autosave_seconds = try!(parse(key, value)),
+      "terrain_threads"      => self.terrain_threads = try!(parse(key, value)),
+      "terrain_memory_mb"    => self.terrain_memory_mb = try!(parse(key, value)),
+      "metrics_port"         => self.metrics_port = try!(parse(key, value)),
+      "metrics_csv"          => {
+        self.metrics_csv = if value.is_empty() { None } else { Some(PathBuf::from(value)) };
+      },
+      _ => return Err(format!("Unknown setting {:?}", key)),
+    }
+    Ok(())
+  }
+
+  /// Check that the settings make sense together.
+  pub fn validate(&self) -> Result<(), String> {
+    if self.world.as_os_str().is_empty() {
+      return Err(String::from("world can't be empty"))
+    }
+    if self.updates_per_second == 0 || self.updates_per_second > 1000 {
+      return Err(format!("updates_per_second must be between 1 and 1000, not {}", self.updates_per_second))
+    }
+    if self.sun_tick_ns == 0 {
+      return Err(String::from("sun_tick_ns must be positive"))
+    }
+    if self.player_load_distance == 0 || self.player_load_distance > 64 {
+      return Err(format!("player_load_distance must be between 1 and 64, not {}", self.player_load_distance))
+    }
+    if self.autosave_seconds == 0 {
+      return Err(String::from("autosave_seconds must be positive"))
+    }
+    if self.terrain_threads == 0 || self.terrain_threads > 64 {
+      return Err(format!("terrain_threads must be between 1 and 64, not {}", self.terrain_threads))
+    }
+    if self.terrain_memory_mb == 0 {
+      return Err(String::from("terrain_memory_mb must be positive"))
+    }
+    Ok(())
+  }
+}
+
+/// Read settings from a config file, on top of the defaults.
+/// If `required` is false, a missing file just means the defaults.
+pub fn load(path: &Path, required: bool) -> Result<T, String> {
+  let mut config = default();
+  let file =
+    match std::fs::File::open(path) {
+      Err(ref err) if err.kind() == io::ErrorKind::NotFound && !required => return Ok(config),
+      Err(err) => return Err(format!("Error opening {:?}: {}", path, err)),
+      Ok(file) => file,
+    };
+
+  for (i, line) in io::BufReader::new(file).lines().enumerate() {
+    let line = try!(line.map_err(|err| format!("Error reading {:?}: {}", path, err)));
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue
+    }
+    let mut parts = line.splitn(2, '=');
+    let key = parts.next().unwrap().trim();
+    let value =
+      match parts.next() {
+        None => return Err(format!("{:?} line {}: expected `key = value`, got {:?}", path, i + 1, line)),
+        Some(value) => value.trim(),
+      };
+    try!(config.set(key, value).map_err(|err| format!("{:?} line {}: {}", path, i + 1, err)));
+  }
+  Ok(config)
+}
+
+/// Read settings from command-line arguments, on top of a config file.
+/// `--config <file>` picks the config file, and every other `--<key> <value>` overrides the setting `key`.
+/// Returns the settings, and the arguments that aren't settings.
+pub fn of_args<Args: Iterator<Item=String>>(args: Args) -> Result<(T, Vec<String>), String> {
+  let mut path = None;
+  let mut overrides = Vec::new();
+  let mut rest = Vec::new();
+
+  let mut args = args;
+  while let Some(arg) = args.next() {
+    if arg.starts_with("--") {
+      let value = try!(args.next().ok_or_else(|| format!("{} needs a value", arg)));
+      if arg == "--config" {
+        path = Some(value);
+      } else {
+        overrides.push((String::from(&arg[2..]), value));
+      }
+    } else {
+      rest.push(arg);
+    }
+  }
+
+  let mut config =
+    match path {
+      None => try!(load(Path::new(DEFAULT_PATH), false)),
+      Some(path) => try!(load(Path::new(&path), true)),
+    };
+  for (key, value) in overrides {
+    try!(config.set(&key, &value));
+  }
+  try!(config.validate());
+
+  Ok((config, rest))
+}
+
+#[cfg(test)]
+mod test {
+  use std;
+  use std::io::Write;
+  use std::path::{Path, PathBuf};
+  use time;
+
+  use super::*;
+
+  fn config_file(contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("playform-config-test-{}", time::precise_time_ns()));
+    std::fs::File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+    path
+  }
+
+  fn args(args: &[&str]) -> std::vec::IntoIter<String> {
+    args.iter().map(|&arg| String::from(arg)).collect::<Vec<_>>().into_iter()
+  }
+
+  #[test]
+  fn load_file() {
+    let path = config_file("# A comment\n\nworld = saves/home \nseed=7\n  metrics_csv =  metrics.csv\n");
+    let config = load(&path, true);
+    std::fs::remove_file(&path).unwrap();
+
+    let config = config.unwrap();
+    assert_eq!(config.world, PathBuf::from("saves/home"));
+    assert_eq!(config.seed, 7);
+    assert_eq!(config.metrics_csv, Some(PathBuf::from("metrics.csv")));
+    // Anything not in the file keeps its default.
+    assert_eq!(config.updates_per_second, default().updates_per_second);
+  }
+
+  #[test]
+  fn load_bad_file() {
+    for contents in &["seed 7\n", "seed = seven\n", "seed = -1\n"] {
+      let path = config_file(contents);
+      let config = load(&path, true);
+      std::fs::remove_file(&path).unwrap();
+      assert!(config.is_err(), "{:?} loaded", contents);
+    }
+  }
+
+  #[test]
+  fn load_missing_file() {
+    let path = Path::new("/nonexistent/playform.conf");
+    assert!(load(path, false).is_ok());
+    assert!(load(path, true).is_err());
+  }
+
+  #[test]
+  fn args_override_file() {
+    let path = config_file("seed = 1\nterrain_threads = 2\n");
+    let parsed = of_args(args(&["ipc:///tmp/test.ipc", "--config", path.to_str().unwrap(), "--seed", "3"]));
+    std::fs::remove_file(&path).unwrap();
+
+    let (config, rest) = parsed.unwrap();
+    assert_eq!(config.seed, 3);
+    assert_eq!(config.terrain_threads, 2);
+    assert_eq!(rest, vec!(String::from("ipc:///tmp/test.ipc")));
+  }
+
+  #[test]
+  fn bad_args() {
+    let path = config_file("");
+    let config = path.to_str().unwrap();
+    let missing_value = of_args(args(&["--config", config, "--seed"]));
+    let invalid = of_args(args(&["--config", config, "--terrain_threads", "0"]));
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(missing_value.is_err());
+    assert!(invalid.is_err());
+    assert!(of_args(args(&["--config", "/nonexistent/playform.conf"])).is_err());
+  }
+
+  #[test]
+  fn unknown_key() {
+    assert!(default().set("sead", "7").is_err());
+
+    let path = config_file("sead = 7\n");
+    let from_file = load(&path, true);
+    let from_args = of_args(args(&["--config", path.to_str().unwrap(), "--sead", "7"]));
+    std::fs::remove_file(&path).unwrap();
+    assert!(from_file.is_err());
+    assert!(from_args.is_err());
+  }
+
+  #[test]
+  fn validate_bounds() {
+    assert!(default().validate().is_ok());
+
+    let cases = [
+      ("world", "", ""),
+      ("updates_per_second", "0", "1"),
+      ("updates_per_second", "1001", "1000"),
+      ("sun_tick_ns", "0", "1"),
+      ("player_load_distance", "0", "1"),
+      ("player_load_distance", "65", "64"),
+      ("autosave_seconds", "0", "1"),
+      ("terrain_threads", "0", "1"),
+      ("terrain_threads", "65", "64"),
+      ("terrain_memory_mb", "0", "1"),
+    ];
+    for &(key, bad, good) in &cases {
+      let mut config = default();
+      config.set(key, bad).unwrap();
+      assert!(config.validate().is_err(), "{} = {:?} is valid", key, bad);
+      if !good.is_empty() {
+        config.set(key, good).unwrap();
+        assert!(config.validate().is_ok(), "{} = {:?} is invalid", key, good);
+      }
+    }
+  }
+}
@@ -0,0 +1,87 @@
+//! Keep the server's terrain within its memory budget by dropping regions nobody's near.
+//! Untouched terrain is regenerated from the seed when it's needed again; edits are saved before they're dropped,
+//! and read back in with the rest of their region.
+
+use cgmath::{Point3, InnerSpace};
+use std;
+
+use common::fnv_set;
+use common::trace;
+use common::world_position;
+
+use gaia_pool;
+use regions;
+use server;
+
+/// Once over budget, regions are dropped until the terrain is down to this portion of the budget,
+/// so it isn't right back over as soon as anything else loads.
+const LOW_WATER: f32 = 0.8;
+
+/// If the terrain is over its memory budget, drop the regions farthest from any player or mob,
+/// leaving those anyone has loaded, or is close enough to soon load.
+pub fn evict_terrain(server: &server::T, gaia: &gaia_pool::T) {
+  let budget = (server.config.terrain_memory_mb as usize) << 20;
+  let mut usage = server.terrain_loader.terrain.ram_usage();
+  server.metrics.record_voxel_bytes(usage);
+  if usage <= budget {
+    return
+  }
+
+  trace::time("evict_terrain", || {
+    // Edits and loads both read in regions, then use them; don't drop a region partway through either.
+    // Otherwise, e.g., a load could regenerate a region without its edits, and send that out.
+    let _gaia_paused = gaia.pause();
+
+    let mut observers: Vec<world_position::T> =
+      server.mobs.lock().unwrap().values().map(|mob| mob.position).collect();
+    observers.extend(server.players.lock().unwrap().values().map(|player| player.position));
+
+    let owned: fnv_set::T<regions::Id> =
+      server.terrain_loader.lod_map.lock().unwrap().positions().iter()
+      .map(regions::of_voxel)
+      .collect();
+
+    let region_width = (1 << regions::LG_SIZE) as f64;
+    // Regions near someone's loaded surroundings stay, so they can wander a bit without regenerating anything.
+    let keep_distance = server.config.player_load_distance as f64 + 2.0 * region_width;
+
+    let mut candidates: Vec<(f64, regions::Id)> =
+      server.regions.loaded().into_iter()
+      .filter(|region| !owned.contains(region))
+      .map(|region| {
+        let center =
+          Point3::new(
+            (region.x as f64 + 0.5) * region_width,
+            (region.y as f64 + 0.5) * region_width,
+            (region.z as f64 + 0.5) * region_width,
+          );
+        let distance =
+          observers.iter()
+          .map(|observer| (*observer - center).magnitude())
+          .fold(std::f64::INFINITY, f64::min);
+        (distance, region)
+      })
+      .filter(|&(distance, _)| distance > keep_distance)
+      .collect();
+    // Farthest first.
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+    let target = (budget as f32 * LOW_WATER) as usize;
+    let mut evicted = 0;
+    for (_, region) in candidates {
+      if usage <= target {
+        break
+      }
+      if let Some(freed) = server.regions.evict(&server.terrain_loader.terrain, &region) {
+        usage = usage.saturating_sub(freed);
+        evicted += 1;
+      }
+    }
+    server.metrics.record_voxel_bytes(usage);
+
+    info!("Evicted {} terrain regions; terrain is using about {} of {} MB", evicted, usage >> 20, budget >> 20);
+    if usage > budget {
+      warn!("Terrain is still over its memory budget; the rest is in use");
+    }
+  })
+}
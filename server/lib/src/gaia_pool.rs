@@ -0,0 +1,105 @@
+//! Queue gaia updates, and carry them out on a pool of worker threads.
+//! Terrain loads don't depend on each other, so any worker can take one. Edits are applied one at a time,
+//! in the order they were made, since each one can depend on the ones before it.
+
+use std::collections::VecDeque;
+
+use common::lock::{Mutex, MutexGuard, RwLock, RwLockWriteGuard};
+
+use lock_order;
+use server;
+use update_gaia;
+use update_gaia::update_gaia;
+
+#[allow(missing_docs)]
+pub struct T {
+  loads         : Mutex<VecDeque<update_gaia::Message>>,
+  edits         : Mutex<VecDeque<update_gaia::Message>>,
+  /// Held by whichever worker is applying an edit.
+  applying_edit : Mutex<()>,
+  /// Held, shared, by every worker carrying out an update, so `pause` can wait for them all to finish.
+  running       : RwLock<()>,
+  /// Passed through on the way into `running`, and held by `pause`, so no update can start while it's waiting.
+  turnstile     : Mutex<()>,
+}
+
+/// Keeps gaia from carrying out any updates until it's dropped.
+pub struct Paused<'a> {
+  _turnstile : MutexGuard<'a, ()>,
+  _running   : RwLockWriteGuard<'a, ()>,
+}
+
+#[allow(missing_docs)]
+pub fn new() -> T {
+  T {
+    loads         : Mutex::new(lock_order::LEAF, VecDeque::new()),
+    edits         : Mutex::new(lock_order::LEAF, VecDeque::new()),
+    applying_edit : Mutex::new(lock_order::APPLYING_EDIT, ()),
+    running       : RwLock::new(lock_order::GAIA_RUNNING, ()),
+    turnstile     : Mutex::new(lock_order::GAIA_TURNSTILE, ()),
+  }
+}
+
+impl T {
+  #[allow(missing_docs)]
+  pub fn push(&self, up: update_gaia::Message) {
+    if up.is_edit() {
+      self.edits.lock().unwrap().push_back(up);
+    } else {
+      self.loads.lock().unwrap().push_back(up);
+    }
+  }
+
+  /// The number of queued loads and edits.
+  pub fn queued(&self) -> (usize, usize) {
+    let loads = self.loads.lock().unwrap().len();
+    let edits = self.edits.lock().unwrap().len();
+    (loads, edits)
+  }
+
+  /// Take the next queued edit, e.g. to apply it while shutting down.
+  pub fn pop_edit(&self) -> Option<update_gaia::Message> {
+    self.edits.lock().unwrap().pop_front()
+  }
+
+  /// Wait for every update being carried out, loads and edits alike, to finish,
+  /// and keep any more from starting until the guard is dropped.
+  pub fn pause<'a>(&'a self) -> Paused<'a> {
+    let turnstile = self.turnstile.lock().unwrap();
+    let running = self.running.write().unwrap();
+    Paused {
+      _turnstile : turnstile,
+      _running   : running,
+    }
+  }
+
+  fn carry_out(&self, server: &server::T, up: update_gaia::Message) {
+    // Wait out any pause before starting.
+    drop(self.turnstile.lock().unwrap());
+    let _running = self.running.read().unwrap();
+    update_gaia(server, up);
+  }
+
+  /// Carry out one queued update, if there are any. Returns whether there was one.
+  pub fn work(&self, server: &server::T) -> bool {
+    // Edits go first, since players are waiting to see them. If another worker is applying one,
+    // the rest have to wait their turn anyway, so we go load something instead.
+    if let Ok(_applying_edit) = self.applying_edit.try_lock() {
+      let up = self.edits.lock().unwrap().pop_front();
+      if let Some(up) = up {
+        self.carry_out(server, up);
+        return true
+      }
+    }
+
+    let up = self.loads.lock().unwrap().pop_front();
+    match up {
+      None => false,
+      Some(up) => {
+        self.carry_out(server, up);
+        true
+      },
+    }
+  }
+}
+
@@ -0,0 +1,166 @@
+//! Each player's recent terrain edits, so they can be undone and redone.
+
+use std::collections::VecDeque;
+
+use common::voxel;
+
+/// The most edits we remember for each player.
+const MAX_EDITS: usize = 32;
+
+/// A voxel's bounds, and its contents before and after an edit.
+pub type Change = (voxel::bounds::T, voxel::T, voxel::T);
+
+pub struct Edit {
+  pub changes: Vec<Change>,
+}
+
+impl Edit {
+  /// The edit that would put things back the way they were.
+  pub fn inverse(&self) -> Edit {
+    Edit {
+      changes: self.changes.iter().map(|&(bounds, before, after)| (bounds, after, before)).collect(),
+    }
+  }
+}
+
+pub struct T {
+  undo : VecDeque<Edit>,
+  redo : Vec<Edit>,
+}
+
+pub fn new() -> T {
+  T {
+    undo : VecDeque::new(),
+    redo : Vec::new(),
+  }
+}
+
+impl T {
+  fn push_undo(&mut self, edit: Edit) {
+    if edit.changes.is_empty() {
+      return
+    }
+    self.undo.push_back(edit);
+    if self.undo.len() > MAX_EDITS {
+      self.undo.pop_front();
+    }
+  }
+
+  /// Remember a new edit. This forgets anything that could have been redone.
+  pub fn push(&mut self, edit: Edit) {
+    self.redo.clear();
+    self.push_undo(edit);
+  }
+
+  /// Take the most recent edit, to be undone.
+  pub fn pop_undo(&mut self) -> Option<Edit> {
+    self.undo.pop_back()
+  }
+
+  /// Remember an edit that was undone. `edit` is the change the undo made.
+  pub fn push_redo(&mut self, edit: Edit) {
+    if edit.changes.is_empty() {
+      return
+    }
+    self.redo.push(edit);
+  }
+
+  /// Take the most recently undone edit, to be redone.
+  pub fn pop_redo(&mut self) -> Option<Edit> {
+    self.redo.pop()
+  }
+
+  /// Remember an edit that was redone, without forgetting anything else that could be redone.
+  pub fn push_redone(&mut self, edit: Edit) {
+    self.push_undo(edit);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  /// An edit that turns the voxel at x = `i` to stone, so edits can be told apart by position.
+  fn edit(i: i32) -> Edit {
+    Edit {
+      changes: vec!((
+        voxel::bounds::new(i, 0, 0, 0),
+        voxel::Volume(voxel::Material::Empty),
+        voxel::Volume(voxel::Material::Stone),
+      )),
+    }
+  }
+
+  fn which(edit: Option<Edit>) -> Option<voxel::bounds::T> {
+    edit.map(|edit| edit.changes[0].0)
+  }
+
+  fn at(i: i32) -> Option<voxel::bounds::T> {
+    Some(voxel::bounds::new(i, 0, 0, 0))
+  }
+
+  #[test]
+  fn undo_redo_order() {
+    let mut history = new();
+    for i in 0 .. 3 {
+      history.push(edit(i));
+    }
+
+    // Undo most recent first.
+    for &i in &[2, 1] {
+      let undone = history.pop_undo().unwrap();
+      assert_eq!(undone.changes[0].0, voxel::bounds::new(i, 0, 0, 0));
+      history.push_redo(undone.inverse());
+    }
+
+    // Redo in the reverse order of undoing.
+    let redone = history.pop_redo().unwrap();
+    assert_eq!(redone.changes[0].0, voxel::bounds::new(1, 0, 0, 0));
+    history.push_redone(redone.inverse());
+    assert_eq!(which(history.pop_redo()), at(2));
+    assert_eq!(which(history.pop_redo()), None);
+
+    assert_eq!(which(history.pop_undo()), at(1));
+    assert_eq!(which(history.pop_undo()), at(0));
+    assert_eq!(which(history.pop_undo()), None);
+  }
+
+  #[test]
+  fn oldest_edits_are_forgotten() {
+    let mut history = new();
+    let count = MAX_EDITS as i32 + 5;
+    for i in 0 .. count {
+      history.push(edit(i));
+    }
+
+    for i in (count - MAX_EDITS as i32 .. count).rev() {
+      assert_eq!(which(history.pop_undo()), at(i));
+    }
+    assert_eq!(which(history.pop_undo()), None);
+  }
+
+  #[test]
+  fn new_edit_clears_redo() {
+    let mut history = new();
+    history.push(edit(0));
+    history.push(edit(1));
+    let undone = history.pop_undo().unwrap();
+    history.push_redo(undone.inverse());
+
+    history.push(edit(2));
+    assert_eq!(which(history.pop_redo()), None);
+    assert_eq!(which(history.pop_undo()), at(2));
+    assert_eq!(which(history.pop_undo()), at(0));
+  }
+
+  #[test]
+  fn empty_edits_are_ignored() {
+    let mut history = new();
+    history.push(edit(0));
+    history.push(Edit { changes: vec!() });
+    history.push_redo(Edit { changes: vec!() });
+    assert_eq!(which(history.pop_redo()), None);
+    assert_eq!(which(history.pop_undo()), at(0));
+    assert_eq!(which(history.pop_undo()), None);
+  }
+}
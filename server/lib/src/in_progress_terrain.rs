@@ -1,9 +1,10 @@
 use collision::{Aabb3};
-use std::sync::Mutex;
 
 use common::fnv_map;
 use common::id_allocator;
+use common::lock::Mutex;
 use common::voxel;
+use common::world_position;
 
 use entity;
 use physics;
@@ -37,7 +38,7 @@ impl T {
         entry.insert(id);
 
         let (low, high) = block_position.corners();
-        physics.lock().unwrap().insert_misc(id, &Aabb3::new(low, high));
+        physics.lock().unwrap().insert_misc(id, &world_position::bounds_of_f32(&Aabb3::new(low, high)));
         true
       }
     }
@@ -0,0 +1,91 @@
+//! The materials a player has collected, and can build with.
+
+use std::cmp::Ordering;
+
+use common::fnv_map;
+use common::voxel;
+
+pub struct T {
+  counts: fnv_map::T<voxel::Material, u32>,
+}
+
+pub fn new() -> T {
+  T {
+    counts: fnv_map::new(),
+  }
+}
+
+/// An inventory holding `contents`, e.g. as saved from `T::contents`.
+pub fn of(contents: &[(voxel::Material, u32)]) -> T {
+  let mut inventory = new();
+  for &(material, count) in contents {
+    if count > 0 {
+      *inventory.counts.entry(material).or_insert(0) += count;
+    }
+  }
+  inventory
+}
+
+/// The material a unit voxel counts as when it's collected or placed.
+pub fn material_of(voxel: &voxel::T) -> voxel::Material {
+  match *voxel {
+    voxel::Volume(material) => material,
+    voxel::Surface(ref surface) => surface.corner,
+  }
+}
+
+/// Net material changes, e.g. from a brush. Positive amounts are collected; negative are spent.
+pub type Delta = fnv_map::T<voxel::Material, i64>;
+
+/// Account for a unit voxel changing from `before` to `after`.
+pub fn record_change(delta: &mut Delta, before: &voxel::T, after: &voxel::T) {
+  let before = material_of(before);
+  let after = material_of(after);
+  if before == after {
+    return
+  }
+  if before != voxel::Material::Empty {
+    *delta.entry(before).or_insert(0) += 1;
+  }
+  if after != voxel::Material::Empty {
+    *delta.entry(after).or_insert(0) -= 1;
+  }
+}
+
+impl T {
+  pub fn count(&self, material: voxel::Material) -> u32 {
+    self.counts.get(&material).cloned().unwrap_or(0)
+  }
+
+  /// The first material in `cost` we don't have enough of, if any.
+  pub fn shortfall(&self, cost: &[(voxel::Material, u32)]) -> Option<voxel::Material> {
+    cost.iter()
+      .find(|&&(material, needed)| self.count(material) < needed)
+      .map(|&(material, _)| material)
+  }
+
+  /// Apply a change in materials. If it spends more of a material than we have, nothing changes,
+  /// and that material is returned.
+  pub fn apply(&mut self, delta: &Delta) -> Result<(), voxel::Material> {
+    for (&material, &amount) in delta {
+      if amount < 0 && (self.count(material) as i64) < -amount {
+        return Err(material)
+      }
+    }
+    for (&material, &amount) in delta {
+      let count = self.counts.entry(material).or_insert(0);
+      match amount.cmp(&0) {
+        Ordering::Greater => *count = count.saturating_add(amount as u32),
+        Ordering::Less    => *count -= (-amount) as u32,
+        Ordering::Equal   => {},
+      }
+    }
+    self.counts.retain(|_, count| *count > 0);
+    Ok(())
+  }
+
+  /// Every material we have any of.
+  pub fn contents(&self) -> Vec<(voxel::Material, u32)> {
+    self.counts.iter().map(|(&material, &count)| (material, count)).collect()
+  }
+}
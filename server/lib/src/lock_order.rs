@@ -0,0 +1,49 @@
+//! The server's lock hierarchy. A thread may only wait on a lock that comes later in this list than every lock it
+//! already holds (see `common::lock`); debug builds panic when that's broken.
+//!
+//! The order follows how the server actually nests its locks:
+//!
+//!   * An edit holds `APPLYING_EDIT` the whole time it's applied.
+//!   * Every gaia update passes through `GAIA_TURNSTILE` and holds `GAIA_RUNNING` while it's carried out.
+//!   * The world update holds `MOBS` while running mob behaviors, which look at `PLAYERS`, `PHYSICS` and navigation.
+//!   * `MOBS` and `PLAYERS` are held while their surroundings are loaded, which takes the terrain loader's locks.
+//!   * Loading a block holds `LOD_MAP`, `IN_PROGRESS_TERRAIN`, `LOADED_TERRAIN` and `NAVIGATION` while reading
+//!     `TERRAIN_VOXELS` and updating `PHYSICS`.
+//!   * Reading in saved regions holds `REGIONS_LOADED`, then `TERRAIN_VOXELS`, then `REGIONS_EDITED`.
+//!     Saving and evicting them hold `REGIONS_WRITING` first.
+//!   * Evicting terrain holds `GAIA_TURNSTILE` and `GAIA_RUNNING`, so no load or edit is halfway done.
+//!   * `SUN` is held while telling `CLIENTS` about it.
+//!   * Nothing is locked while holding a `LEAF`, e.g. an id allocator or a queue.
+
+use common::lock::Level;
+
+macro_rules! levels {
+  ($($name:ident = $rank:expr,)*) => {
+    $(
+      #[allow(missing_docs)]
+      pub const $name: Level = Level { rank: $rank, name: stringify!($name) };
+    )*
+  }
+}
+
+levels! {
+  APPLYING_EDIT       = 10,
+  GAIA_TURNSTILE      = 12,
+  GAIA_RUNNING        = 14,
+  MOBS                = 20,
+  PLAYERS             = 30,
+  SUN                 = 40,
+  LOD_MAP             = 50,
+  IN_PROGRESS_TERRAIN = 60,
+  LOADED_TERRAIN      = 70,
+  NAVIGATION          = 80,
+  REGIONS_WRITING     = 85,
+  REGIONS_LOADED      = 90,
+  TERRAIN_VOXELS      = 100,
+  REGIONS_EDITED      = 110,
+  REGIONS_DIRTY       = 120,
+  REGIONS_UNREADABLE  = 130,
+  PHYSICS             = 140,
+  CLIENTS             = 150,
+  LEAF                = 1000,
+}
@@ -47,6 +47,16 @@ impl Map {
     }
   }
 
+  /// The number of positions with any owners.
+  pub fn len(&self) -> usize {
+    self.loaded.len()
+  }
+
+  /// Every position with any owners.
+  pub fn positions(&self) -> Vec<voxel::bounds::T> {
+    self.loaded.keys().cloned().collect()
+  }
+
   /// Find out what T is up at a `position`.
   pub fn get<'a>(
     &'a self,
@@ -0,0 +1,289 @@
+//! Numbers for watching a live server. Everything's sampled once a second, except voxel memory, which is as of the
+//! last time eviction measured it. The latest sample is served as a plain-text page on a local port, and every sample
+//! can be appended to a CSV file, which rolls over when it's big.
+
+use std;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use time;
+
+use common::lock::Mutex;
+use common::protocol;
+
+use gaia_pool;
+use lock_order;
+use server;
+
+/// Once the CSV file is this big, it's moved to `<file>.1` (replacing any older one) and a new one is started.
+const MAX_CSV_BYTES: u64 = 16 << 20;
+/// How long the metrics server waits between checks for new connections.
+const ACCEPT_SLEEP_MS: u64 = 50;
+
+const CSV_HEADER: &'static str =
+  "time,ticks,mean_tick_ms,max_tick_ms,queued_loads,queued_edits,blocks_generated_per_second,lod_map_blocks,\
+   voxel_bytes,physics_terrain,physics_misc,players,mobs,clients,bytes_sent,messages_sent";
+
+/// Tick times since the last sample.
+struct Ticks {
+  count    : u32,
+  total_ns : u64,
+  max_ns   : u64,
+}
+
+/// What the rest of the server reports as it runs, and the latest sample.
+pub struct T {
+  ticks       : Mutex<Ticks>,
+  /// Measuring this walks the whole voxel tree, so it's only updated when eviction checks it.
+  voxel_bytes : AtomicUsize,
+  /// The latest sample, as it's served.
+  latest      : Mutex<String>,
+}
+
+#[allow(missing_docs)]
+pub fn new() -> T {
+  T {
+    ticks       : Mutex::new(lock_order::LEAF, Ticks { count: 0, total_ns: 0, max_ns: 0 }),
+    voxel_bytes : AtomicUsize::new(0),
+    latest      : Mutex::new(lock_order::LEAF, String::from("No metrics yet\n")),
+  }
+}
+
+impl T {
+  /// Note how long one world update took.
+  pub fn record_tick(&self, ns: u64) {
+    let mut ticks = self.ticks.lock().unwrap();
+    ticks.count += 1;
+    ticks.total_ns += ns;
+    ticks.max_ns = std::cmp::max(ticks.max_ns, ns);
+  }
+
+  /// Note how much memory the terrain's voxels were last measured to be using.
+  pub fn record_voxel_bytes(&self, bytes: usize) {
+    self.voxel_bytes.store(bytes, Ordering::Relaxed);
+  }
+
+  fn take_ticks(&self) -> Ticks {
+    let mut ticks = self.ticks.lock().unwrap();
+    std::mem::replace(&mut *ticks, Ticks { count: 0, total_ns: 0, max_ns: 0 })
+  }
+}
+
+/// Everything measured at one point in time.
+struct Sample {
+  /// Seconds since the epoch.
+  time                        : i64,
+  /// World updates since the last sample.
+  ticks                       : u32,
+  mean_tick_ms                : f64,
+  max_tick_ms                 : f64,
+  queued_loads                : usize,
+  queued_edits                : usize,
+  blocks_generated_per_second : f64,
+  lod_map_blocks              : usize,
+  /// As of the last eviction check, rather than now.
+  voxel_bytes                 : usize,
+  physics_terrain             : usize,
+  physics_misc                : usize,
+  players                     : usize,
+  mobs                        : usize,
+  /// The bytes and messages sent to each client so far.
+  clients                     : Vec<(protocol::ClientId, u64, u64)>,
+}
+
+/// Take a sample. `blocks_generated` is how many blocks had been generated as of the last sample,
+/// `seconds` ago; it's updated to the current count.
+fn sample(server: &server::T, gaia: &gaia_pool::T, blocks_generated: &mut usize, seconds: f64) -> Sample {
+  let ticks = server.metrics.take_ticks();
+  let (queued_loads, queued_edits) = gaia.queued();
+
+  let generated = server.terrain_loader.terrain.blocks_generated();
+  let blocks_generated_per_second = (generated - *blocks_generated) as f64 / seconds;
+  *blocks_generated = generated;
+
+  // Each lock is taken on its own, so none are held at once.
+  let players = server.players.lock().unwrap().len();
+  let mobs = server.mobs.lock().unwrap().len();
+  let lod_map_blocks = server.terrain_loader.lod_map.lock().unwrap().len();
+  let voxel_bytes = server.metrics.voxel_bytes.load(Ordering::Relaxed);
+  let (physics_terrain, physics_misc) = server.physics.lock().unwrap().counts();
+  let mut clients: Vec<_> =
+    server.clients.lock().unwrap().iter()
+    .map(|(id, client)| (*id, client.bytes_sent, client.messages_sent))
+    .collect();
+  clients.sort_by(|a, b| a.0.cmp(&b.0));
+
+  Sample {
+    time                        : time::get_time().sec,
+    ticks                       : ticks.count,
+    mean_tick_ms                :
+      if ticks.count == 0 { 0.0 } else { ticks.total_ns as f64 / ticks.count as f64 / 1e6 },
+    max_tick_ms                 : ticks.max_ns as f64 / 1e6,
+    queued_loads                : queued_loads,
+    queued_edits                : queued_edits,
+    blocks_generated_per_second : blocks_generated_per_second,
+    lod_map_blocks              : lod_map_blocks,
+    voxel_bytes                 : voxel_bytes,
+    physics_terrain             : physics_terrain,
+    physics_misc                : physics_misc,
+    players                     : players,
+    mobs                        : mobs,
+    clients                     : clients,
+  }
+}
+
+impl Sample {
+  fn bytes_sent(&self) -> u64 {
+    self.clients.iter().map(|&(_, bytes, _)| bytes).sum()
+  }
+
+  fn messages_sent(&self) -> u64 {
+    self.clients.iter().map(|&(_, _, messages)| messages).sum()
+  }
+
+  /// One `name value` line per metric, then one line per client.
+  fn to_text(&self) -> String {
+    let mut text =
+      format!(
+        "time {}\n\
+         ticks {}\n\
+         mean_tick_ms {:.3}\n\
+         max_tick_ms {:.3}\n\
+         queued_loads {}\n\
+         queued_edits {}\n\
+         blocks_generated_per_second {:.1}\n\
+         lod_map_blocks {}\n\
+         voxel_bytes {}\n\
+         physics_terrain {}\n\
+         physics_misc {}\n\
+         players {}\n\
+         mobs {}\n\
+         clients {}\n\
+         bytes_sent {}\n\
+         messages_sent {}\n",
+        self.time, self.ticks, self.mean_tick_ms, self.max_tick_ms, self.queued_loads, self.queued_edits,
+        self.blocks_generated_per_second, self.lod_map_blocks, self.voxel_bytes, self.physics_terrain,
+        self.physics_misc, self.players, self.mobs, self.clients.len(), self.bytes_sent(), self.messages_sent(),
+      );
+    for &(id, bytes, messages) in &self.clients {
+      text.push_str(&format!("client {:?} bytes_sent {} messages_sent {}\n", id, bytes, messages));
+    }
+    text
+  }
+
+  /// A row to go under `CSV_HEADER`. Clients are only counted in total, so every row has the same columns.
+  fn to_csv(&self) -> String {
+    format!(
+      "{},{},{:.3},{:.3},{},{},{:.1},{},{},{},{},{},{},{},{},{}\n",
+      self.time, self.ticks, self.mean_tick_ms, self.max_tick_ms, self.queued_loads, self.queued_edits,
+      self.blocks_generated_per_second, self.lod_map_blocks, self.voxel_bytes, self.physics_terrain,
+      self.physics_misc, self.players, self.mobs, self.clients.len(), self.bytes_sent(), self.messages_sent(),
+    )
+  }
+}
+
+/// Append a row to the CSV file at `path`, starting a new file (with a header) if it's missing or too big.
+fn append_csv(path: &Path, row: &str) -> io::Result<()> {
+  let size =
+    match fs::metadata(path) {
+      Ok(metadata) => Some(metadata.len()),
+      Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+      Err(err) => return Err(err),
+    };
+  let needs_header =
+    match size {
+      None => true,
+      Some(size) if size >= MAX_CSV_BYTES => {
+        let mut old = PathBuf::from(path);
+        let mut name = old.file_name().unwrap().to_os_string();
+        name.push(".1");
+        old.set_file_name(name);
+        try!(fs::rename(path, &old));
+        true
+      },
+      Some(_) => false,
+    };
+
+  let mut file = try!(fs::OpenOptions::new().create(true).append(true).open(path));
+  if needs_header {
+    try!(writeln!(file, "{}", CSV_HEADER));
+  }
+  file.write_all(row.as_bytes())
+}
+
+/// Sample the server once a second until `quit_signal` is set, keeping the latest sample to serve,
+/// and appending each to the configured CSV file, if any.
+pub fn run_sampler(server: &server::T, gaia: &gaia_pool::T, quit_signal: &std::sync::Mutex<bool>) {
+  let mut blocks_generated = server.terrain_loader.terrain.blocks_generated();
+  let mut last_sample = time::precise_time_ns();
+  while !*quit_signal.lock().unwrap() {
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let now = time::precise_time_ns();
+    let seconds = (now - last_sample) as f64 / 1e9;
+    last_sample = now;
+
+    let sample = sample(server, gaia, &mut blocks_generated, seconds);
+    info!("Outstanding gaia updates: {} loads, {} edits", sample.queued_loads, sample.queued_edits);
+
+    if let Some(ref path) = server.config.metrics_csv {
+      if let Err(err) = append_csv(path, &sample.to_csv()) {
+        warn!("Couldn't write metrics to {:?}: {}", path, err);
+      }
+    }
+    *server.metrics.latest.lock().unwrap() = sample.to_text();
+  }
+}
+
+/// Answer one request with the latest sample, whatever was asked for.
+fn respond(metrics: &T, mut stream: TcpStream) -> io::Result<()> {
+  try!(stream.set_nonblocking(false));
+  // The request itself doesn't matter, but read some of it so the client isn't cut off mid-send.
+  try!(stream.set_read_timeout(Some(std::time::Duration::from_millis(100))));
+  let mut request = [0; 1024];
+  let _ = stream.read(&mut request);
+
+  let body = metrics.latest.lock().unwrap().clone();
+  try!(
+    write!(
+      stream,
+      "HTTP/1.0 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+      body.len(), body,
+    )
+  );
+  stream.flush()
+}
+
+/// Serve the latest sample to anyone on this machine who connects to `port`, until `quit_signal` is set.
+pub fn serve(metrics: &T, port: u16, quit_signal: &std::sync::Mutex<bool>) {
+  let listener =
+    match TcpListener::bind(("127.0.0.1", port)) {
+      Ok(listener) => listener,
+      Err(err) => {
+        warn!("Couldn't serve metrics on port {}: {}", port, err);
+        return
+      },
+    };
+  // Don't block, so we notice when to quit.
+  listener.set_nonblocking(true).unwrap();
+  info!("Serving metrics at http://127.0.0.1:{}/", port);
+
+  while !*quit_signal.lock().unwrap() {
+    match listener.accept() {
+      Ok((stream, _)) => {
+        if let Err(err) = respond(metrics, stream) {
+          debug!("Error serving metrics: {}", err);
+        }
+      },
+      Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+        std::thread::sleep(std::time::Duration::from_millis(ACCEPT_SLEEP_MS));
+      },
+      Err(err) => {
+        warn!("Error accepting a metrics connection: {}", err);
+        std::thread::sleep(std::time::Duration::from_millis(ACCEPT_SLEEP_MS));
+      },
+    }
+  }
+}
@@ -1,17 +1,29 @@
-use cgmath::{Point3, Vector3};
+use cgmath::{Vector3};
 
 use common::surroundings_loader;
+use common::world_position;
 
+use combat;
 use entity;
 use lod;
+use navigation;
 use server;
 
 pub type Behavior = fn(&server::T, &mut Mob);
 
 pub struct Mob {
-  pub position            : Point3<f32>,
+  pub position            : world_position::T,
   pub speed               : Vector3<f32>,
   pub behavior            : Behavior,
+  /// The route the mob is currently following, if any.
+  pub path                : Option<navigation::Path>,
+  /// How many consecutive updates this mob has been far away from every player.
+  pub ticks_unobserved    : u32,
+  pub health              : combat::Health,
+  /// World updates until this mob can attack again.
+  pub attack_cooldown     : u32,
+  /// World updates until this mob recovers from being hit.
+  pub stagger_ticks       : u32,
 
   pub entity_id           : entity::id::Mob,
   pub physics_id          : entity::id::Misc,
@@ -0,0 +1,198 @@
+use cgmath::{Point3, EuclideanSpace, InnerSpace, Vector3};
+
+use common::protocol;
+use common::world_position;
+
+use combat;
+use entity;
+use mob;
+use navigation;
+use server;
+
+const WALK_SPEED: f32 = 0.15;
+const JUMP_SPEED: f32 = 0.6;
+/// Plan a new path once things have strayed this many blocks from the current one.
+const REPATH_DISTANCE: i32 = 2;
+
+fn center(bounds: &world_position::Bounds) -> world_position::T {
+  (bounds.min + bounds.max.to_vec()) * 0.5
+}
+
+fn block_distance(p1: &Point3<i32>, p2: &Point3<i32>) -> i32 {
+  (p1.x - p2.x).abs() + (p1.y - p2.y).abs() + (p1.z - p2.z).abs()
+}
+
+/// Steer a mob along a path to `goal`, planning a new one if necessary.
+fn walk_towards(world: &server::T, mob: &mut mob::Mob, goal: &Point3<i32>) {
+  let feet = navigation::feet_of(world.physics.lock().unwrap().get_bounds(mob.physics_id).unwrap());
+
+  let needs_path =
+    match mob.path {
+      None => true,
+      Some(ref path) => {
+        block_distance(&path.goal, goal) > REPATH_DISTANCE ||
+        path.waypoints.front().map(|w| block_distance(w, &feet) > REPATH_DISTANCE) == Some(true)
+      },
+    };
+  if needs_path {
+    let loaded = world.terrain_loader.loaded.read().unwrap();
+    mob.path = world.terrain_loader.navigation.lock().unwrap().find_path(&loaded, &feet, goal);
+  }
+
+  let waypoint =
+    match mob.path {
+      None => None,
+      Some(ref mut path) => {
+        if path.waypoints.front() == Some(&feet) {
+          path.waypoints.pop_front();
+        }
+        path.waypoints.front().cloned()
+      },
+    };
+
+  match waypoint {
+    None => {
+      // Nowhere to go (or nowhere closer); wait for things to change.
+      mob.speed.x = 0.0;
+      mob.speed.z = 0.0;
+    },
+    Some(waypoint) => {
+      let to_waypoint =
+        Vector3::new(
+          (waypoint.x as f64 + 0.5 - mob.position.x) as f32,
+          0.0,
+          (waypoint.z as f64 + 0.5 - mob.position.z) as f32,
+        );
+      let walk =
+        if to_waypoint.magnitude2() > WALK_SPEED * WALK_SPEED {
+          to_waypoint.normalize() * WALK_SPEED
+        } else {
+          to_waypoint
+        };
+      mob.speed.x = walk.x;
+      mob.speed.z = walk.z;
+
+      // Vertical speed is zeroed whenever we land, so this only jumps from the ground.
+      if waypoint.y > feet.y && mob.speed.y == 0.0 {
+        mob.speed.y = JUMP_SPEED;
+      }
+    },
+  }
+}
+
+/// The closest player to a mob, and the vector to them.
+fn nearest_player(world: &server::T, mob: &mob::Mob) -> Option<(entity::id::Player, Vector3<f32>)> {
+  let mob_posn = center(world.physics.lock().unwrap().get_bounds(mob.physics_id).unwrap());
+
+  let players: Vec<(entity::id::Player, entity::id::Misc)> =
+    world.players.lock().unwrap().values().map(|player| (player.entity_id, player.physics_id)).collect();
+  let mut players = players.into_iter();
+
+  let to = |physics_id| {
+    world_position::offset(&mob_posn, &center(world.physics.lock().unwrap().get_bounds(physics_id).unwrap()))
+  };
+
+  players.next().map(|(id, physics_id)| {
+    let mut min = (id, to(physics_id));
+    let mut min_d = min.1.magnitude2();
+    for (id, physics_id) in players {
+      let v = to(physics_id);
+      let d = v.magnitude2();
+      if d < min_d {
+        min = (id, v);
+        min_d = d;
+      }
+    }
+
+    min
+  })
+}
+
+fn to_player(world: &server::T, mob: &mob::Mob) -> Option<Vector3<f32>> {
+  nearest_player(world, mob).map(|(_, v)| v)
+}
+
+/// The feet of a player, given the vector to their center from a mob.
+fn player_feet(mob: &mob::Mob, to_player: &Vector3<f32>) -> Point3<i32> {
+  // Players are the same height as mobs, so their feet are as far below their center.
+  let player = mob.position + world_position::vector_of_f32(to_player);
+  Point3::new(
+    player.x.floor() as i32,
+    (player.y - 1.0 + 0.01).floor() as i32,
+    player.z.floor() as i32,
+  )
+}
+
+/// The initial behavior of a mob: wait for a player to come close, then follow them around.
+pub fn mob_behavior(world: &server::T, mob: &mut mob::Mob) {
+  match to_player(world, mob) {
+    None => { mob.behavior = mob_behavior },
+    Some(to_player) => {
+      if to_player.magnitude() < 2.0 {
+        mob.behavior = wait_for_distance;
+      }
+    },
+  }
+}
+
+fn wait_for_distance(world: &server::T, mob: &mut mob::Mob) {
+  match to_player(world, mob) {
+    None => { mob.behavior = mob_behavior },
+    Some(to_player) => {
+      if to_player.magnitude() > 8.0 {
+        mob.behavior = follow_player;
+      }
+    },
+  }
+}
+
+fn follow_player(world: &server::T, mob: &mut mob::Mob) {
+  match to_player(world, mob) {
+    None => { mob.behavior = mob_behavior },
+    Some(to_player) => {
+      if to_player.magnitude2() < 4.0 {
+        mob.behavior = wait_to_reset;
+        mob.speed = Vector3::new(0.0, 0.0, 0.0);
+        mob.path = None;
+      } else {
+        let goal = player_feet(mob, &to_player);
+        walk_towards(world, mob, &goal);
+      }
+    },
+  }
+}
+
+fn wait_to_reset(world: &server::T, mob: &mut mob::Mob) {
+  match to_player(world, mob) {
+    None => { mob.behavior = mob_behavior },
+    Some(to_player) => {
+      if to_player.magnitude() >= 2.0 {
+        mob.behavior = mob_behavior;
+      }
+    },
+  }
+}
+
+/// Chase down the nearest player and attack them.
+pub fn hostile(world: &server::T, mob: &mut mob::Mob) {
+  match nearest_player(world, mob) {
+    None => {
+      mob.speed.x = 0.0;
+      mob.speed.z = 0.0;
+      mob.path = None;
+    },
+    Some((player_id, to_player)) => {
+      if to_player.magnitude() <= combat::MOB_ATTACK_RANGE {
+        mob.speed.x = 0.0;
+        mob.speed.z = 0.0;
+        if mob.attack_cooldown == 0 {
+          mob.attack_cooldown = combat::ATTACK_COOLDOWN_TICKS;
+          combat::hit(world, protocol::Combatant::Player(player_id), combat::MOB_ATTACK_DAMAGE, &to_player);
+        }
+      } else {
+        let goal = player_feet(mob, &to_player);
+        walk_towards(world, mob, &goal);
+      }
+    },
+  }
+}
@@ -15,6 +15,9 @@ extern crate log;
 extern crate nanomsg;
 extern crate num;
 extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate stopwatch;
 extern crate terrain;
 extern crate test;
@@ -22,17 +25,34 @@ extern crate thread_scoped;
 extern crate time;
 extern crate voxel_data;
 
+pub mod admin;
+mod brush;
 mod client_recv_thread;
+mod clipboard;
+mod combat;
+pub mod config;
 mod entity;
+mod evict_terrain;
+mod gaia_pool;
+mod history;
 mod in_progress_terrain;
-mod init_mobs;
+mod inventory;
+mod lock_order;
 mod lod;
+mod metrics;
 mod mob;
+mod mob_behavior;
+mod navigation;
 mod octree;
+mod octree_grid;
 mod physics;
 mod player;
+mod regions;
 mod run;
+mod save_file;
 mod server;
+mod spawn;
+mod spawner;
 mod sun;
 mod terrain_loader;
 mod update_gaia;
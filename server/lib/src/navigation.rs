@@ -0,0 +1,387 @@
+//! Walkable graph over the loaded terrain, and A* search across it.
+//! Nodes are the unit blocks an agent's feet can occupy; they're discovered lazily from
+//! `terrain_loader::T::loaded` and cached until the terrain around them changes.
+
+use cgmath::{Point3};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+use common::fnv_map;
+use common::voxel;
+use common::world_position;
+
+use entity;
+
+/// Height, in blocks, of the open space an agent needs to stand in.
+pub const AGENT_HEIGHT: i32 = 2;
+/// The tallest ledge an agent can jump onto.
+pub const MAX_JUMP_HEIGHT: i32 = 1;
+/// The furthest an agent will deliberately drop.
+pub const MAX_DROP_HEIGHT: i32 = 3;
+
+/// Give up on a search after expanding this many nodes.
+const MAX_SEARCH_NODES: usize = 1 << 11;
+
+const WALK_COST: u32 = 10;
+// Extra costs per block climbed or dropped.
+const JUMP_COST: u32 = 10;
+const DROP_COST: u32 = 2;
+
+/// The fully-loaded terrain blocks, and their physics ids.
+pub type Loaded = fnv_map::T<voxel::bounds::T, Vec<entity::id::Terrain>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+  /// Not loaded; we can't say anything about it.
+  Unknown,
+  Open,
+  Solid,
+}
+
+fn cell(loaded: &Loaded, p: &Point3<i32>) -> Cell {
+  match loaded.get(&voxel::bounds::new(p.x, p.y, p.z, 0)) {
+    None => Cell::Unknown,
+    Some(ids) => if ids.is_empty() { Cell::Open } else { Cell::Solid },
+  }
+}
+
+/// Is there enough open space above `low` (inclusive) for an agent?
+fn is_clear(loaded: &Loaded, low: &Point3<i32>, height: i32) -> bool {
+  (0 .. height).all(|dy| cell(loaded, &Point3::new(low.x, low.y + dy, low.z)) == Cell::Open)
+}
+
+fn is_standable(loaded: &Loaded, p: &Point3<i32>) -> bool {
+  cell(loaded, &Point3::new(p.x, p.y - 1, p.z)) == Cell::Solid &&
+  is_clear(loaded, p, AGENT_HEIGHT)
+}
+
+/// The block containing the feet of an entity with the given bounds.
+pub fn feet_of(bounds: &world_position::Bounds) -> Point3<i32> {
+  Point3::new(
+    ((bounds.min.x + bounds.max.x) / 2.0).floor() as i32,
+    // Bounds resting on the ground sit exactly on a block boundary; nudge into the block above.
+    (bounds.min.y + 0.01).floor() as i32,
+    ((bounds.min.z + bounds.max.z) / 2.0).floor() as i32,
+  )
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+  to   : Point3<i32>,
+  cost : u32,
+}
+
+/// A route through the walkable graph.
+#[derive(Debug, Clone)]
+pub struct Path {
+  /// The block the path was requested to.
+  pub goal      : Point3<i32>,
+  /// The blocks left to walk through, in order. The last one is the closest reachable block to `goal`.
+  pub waypoints : VecDeque<Point3<i32>>,
+}
+
+/// The cached walkable graph.
+pub struct T {
+  /// Outgoing edges of every block we've examined; `None` means it can't be stood in.
+  nodes: fnv_map::T<Point3<i32>, Option<Vec<Edge>>>,
+}
+
+#[allow(missing_docs)]
+pub fn new() -> T {
+  T {
+    nodes: fnv_map::new(),
+  }
+}
+
+#[derive(PartialEq, Eq)]
+struct Open {
+  estimate : u32,
+  cost     : u32,
+  position : Point3<i32>,
+}
+
+impl PartialOrd for Open {
+  fn partial_cmp(&self, other: &Open) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Open {
+  fn cmp(&self, other: &Open) -> Ordering {
+    // Reversed, so the BinaryHeap pops the cheapest estimate first.
+    match other.estimate.cmp(&self.estimate) {
+      Ordering::Equal => self.cost.cmp(&other.cost),
+      ordering => ordering,
+    }
+  }
+}
+
+fn heuristic(p: &Point3<i32>, goal: &Point3<i32>) -> u32 {
+  let dx = (p.x - goal.x).abs() as u32;
+  let dy = (p.y - goal.y).abs() as u32;
+  let dz = (p.z - goal.z).abs() as u32;
+  WALK_COST * (dx + dz) + DROP_COST * dy
+}
+
+impl T {
+  fn edges(&mut self, loaded: &Loaded, p: &Point3<i32>) -> Option<&Vec<Edge>> {
+    self.nodes
+      .entry(*p)
+      .or_insert_with(|| compute_edges(loaded, p))
+      .as_ref()
+  }
+
+  /// Can an agent stand at `p`?
+  pub fn is_standable(&mut self, loaded: &Loaded, p: &Point3<i32>) -> bool {
+    self.edges(loaded, p).is_some()
+  }
+
+  /// Forget everything that depends on the block at `position`, because it's been loaded, unloaded or changed.
+  pub fn invalidate(&mut self, position: &voxel::bounds::T) {
+    if self.nodes.is_empty() {
+      return
+    }
+
+    let (low, high) = position.corners();
+    let low = Point3::new(low.x.floor() as i32, low.y.floor() as i32, low.z.floor() as i32);
+    let high = Point3::new(high.x.ceil() as i32, high.y.ceil() as i32, high.z.ceil() as i32);
+    // A block affects the nodes standing on it, the nodes whose headroom it's in,
+    // and the edges from neighbouring columns that jump or drop past it.
+    let reach = AGENT_HEIGHT + MAX_JUMP_HEIGHT + MAX_DROP_HEIGHT;
+    for x in low.x - 1 .. high.x + 1 {
+    for y in low.y - reach .. high.y + reach + 1 {
+    for z in low.z - 1 .. high.z + 1 {
+      self.nodes.remove(&Point3::new(x, y, z));
+    }}}
+  }
+
+  /// Find a path from `from` towards `to`. If `to` can't be reached, the path leads to the closest block found.
+  /// Returns `None` if there's nowhere to stand near `from`.
+  pub fn find_path(
+    &mut self,
+    loaded : &Loaded,
+    from   : &Point3<i32>,
+    to     : &Point3<i32>,
+  ) -> Option<Path> {
+    // We might be mid-jump or mid-fall; path from wherever we'll land.
+    let start =
+      match
+        (0 .. MAX_DROP_HEIGHT + 2)
+        .map(|dy| Point3::new(from.x, from.y - dy, from.z))
+        .find(|p| self.edges(loaded, p).is_some())
+      {
+        None => return None,
+        Some(start) => start,
+      };
+
+    let mut came_from: fnv_map::T<Point3<i32>, Point3<i32>> = fnv_map::new();
+    let mut costs: fnv_map::T<Point3<i32>, u32> = fnv_map::new();
+    let mut open = BinaryHeap::new();
+
+    costs.insert(start, 0);
+    open.push(Open { estimate: heuristic(&start, to), cost: 0, position: start });
+
+    let mut closest = (heuristic(&start, to), start);
+    let mut expanded = 0;
+    while let Some(Open { cost, position, .. }) = open.pop() {
+      if costs.get(&position).map(|&c| c < cost) == Some(true) {
+        // Stale entry; we've already found a cheaper way here.
+        continue
+      }
+
+      let h = heuristic(&position, to);
+      if h < closest.0 {
+        closest = (h, position);
+      }
+      if position == *to {
+        break
+      }
+
+      expanded += 1;
+      if expanded >= MAX_SEARCH_NODES {
+        debug!("Pathfinding from {:?} to {:?} gave up", start, to);
+        break
+      }
+
+      let edges = self.edges(loaded, &position).unwrap().clone();
+      for edge in edges {
+        if self.edges(loaded, &edge.to).is_none() {
+          continue
+        }
+        let cost = cost + edge.cost;
+        if costs.get(&edge.to).map(|&c| c <= cost) == Some(true) {
+          continue
+        }
+        costs.insert(edge.to, cost);
+        came_from.insert(edge.to, position);
+        open.push(Open { estimate: cost + heuristic(&edge.to, to), cost: cost, position: edge.to });
+      }
+    }
+
+    let mut waypoints = VecDeque::new();
+    let mut position = closest.1;
+    while position != start {
+      waypoints.push_front(position);
+      position = came_from[&position];
+    }
+
+    Some(Path {
+      goal      : *to,
+      waypoints : waypoints,
+    })
+  }
+}
+
+fn compute_edges(loaded: &Loaded, p: &Point3<i32>) -> Option<Vec<Edge>> {
+  if !is_standable(loaded, p) {
+    return None
+  }
+
+  let mut edges = Vec::new();
+  for &(dx, dz) in &[(1, 0), (-1, 0), (0, 1), (0, -1)] {
+    // Jumping up requires headroom above where we start.
+    for dy in 1 .. MAX_JUMP_HEIGHT + 1 {
+      let to = Point3::new(p.x + dx, p.y + dy, p.z + dz);
+      if !is_clear(loaded, &Point3::new(p.x, p.y + AGENT_HEIGHT, p.z), dy) {
+        break
+      }
+      if is_standable(loaded, &to) {
+        edges.push(Edge { to: to, cost: WALK_COST + JUMP_COST * dy as u32 });
+      }
+    }
+
+    let to = Point3::new(p.x + dx, p.y, p.z + dz);
+    if is_standable(loaded, &to) {
+      edges.push(Edge { to: to, cost: WALK_COST });
+    }
+
+    // Dropping down requires the neighbouring column to be open all the way down.
+    for dy in 1 .. MAX_DROP_HEIGHT + 1 {
+      let to = Point3::new(p.x + dx, p.y - dy, p.z + dz);
+      if cell(loaded, &Point3::new(to.x, to.y + AGENT_HEIGHT, to.z)) != Cell::Open {
+        break
+      }
+      if is_standable(loaded, &to) {
+        edges.push(Edge { to: to, cost: WALK_COST + DROP_COST * dy as u32 });
+        break
+      }
+    }
+  }
+
+  Some(edges)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use cgmath::{Point3};
+  use collision::{Aabb3};
+
+  use common::id_allocator;
+  use common::lock::{Mutex};
+
+  use entity;
+  use lock_order;
+  use physics;
+  use terrain_loader;
+
+  const WIDTH: i32 = 5;
+
+  fn set(loaded: &mut Loaded, ids: &mut id_allocator::T<entity::id::Terrain>, p: Point3<i32>, solid: bool) {
+    let block = if solid { vec!(ids.allocate()) } else { vec!() };
+    loaded.insert(voxel::bounds::new(p.x, p.y, p.z, 0), block);
+  }
+
+  /// A flat, loaded WIDTH x WIDTH floor at y = 0, with room to stand and jump above it.
+  fn floor(ids: &mut id_allocator::T<entity::id::Terrain>) -> Loaded {
+    let mut loaded = fnv_map::new();
+    for x in 0 .. WIDTH {
+    for z in 0 .. WIDTH {
+      set(&mut loaded, ids, Point3::new(x, 0, z), true);
+      for y in 1 .. AGENT_HEIGHT + MAX_JUMP_HEIGHT + 1 {
+        set(&mut loaded, ids, Point3::new(x, y, z), false);
+      }
+    }}
+    loaded
+  }
+
+  /// Block x = 2 with a wall too tall to jump, for every z in `zs`.
+  fn wall(loaded: &mut Loaded, ids: &mut id_allocator::T<entity::id::Terrain>, zs: ::std::ops::Range<i32>) {
+    for z in zs {
+      for y in 1 .. AGENT_HEIGHT + MAX_JUMP_HEIGHT + 1 {
+        set(loaded, ids, Point3::new(2, y, z), true);
+      }
+    }
+  }
+
+  #[test]
+  fn path_around_obstacle() {
+    let mut ids = id_allocator::new();
+    let mut loaded = floor(&mut ids);
+    wall(&mut loaded, &mut ids, 0 .. WIDTH - 1);
+
+    let from = Point3::new(0, 1, 0);
+    let to = Point3::new(4, 1, 0);
+    let path = new().find_path(&loaded, &from, &to).unwrap();
+    assert_eq!(path.goal, to);
+    assert_eq!(path.waypoints.back(), Some(&to));
+    // The only way past the wall is through the gap at the far edge.
+    assert!(path.waypoints.contains(&Point3::new(2, 1, WIDTH - 1)));
+    for w in &path.waypoints {
+      assert!(w.x != 2 || w.z == WIDTH - 1, "walked through the wall at {:?}", w);
+    }
+  }
+
+  #[test]
+  fn no_path() {
+    let mut ids = id_allocator::new();
+    let mut loaded = floor(&mut ids);
+    wall(&mut loaded, &mut ids, 0 .. WIDTH);
+
+    let mut navigation = new();
+    let from = Point3::new(0, 1, 0);
+    let to = Point3::new(4, 1, 0);
+    let path = navigation.find_path(&loaded, &from, &to).unwrap();
+    // We get as close as we can on our side of the wall.
+    assert_eq!(path.waypoints.back(), Some(&Point3::new(1, 1, 0)));
+    assert!(path.waypoints.iter().all(|w| w.x < 2));
+
+    // Nowhere to stand at all.
+    assert!(navigation.find_path(&loaded, &Point3::new(-10, 1, 0), &to).is_none());
+  }
+
+  #[test]
+  fn invalidate_after_update_block() {
+    let mut ids = id_allocator::new();
+    let loader = terrain_loader::T::new(0);
+    *loader.loaded.write().unwrap() = floor(&mut ids);
+    let physics = Mutex::new(lock_order::PHYSICS, physics::T::new());
+
+    let from = Point3::new(0, 1, 0);
+    let to = Point3::new(4, 1, 0);
+    let find_path = || {
+      let loaded = loader.loaded.read().unwrap();
+      loader.navigation.lock().unwrap().find_path(&loaded, &from, &to).unwrap()
+    };
+
+    assert_eq!(find_path().waypoints.back(), Some(&to));
+
+    // Fill in a wall across the whole floor, as a brush would.
+    for z in 0 .. WIDTH {
+      for y in 1 .. AGENT_HEIGHT + MAX_JUMP_HEIGHT + 1 {
+        let block =
+          terrain_loader::LoadedTerrain {
+            bounds: vec!((ids.allocate(), Aabb3::new(
+              Point3::new(2.0, y as f64, z as f64),
+              Point3::new(3.0, y as f64 + 1.0, z as f64 + 1.0),
+            ))),
+          };
+        loader.update_block(|| block, &voxel::bounds::new(2, y, z, 0), &physics);
+      }
+    }
+
+    let path = find_path();
+    assert_eq!(path.waypoints.back(), Some(&Point3::new(1, 1, 0)));
+    assert!(path.waypoints.iter().all(|w| w.x < 2));
+  }
+}
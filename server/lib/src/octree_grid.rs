@@ -0,0 +1,280 @@
+//! A sparse grid of octrees, so objects can go anywhere instead of only inside one octree's fixed bounds.
+//! Cells are only created when something's put in them, and dropped again when they're emptied.
+//! Objects spanning several cells are split along cell boundaries, the same way an octree splits them between its
+//! children, so collisions report the part of an object inside the cell it was found in.
+//! Each cell's octree works in coordinates relative to the cell's low corner, so collisions are just as precise
+//! far from the origin as they are near it.
+
+use cgmath::{Point3};
+use collision::{Aabb3};
+use std::fmt::Debug;
+
+use common::fnv_map;
+use common::world_position;
+
+use octree::Octree;
+
+/// Each cell is this wide in every dimension, as a power of two.
+const LG_CELL_WIDTH: i32 = 8;
+
+/// A cell's position, in units of cells.
+type CellId = Point3<i32>;
+
+pub struct T<V> {
+  /// Boxed, since octrees keep pointers to their parents, so the roots can't move.
+  cells: fnv_map::T<CellId, Box<Octree<V>>>,
+}
+
+pub fn new<V>() -> T<V> {
+  T {
+    cells: fnv_map::new(),
+  }
+}
+
+fn cell_width() -> f64 {
+  (1 << LG_CELL_WIDTH) as f64
+}
+
+/// The low corner of a cell, in world coordinates.
+fn cell_origin(cell: &CellId) -> world_position::T {
+  let w = cell_width();
+  Point3::new(cell.x as f64 * w, cell.y as f64 * w, cell.z as f64 * w)
+}
+
+/// The bounds of every cell, relative to its low corner.
+fn local_cell_bounds() -> Aabb3<f32> {
+  let w = cell_width() as f32;
+  Aabb3::new(Point3::new(0.0, 0.0, 0.0), Point3::new(w, w, w))
+}
+
+/// The range of cells overlapping [min, max) in one dimension.
+fn cell_range(min: f64, max: f64) -> (i32, i32) {
+  let w = cell_width();
+  let low = (min / w).floor() as i32;
+  let high = (max / w).ceil() as i32 - 1;
+  (low, if high < low { low } else { high })
+}
+
+/// Split `bounds` into the parts inside each cell it overlaps, relative to those cells.
+fn pieces(bounds: &world_position::Bounds) -> Vec<(CellId, Aabb3<f32>)> {
+  let (low_x, high_x) = cell_range(bounds.min.x, bounds.max.x);
+  let (low_y, high_y) = cell_range(bounds.min.y, bounds.max.y);
+  let (low_z, high_z) = cell_range(bounds.min.z, bounds.max.z);
+  let w = cell_width();
+  let mut pieces = Vec::new();
+  for x in low_x .. high_x + 1 {
+  for y in low_y .. high_y + 1 {
+  for z in low_z .. high_z + 1 {
+    let cell = Point3::new(x, y, z);
+    let origin = cell_origin(&cell);
+    let clip = |min: f64, max: f64, low: f64| -> (f32, f32) {
+      ((min - low).max(0.0) as f32, (max - low).min(w) as f32)
+    };
+    let (min_x, max_x) = clip(bounds.min.x, bounds.max.x, origin.x);
+    let (min_y, max_y) = clip(bounds.min.y, bounds.max.y, origin.y);
+    let (min_z, max_z) = clip(bounds.min.z, bounds.max.z, origin.z);
+    pieces.push((cell, Aabb3::new(Point3::new(min_x, min_y, min_z), Point3::new(max_x, max_y, max_z))));
+  }}}
+  pieces
+}
+
+/// Put a piece of a cell back in world coordinates.
+fn to_world(cell: &CellId, piece: &Aabb3<f32>) -> world_position::Bounds {
+  let origin = cell_origin(cell);
+  let shift = |p: &Point3<f32>| Point3::new(origin.x + p.x as f64, origin.y + p.y as f64, origin.z + p.z as f64);
+  Aabb3::new(shift(&piece.min), shift(&piece.max))
+}
+
+impl<V: Debug + Copy + Eq + PartialOrd> T<V> {
+  pub fn insert(&mut self, bounds: &world_position::Bounds, v: V) {
+    for (cell, piece) in pieces(bounds) {
+      self.cells
+        .entry(cell)
+        .or_insert_with(|| Box::new(Octree::new(&local_cell_bounds())))
+        .insert(&piece, v);
+    }
+  }
+
+  pub fn remove(&mut self, bounds: &world_position::Bounds, v: V) {
+    for (cell, piece) in pieces(bounds) {
+      let is_empty =
+        match self.cells.get_mut(&cell) {
+          None => panic!("{:?} was not found in the octree grid", v),
+          Some(octree) => {
+            octree.remove(&piece, v);
+            octree.is_empty()
+          },
+        };
+      if is_empty {
+        self.cells.remove(&cell);
+      }
+    }
+  }
+
+  /// Like `Octree::intersect`, across every cell `bounds` overlaps.
+  pub fn intersect(&self, bounds: &world_position::Bounds, self_v: Option<V>) -> Option<(world_position::Bounds, V)> {
+    pieces(bounds).iter()
+      .filter_map(|&(ref cell, ref piece)| {
+        self.cells.get(cell)
+          .and_then(|octree| octree.intersect(piece, self_v))
+          .map(|(hit, v)| (to_world(cell, &hit), v))
+      })
+      .next()
+  }
+
+  pub fn reinsert(&mut self, v: V, bounds: &world_position::Bounds, new_bounds: &world_position::Bounds) {
+    let old_pieces = pieces(bounds);
+    let new_pieces = pieces(new_bounds);
+    if old_pieces.len() == 1 && new_pieces.len() == 1 && old_pieces[0].0 == new_pieces[0].0 {
+      // The usual case, something moving around inside one cell, can be left to that cell's octree.
+      let (cell, old_piece) = old_pieces[0];
+      self.cells.get_mut(&cell).unwrap().reinsert(v, &old_piece, &new_pieces[0].1);
+      return
+    }
+    self.remove(bounds, v);
+    self.insert(new_bounds, v);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use cgmath::{Point3};
+  use collision::{Aabb3};
+
+  use common::world_position;
+
+  use super::*;
+
+  fn bounds(min: [f64; 3], max: [f64; 3]) -> world_position::Bounds {
+    Aabb3::new(Point3::new(min[0], min[1], min[2]), Point3::new(max[0], max[1], max[2]))
+  }
+
+  fn local(min: [f32; 3], max: [f32; 3]) -> Aabb3<f32> {
+    Aabb3::new(Point3::new(min[0], min[1], min[2]), Point3::new(max[0], max[1], max[2]))
+  }
+
+  fn cells<V>(grid: &T<V>) -> Vec<CellId> {
+    let mut cells: Vec<CellId> = grid.cells.keys().cloned().collect();
+    cells.sort_by(|a, b| (a.x, a.y, a.z).cmp(&(b.x, b.y, b.z)));
+    cells
+  }
+
+  #[test]
+  fn cell_ranges() {
+    let w = cell_width();
+    assert_eq!(cell_range(0.0, 1.0), (0, 0));
+    assert_eq!(cell_range(-1.0, 1.0), (-1, 0));
+    assert_eq!(cell_range(-w - 0.5, -w + 0.5), (-2, -1));
+    // Bounds ending exactly on a cell edge don't reach into the next cell.
+    assert_eq!(cell_range(0.0, w), (0, 0));
+    assert_eq!(cell_range(-1.0, 0.0), (-1, -1));
+    assert_eq!(cell_range(-w, 0.0), (-1, -1));
+    // Several cells.
+    assert_eq!(cell_range(w - 1.0, 2.0 * w + 1.0), (0, 2));
+    // Flat bounds are still in a cell.
+    assert_eq!(cell_range(w, w), (1, 1));
+  }
+
+  #[test]
+  fn pieces_in_one_cell() {
+    let w = cell_width() as f32;
+    assert_eq!(
+      pieces(&bounds([-10.0, -10.0, -10.0], [-9.0, -9.0, -9.0])),
+      vec!((Point3::new(-1, -1, -1), local([w - 10.0; 3], [w - 9.0; 3]))),
+    );
+    assert_eq!(
+      pieces(&bounds([w as f64 - 6.0, 0.0, 0.0], [w as f64, 1.0, 1.0])),
+      vec!((Point3::new(0, 0, 0), local([w - 6.0, 0.0, 0.0], [w, 1.0, 1.0]))),
+    );
+  }
+
+  #[test]
+  fn pieces_across_cells() {
+    let w = cell_width() as f32;
+    let whole = bounds([-2.0, 0.0, 0.0], [2.0, 1.0, 1.0]);
+    let pieces = pieces(&whole);
+    assert_eq!(
+      pieces,
+      vec!(
+        (Point3::new(-1, 0, 0), local([w - 2.0, 0.0, 0.0], [w, 1.0, 1.0])),
+        (Point3::new(0, 0, 0), local([0.0, 0.0, 0.0], [2.0, 1.0, 1.0])),
+      ),
+    );
+    assert_eq!(to_world(&pieces[0].0, &pieces[0].1), bounds([-2.0, 0.0, 0.0], [0.0, 1.0, 1.0]));
+    assert_eq!(to_world(&pieces[1].0, &pieces[1].1), bounds([0.0, 0.0, 0.0], [2.0, 1.0, 1.0]));
+
+    // Every corner of the origin.
+    assert_eq!(super::pieces(&bounds([-1.0; 3], [1.0; 3])).len(), 8);
+  }
+
+  #[test]
+  fn insert_and_remove_across_cells() {
+    let mut grid = new();
+    let object = bounds([-2.0, 0.0, 0.0], [2.0, 1.0, 1.0]);
+    grid.insert(&object, 1_u32);
+    assert_eq!(cells(&grid), vec!(Point3::new(-1, 0, 0), Point3::new(0, 0, 0)));
+
+    // Each side is found as the part in its own cell.
+    assert_eq!(
+      grid.intersect(&bounds([-1.5, 0.0, 0.0], [-1.0, 1.0, 1.0]), None),
+      Some((bounds([-2.0, 0.0, 0.0], [0.0, 1.0, 1.0]), 1)),
+    );
+    assert_eq!(
+      grid.intersect(&bounds([1.0, 0.0, 0.0], [1.5, 1.0, 1.0]), None),
+      Some((bounds([0.0, 0.0, 0.0], [2.0, 1.0, 1.0]), 1)),
+    );
+    assert_eq!(grid.intersect(&bounds([2.0, 0.0, 0.0], [3.0, 1.0, 1.0]), None), None);
+    assert_eq!(grid.intersect(&bounds([-1.0, 0.0, 0.0], [1.0, 1.0, 1.0]), Some(1)), None);
+
+    grid.remove(&object, 1);
+    assert!(cells(&grid).is_empty());
+  }
+
+  #[test]
+  fn emptied_cells_are_dropped() {
+    let mut grid = new();
+    grid.insert(&bounds([1.0; 3], [2.0; 3]), 1_u32);
+    grid.insert(&bounds([3.0; 3], [4.0; 3]), 2);
+    grid.remove(&bounds([1.0; 3], [2.0; 3]), 1);
+    assert_eq!(cells(&grid), vec!(Point3::new(0, 0, 0)));
+    grid.remove(&bounds([3.0; 3], [4.0; 3]), 2);
+    assert!(cells(&grid).is_empty());
+  }
+
+  #[test]
+  fn reinsert_within_cell() {
+    let mut grid = new();
+    let old = bounds([1.0; 3], [2.0; 3]);
+    let moved = bounds([5.0; 3], [6.0; 3]);
+    grid.insert(&old, 1_u32);
+    grid.reinsert(1, &old, &moved);
+    assert_eq!(cells(&grid), vec!(Point3::new(0, 0, 0)));
+    assert_eq!(grid.intersect(&old, None), None);
+    assert_eq!(grid.intersect(&moved, None), Some((moved, 1)));
+  }
+
+  #[test]
+  fn reinsert_across_cells() {
+    let mut grid = new();
+    let old = bounds([1.0; 3], [2.0; 3]);
+    let straddling = bounds([-0.5, 1.0, 1.0], [0.5, 2.0, 2.0]);
+    let moved = bounds([-2.0, 1.0, 1.0], [-1.0, 2.0, 2.0]);
+    grid.insert(&old, 1_u32);
+
+    grid.reinsert(1, &old, &straddling);
+    assert_eq!(cells(&grid), vec!(Point3::new(-1, 0, 0), Point3::new(0, 0, 0)));
+    assert_eq!(grid.intersect(&old, None), None);
+
+    grid.reinsert(1, &straddling, &moved);
+    assert_eq!(cells(&grid), vec!(Point3::new(-1, 0, 0)));
+    assert_eq!(grid.intersect(&moved, None), Some((moved, 1)));
+  }
+
+  #[test]
+  #[should_panic]
+  fn remove_missing() {
+    let mut grid = new();
+    grid.insert(&bounds([1.0; 3], [2.0; 3]), 1_u32);
+    grid.remove(&bounds([-2.0; 3], [-1.0; 3]), 1);
+  }
+}
@@ -2,15 +2,17 @@ use cgmath::{Vector3};
 use collision::{Aabb3};
 
 use common::fnv_map;
+use common::world_position;
 
 use entity;
-use octree::Octree;
+use octree_grid;
 
+/// Collision detection for everything in the world. There are no bounds on where things can be.
 pub struct T {
-  pub terrain_octree : Octree<entity::id::Terrain>,
-  terrain_bounds : fnv_map::T<entity::id::Terrain, Aabb3<f32>>,
-  pub misc_octree    : Octree<entity::id::Misc>,
-  misc_bounds    : fnv_map::T<entity::id::Misc, Aabb3<f32>>,
+  terrain_octree : octree_grid::T<entity::id::Terrain>,
+  terrain_bounds : fnv_map::T<entity::id::Terrain, world_position::Bounds>,
+  misc_octree    : octree_grid::T<entity::id::Misc>,
+  misc_bounds    : fnv_map::T<entity::id::Misc, world_position::Bounds>,
 }
 
 pub enum Collision {
@@ -19,48 +21,58 @@ pub enum Collision {
 }
 
 impl T {
-  pub fn new(world_bounds: Aabb3<f32>) -> T {
+  pub fn new() -> T {
     T {
-      terrain_octree : Octree::new(&world_bounds),
+      terrain_octree : octree_grid::new(),
       terrain_bounds : fnv_map::new(),
-      misc_octree    : Octree::new(&world_bounds),
+      misc_octree    : octree_grid::new(),
       misc_bounds    : fnv_map::new(),
     }
   }
 
-  pub fn insert_terrain(&mut self, id: entity::id::Terrain, bounds: &Aabb3<f32>) {
+  pub fn insert_terrain(&mut self, id: entity::id::Terrain, bounds: &world_position::Bounds) {
     self.terrain_octree.insert(bounds, id);
     self.terrain_bounds.insert(id, *bounds);
   }
 
-  pub fn insert_misc(&mut self, id: entity::id::Misc, bounds: &Aabb3<f32>) {
+  pub fn insert_misc(&mut self, id: entity::id::Misc, bounds: &world_position::Bounds) {
     self.misc_octree.insert(bounds, id);
     self.misc_bounds.insert(id, *bounds);
   }
 
   pub fn remove_terrain(&mut self, id: entity::id::Terrain) {
-    match self.terrain_bounds.get(&id) {
+    match self.terrain_bounds.remove(&id) {
       None => {},
       Some(bounds) => {
-        self.terrain_octree.remove(bounds, id);
+        self.terrain_octree.remove(&bounds, id);
       },
     }
   }
 
   pub fn remove_misc(&mut self, id: entity::id::Misc) {
-    match self.misc_bounds.get(&id) {
+    match self.misc_bounds.remove(&id) {
       None => {},
       Some(bounds) => {
-        self.misc_octree.remove(bounds, id);
+        self.misc_octree.remove(&bounds, id);
       },
     }
   }
 
-  pub fn get_bounds(&self, id: entity::id::Misc) -> Option<&Aabb3<f32>> {
+  /// The number of terrain and misc objects being tracked.
+  pub fn counts(&self) -> (usize, usize) {
+    (self.terrain_bounds.len(), self.misc_bounds.len())
+  }
+
+  pub fn get_bounds(&self, id: entity::id::Misc) -> Option<&world_position::Bounds> {
     self.misc_bounds.get(&id)
   }
 
-  pub fn translate_misc(&mut self, id: entity::id::Misc, amount: Vector3<f32>) -> Option<(Aabb3<f32>, Collision)> {
+  pub fn translate_misc(
+    &mut self,
+    id: entity::id::Misc,
+    amount: Vector3<f32>,
+  ) -> Option<(world_position::Bounds, Collision)> {
+    let amount = world_position::vector_of_f32(&amount);
     let bounds = self.misc_bounds.get_mut(&id).unwrap();
     let new_bounds =
       Aabb3::new(
@@ -1,24 +1,101 @@
 use cgmath;
-use cgmath::{Point3, Matrix3, Vector3, ElementWise};
+use cgmath::{Point3, Matrix3, Vector2, Vector3, ElementWise, EuclideanSpace};
 use collision::{Aabb3, Ray3};
+use std;
 use std::f32::consts::PI;
+use std::io;
 use std::ops::DerefMut;
-use std::sync::Mutex;
-use stopwatch;
+use std::path::{Path, PathBuf};
 
 use common::id_allocator;
+use common::lock::Mutex;
+use common::protocol;
 use common::surroundings_loader;
+use common::trace;
 use common::voxel;
+use common::world_position;
 
+use clipboard;
+use combat;
 use entity;
+use history;
+use inventory;
 use lod;
 use physics;
+use save_file;
 use server;
+use spawn;
 use update_gaia;
 use update_world::load_placeholders;
 
 const MAX_JUMP_FUEL: u32 = 4;
 const MAX_STEP_HEIGHT: f32 = 1.0;
+/// Players are saved in this subdirectory of the world.
+const DIRECTORY: &'static str = "players";
+const EXTENSION: &'static str = "player";
+
+/// The dimensions of a player's body.
+pub const SIZE: Vector3<f32> = Vector3 { x: 1.0, y: 2.0, z: 1.0 };
+
+/// The bounds of a player entering the world. This can generate terrain, so it isn't for world updates.
+pub fn spawn_bounds(server: &server::T) -> world_position::Bounds {
+  spawn::choose(server, &SIZE)
+}
+
+/// The bounds of a player respawning after they die, from a spawn point gaia prepared ahead of time.
+/// If there isn't one yet, they're dropped in from above one of the world's spawn points.
+pub fn respawn_bounds(server: &server::T) -> world_position::Bounds {
+  let prepared = server.respawn.lock().unwrap().get();
+  match prepared {
+    Some(bounds) => bounds,
+    None => spawn::above(server, &SIZE),
+  }
+}
+
+/// The bounds of a player centered at `center`.
+pub fn bounds_around(center: &world_position::T) -> world_position::Bounds {
+  let half_size = world_position::vector_of_f32(&(SIZE * 0.5));
+  Aabb3::new(*center + (&-half_size), *center + (&half_size))
+}
+
+fn center(bounds: &world_position::Bounds) -> world_position::T {
+  (bounds.min + bounds.max.to_vec()) * 0.5
+}
+
+/// Find somewhere for a player joining for the first time, and add them there.
+/// This can generate terrain, so it's up to gaia.
+pub fn spawn(server: &server::T, mut player: T) {
+  let bounds = spawn_bounds(server);
+  player.position = center(&bounds);
+  player.rotate_lateral(PI / 2.0);
+  add(server, player, &bounds);
+}
+
+/// Put a player's body in the world at `bounds`, and tell their client they're in.
+/// If someone else has joined under the same name in the meantime, the client is turned away instead.
+pub fn add(server: &server::T, player: T, bounds: &world_position::Bounds) {
+  let client_id = player.client_id;
+  let added =
+    protocol::ServerToClient::PlayerAdded(
+      player.entity_id,
+      player.position,
+      Vector2::new(player.lateral_rotation, player.vertical_rotation),
+    );
+
+  {
+    let mut players = server.players.lock().unwrap();
+    if players.values().any(|other| other.name == player.name) {
+      let reason = format!("Someone is already playing as {:?}", player.name);
+      drop(players);
+      server::kick(server, client_id, reason);
+      return
+    }
+    server.physics.lock().unwrap().insert_misc(player.physics_id, bounds);
+    players.insert(player.entity_id, player);
+  }
+
+  server.clients.lock().unwrap().get_mut(&client_id).map(|client| client.send(added));
+}
 
 #[derive(Debug, Clone)]
 pub enum Collision {
@@ -26,8 +103,21 @@ pub enum Collision {
   Misc(entity::id::Misc),
 }
 
+/// What's remembered about a player between visits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Saved {
+  pub position          : world_position::T,
+  pub lateral_rotation  : f32,
+  pub vertical_rotation : f32,
+  pub jump_fuel         : u32,
+  pub health            : u32,
+  pub inventory         : Vec<(voxel::Material, u32)>,
+}
+
 pub struct T {
-  pub position: Point3<f32>,
+  // what the player calls themselves when they join; their saved state is kept under this.
+  pub name: String,
+  pub position: world_position::T,
   // speed; units are world coordinates
   pub speed: Vector3<f32>,
   // acceleration; units are world coordinates
@@ -40,6 +130,18 @@ pub struct T {
   pub is_jumping: bool,
   pub entity_id: entity::id::Player,
   pub physics_id: entity::id::Misc,
+  // the client controlling this player.
+  pub client_id: protocol::ClientId,
+
+  pub inventory: inventory::T,
+  // recent terrain edits, for undo and redo.
+  pub history: history::T,
+  // opposite corners of the region to copy.
+  pub selection: [Option<Point3<i32>>; 2],
+  pub clipboard: Option<clipboard::T>,
+  pub health: combat::Health,
+  // world updates until we can attack again.
+  pub attack_cooldown: u32,
 
   // rotation around the y-axis, in radians
   pub lateral_rotation: f32,
@@ -54,13 +156,17 @@ pub struct T {
 }
 
 pub fn new(
+  name: String,
   entity_id: entity::id::Player,
   physics_id: entity::id::Misc,
+  client_id: protocol::ClientId,
   owner_allocator: &Mutex<id_allocator::T<lod::OwnerId>>,
+  load_distance: u32,
 ) -> T {
   let surroundings_owner = owner_allocator.lock().unwrap().allocate();
   let solid_owner = owner_allocator.lock().unwrap().allocate();
   T {
+    name                : name,
     position            : Point3::new(0.0, 0.0, 0.0),
     speed               : Vector3::new(0.0, 0.0, 0.0),
     accel               : Vector3::new(0.0, -0.1, 0.0),
@@ -69,11 +175,18 @@ pub fn new(
     is_jumping          : false,
     entity_id           : entity_id,
     physics_id          : physics_id,
+    client_id           : client_id,
+    inventory           : inventory::new(),
+    history             : history::new(),
+    selection           : [None, None],
+    clipboard           : None,
+    health              : combat::Health::new(combat::PLAYER_HEALTH),
+    attack_cooldown     : 0,
     lateral_rotation    : 0.0,
     vertical_rotation   : 0.0,
 
-    surroundings_loader : surroundings_loader::new(8, Vec::new()),
-    solid_boundary      : surroundings_loader::new(8, Vec::new()),
+    surroundings_loader : surroundings_loader::new(load_distance, Vec::new()),
+    solid_boundary      : surroundings_loader::new(load_distance, Vec::new()),
     surroundings_owner  : surroundings_owner,
     solid_owner         : solid_owner,
   }
@@ -87,15 +200,15 @@ impl T {
     &mut self,
     physics: &Mutex<physics::T>,
     requested_shift: Vector3<f32>,
-  ) -> (Aabb3<f32>, Vec<Collision>)
+  ) -> (world_position::Bounds, Vec<Collision>)
   {
     let mut physics = physics.lock().unwrap();
     let physics = physics.deref_mut();
     let init_bounds = *physics.get_bounds(self.physics_id).unwrap();
     let requested_bounds =
       Aabb3::new(
-        init_bounds.min + requested_shift,
-        init_bounds.max + requested_shift,
+        init_bounds.min + world_position::vector_of_f32(&requested_shift),
+        init_bounds.max + world_position::vector_of_f32(&requested_shift),
       );
 
     let mut shift = requested_shift;
@@ -116,7 +229,7 @@ impl T {
           collided = true;
 
           // Step to the top of whatever we hit.
-          let step_height = collision_bounds.max.y - requested_bounds.min.y;
+          let step_height = (collision_bounds.max.y - requested_bounds.min.y) as f32;
           assert!(step_height > 0.0);
 
           if step_height > MAX_STEP_HEIGHT {
@@ -152,7 +265,7 @@ impl T {
     &mut self,
     server: &server::T,
     request_block: &mut RequestBlock,
-  ) -> (Aabb3<f32>, Vec<Collision>) where
+  ) -> (world_position::Bounds, Vec<Collision>) where
     RequestBlock: FnMut(update_gaia::Message),
   {
     let player_position =
@@ -162,7 +275,7 @@ impl T {
         self.position.z as i32,
       );
 
-    stopwatch::time("update.player.surroundings", || {
+    trace::time("update.player.surroundings", || {
       let owner = self.surroundings_owner;
       for (pos, load_type) in self.surroundings_loader.updates(&player_position) {
         let pos = voxel::bounds::new(pos.x, pos.y, pos.z, 0);
@@ -200,6 +313,10 @@ impl T {
       }
     });
 
+    if self.attack_cooldown > 0 {
+      self.attack_cooldown -= 1;
+    }
+
     if self.is_jumping {
       if self.jump_fuel > 0 {
         self.jump_fuel -= 1;
@@ -241,6 +358,51 @@ impl T {
     (new_bounds, collisions)
   }
 
+  /// Put the player back at a spawn point (e.g. from `respawn_bounds`), with full health.
+  pub fn respawn(&mut self, physics: &Mutex<physics::T>, bounds: &world_position::Bounds) {
+    self.place(physics, bounds);
+    self.health.restore();
+  }
+
+  /// Move the player's body so it's centered at `position`, ignoring anything in the way.
+  pub fn teleport(&mut self, physics: &Mutex<physics::T>, position: &world_position::T) {
+    self.place(physics, &bounds_around(position));
+  }
+
+  fn place(&mut self, physics: &Mutex<physics::T>, bounds: &world_position::Bounds) {
+    {
+      let mut physics = physics.lock().unwrap();
+      physics.remove_misc(self.physics_id);
+      physics.insert_misc(self.physics_id, bounds);
+    }
+
+    self.position = center(bounds);
+    self.speed = Vector3::new(0.0, 0.0, 0.0);
+  }
+
+  pub fn saved(&self) -> Saved {
+    Saved {
+      position          : self.position,
+      lateral_rotation  : self.lateral_rotation,
+      vertical_rotation : self.vertical_rotation,
+      jump_fuel         : self.jump_fuel,
+      health            : self.health.current,
+      inventory         : self.inventory.contents(),
+    }
+  }
+
+  /// Pick up where a saved player left off. This doesn't touch physics; the caller should
+  /// put the player's body at `bounds_around(&self.position)`.
+  pub fn restore(&mut self, saved: &Saved) {
+    self.position = saved.position;
+    self.lateral_rotation = saved.lateral_rotation;
+    self.vertical_rotation = saved.vertical_rotation;
+    self.jump_fuel = saved.jump_fuel;
+    // Don't bring anyone back dead.
+    self.health.current = if saved.health == 0 { self.health.max } else { saved.health };
+    self.inventory = inventory::of(&saved.inventory);
+  }
+
   /// Changes the player's acceleration by the given `da`.
   pub fn walk(&mut self, da: Vector3<f32>) {
     self.walk_accel += &da * 0.1;
@@ -284,7 +446,53 @@ impl T {
     transform * forward_orig
   }
 
+  /// The way the player's looking, e.g. to see what terrain they're pointing at.
+  /// Terrain is in `f32`s, so this starts from the nearest `f32` point to the player.
   pub fn forward_ray(&self) -> Ray3<f32> {
-    Ray3::new(self.position, self.forward())
+    Ray3::new(world_position::to_f32(&self.position), self.forward())
+  }
+}
+
+fn path_of(world: &Path, name: &str) -> PathBuf {
+  let mut path = world.join(DIRECTORY);
+  path.push(name);
+  path.set_extension(EXTENSION);
+  path
+}
+
+/// Load a player's saved state, if they've played here before.
+pub fn load(world: &Path, name: &str) -> Result<Option<Saved>, String> {
+  if !save_file::is_valid_name(name) {
+    return Err(format!("{:?} isn't a valid player name", name))
+  }
+  let path = path_of(world, name);
+  match save_file::read(&path, save_file::Kind::Player) {
+    Ok(saved) => Ok(Some(saved)),
+    Err(save_file::Error::Io(ref err)) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+    Err(err) => Err(format!("{:?} is {}", path, err)),
+  }
+}
+
+/// Save every player in the world.
+pub fn save_all(server: &server::T) {
+  let saved: Vec<(String, Saved)> =
+    server.players.lock().unwrap().values()
+    .map(|player| (player.name.clone(), player.saved()))
+    .collect();
+  if saved.is_empty() {
+    return
+  }
+
+  let world = server.regions.directory();
+  let directory = world.join(DIRECTORY);
+  if let Err(err) = std::fs::create_dir_all(&directory) {
+    error!("Couldn't create {:?}: {}", directory, err);
+    return
+  }
+  for (name, saved) in saved {
+    let path = path_of(world, &name);
+    if let Err(err) = save_file::write(&path, &saved) {
+      error!("Couldn't save player {:?} to {:?}: {}", name, path, err);
+    }
   }
 }
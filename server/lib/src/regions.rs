@@ -0,0 +1,576 @@
+//! Save terrain edits to disk in fixed-size cubic regions, and load them back as they're needed.
+//! Terrain that hasn't been edited isn't saved; it's regenerated from the world's seed.
+
+use bincode;
+use cgmath::{Point3};
+use collision::{Aabb3};
+use std;
+use std::io;
+use std::path::{Path, PathBuf};
+use voxel_data;
+
+use common::fnv_map;
+use common::fnv_set;
+use common::lock::Mutex;
+use common::voxel;
+
+use lock_order;
+use save_file;
+use terrain;
+
+/// Each region is this many unit voxels wide, as a power of two.
+pub const LG_SIZE: i16 = 5;
+const EXTENSION: &'static str = "region";
+const WORLD_INFO: &'static str = "world.info";
+
+/// Where servers from before region files kept the whole world, in the directory they were run from.
+pub const OLD_TERRAIN_FILE: &'static str = "default.terrain";
+/// The seed those servers always generated terrain from.
+const OLD_SEED: u32 = 0;
+
+/// A region's position, in units of regions.
+pub type Id = Point3<i32>;
+
+/// What it takes to regenerate the parts of a world that weren't saved.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WorldInfo {
+  pub seed              : u32,
+  pub generator_version : u32,
+}
+
+/// Tracks which regions are in memory, and which voxels have been edited.
+pub struct T {
+  directory  : PathBuf,
+  /// Held while writing region files, so a region being saved can't be evicted (and saved) at the same time.
+  writing    : Mutex<()>,
+  loaded     : Mutex<fnv_set::T<Id>>,
+  /// Every edited voxel in each loaded region; these are what get saved.
+  edited     : Mutex<fnv_map::T<Id, fnv_set::T<voxel::bounds::T>>>,
+  /// Regions with unsaved edits.
+  dirty      : Mutex<fnv_set::T<Id>>,
+  /// Regions whose files exist but couldn't be read. We never write over these.
+  unreadable : Mutex<fnv_set::T<Id>>,
+}
+
+pub fn new<P: AsRef<Path>>(directory: P) -> T {
+  T {
+    directory  : directory.as_ref().to_path_buf(),
+    writing    : Mutex::new(lock_order::REGIONS_WRITING, ()),
+    loaded     : Mutex::new(lock_order::REGIONS_LOADED, fnv_set::new()),
+    edited     : Mutex::new(lock_order::REGIONS_EDITED, fnv_map::new()),
+    dirty      : Mutex::new(lock_order::REGIONS_DIRTY, fnv_set::new()),
+    unreadable : Mutex::new(lock_order::REGIONS_UNREADABLE, fnv_set::new()),
+  }
+}
+
+/// Read the world info from `directory`, or start a new world there with `new_seed` if there isn't one.
+pub fn world_info<P: AsRef<Path>>(directory: P, new_seed: u32) -> Result<WorldInfo, String> {
+  let directory = directory.as_ref();
+  let path = directory.join(WORLD_INFO);
+  match save_file::read::<WorldInfo>(&path, save_file::Kind::WorldInfo) {
+    Ok(info) => {
+      if info.generator_version != terrain::GENERATOR_VERSION {
+        return Err(format!(
+          "{:?} was generated by terrain generator version {}, but this is version {}",
+          directory, info.generator_version, terrain::GENERATOR_VERSION,
+        ))
+      }
+      Ok(info)
+    },
+    Err(save_file::Error::Io(ref err)) if err.kind() == io::ErrorKind::NotFound => {
+      let info =
+        WorldInfo {
+          seed              : new_seed,
+          generator_version : terrain::GENERATOR_VERSION,
+        };
+      info!("Starting a new world in {:?} with seed {}", directory, info.seed);
+      try!(
+        std::fs::create_dir_all(directory)
+        .and_then(|()| save_file::write(&path, &info))
+        .map_err(|err| format!("Error writing {:?}: {}", path, err))
+      );
+      Ok(info)
+    },
+    Err(err) => Err(format!("{:?} is {}", path, err)),
+  }
+}
+
+/// Make sure every region file in `directory` can be loaded by this server,
+/// so incompatible worlds are caught before anything is generated over them.
+pub fn check_all<P: AsRef<Path>>(directory: P) -> Result<(), String> {
+  let entries =
+    match std::fs::read_dir(directory.as_ref()) {
+      Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+      Err(err) => return Err(format!("Error listing {:?}: {}", directory.as_ref(), err)),
+      Ok(entries) => entries,
+    };
+  let mut errors = Vec::new();
+  for entry in entries {
+    let path = try!(entry.map_err(|err| format!("{}", err))).path();
+    if path.extension().map_or(true, |extension| extension != EXTENSION) {
+      continue
+    }
+    if let Err(err) = save_file::check::<Vec<(voxel::bounds::T, voxel::T)>>(&path, save_file::Kind::Region) {
+      errors.push(format!("{:?} is {}", path, err));
+    }
+  }
+  if errors.is_empty() {
+    Ok(())
+  } else {
+    Err(errors.join("\n"))
+  }
+}
+
+fn path_in(directory: &Path, region: &Id) -> PathBuf {
+  let mut path = directory.to_path_buf();
+  path.push(format!("{}_{}_{}", region.x, region.y, region.z));
+  path.set_extension(EXTENSION);
+  path
+}
+
+/// Bring a world saved by a server from before region files into `directory`.
+/// Those servers saved their whole voxel tree to `old`, so every voxel in it is saved to a region file, as if it
+/// had been edited. The world starts with the seed those servers used, so the rest is generated as it was.
+/// `old` is renamed once it's been imported, so it's only imported once.
+pub fn import_old_terrain(old: &Path, directory: &Path) -> Result<(), String> {
+  if directory.join(WORLD_INFO).exists() {
+    return Err(format!(
+      "Both {:?} and the world in {:?} exist, so there's no telling which to use. Move {:?} somewhere else to \
+       carry on with {:?}, or move {:?} to import {:?} instead.",
+      old, directory, old, directory, directory, old,
+    ))
+  }
+
+  info!("Importing {:?} into {:?}", old, directory);
+  let tree: voxel::tree::T =
+    try!(
+      std::fs::File::open(old)
+      .map_err(|err| format!("Error opening {:?}: {}", old, err))
+      .and_then(|file| {
+        bincode::deserialize_from(&mut io::BufReader::new(file), bincode::Infinite)
+        .map_err(|err| format!("{:?} is corrupt: {:?}", old, err))
+      })
+    );
+
+  let mut regions: fnv_map::T<Id, Vec<(voxel::bounds::T, voxel::T)>> = fnv_map::new();
+  match try!(tree_lg_size(&tree).map_err(|err| format!("Couldn't make sense of {:?}: {}", old, err))) {
+    // Nothing was ever loaded.
+    None => {},
+    Some(lg_size) => {
+      for (i, node) in tree.contents.as_flat_array().iter().enumerate() {
+        let (x, y, z) = octant(i);
+        collect_voxels(node, &voxel::bounds::new(x - 1, y - 1, z - 1, lg_size), &mut regions);
+      }
+    },
+  }
+
+  try!(world_info(directory, OLD_SEED));
+  let mut count = 0;
+  for (region, voxels) in regions {
+    let path = path_in(directory, &region);
+    try!(save_file::write(&path, &voxels).map_err(|err| format!("Error writing {:?}: {}", path, err)));
+    count += voxels.len();
+  }
+
+  let mut imported = old.as_os_str().to_owned();
+  imported.push(".imported");
+  try!(std::fs::rename(old, &imported).map_err(|err| format!("Error renaming {:?}: {}", old, err)));
+  info!("Imported {} voxels; the old file is now {:?}", count, imported);
+  Ok(())
+}
+
+/// The position of the `i`th node of a `voxel_data::tree::Branches` within its parent, in xyz order.
+fn octant(i: usize) -> (i32, i32, i32) {
+  ((i >> 2) as i32 & 1, (i >> 1) as i32 & 1, i as i32 & 1)
+}
+
+fn collect_voxels(
+  node    : &voxel::tree::Node,
+  bounds  : &voxel::bounds::T,
+  regions : &mut fnv_map::T<Id, Vec<(voxel::bounds::T, voxel::T)>>,
+) {
+  if let Some(voxel) = node.data {
+    regions.entry(of_voxel(bounds)).or_insert_with(Vec::new).push((*bounds, voxel));
+  }
+  if let voxel_data::tree::Inner::Branches(ref branches) = node.next {
+    for (i, child) in branches.as_flat_array().iter().enumerate() {
+      let (x, y, z) = octant(i);
+      let child_bounds =
+        voxel::bounds::new(2 * bounds.x + x, 2 * bounds.y + y, 2 * bounds.z + z, bounds.lg_size - 1);
+      collect_voxels(child, &child_bounds, regions);
+    }
+  }
+}
+
+/// The size of the top-level nodes of a tree, which voxel_data keeps to itself, or `None` if the tree has no voxels.
+/// This finds a voxel by walking down the tree, and then checks which size puts that voxel where `get` finds it.
+fn tree_lg_size(tree: &voxel::tree::T) -> Result<Option<i16>, String> {
+  fn find<'a>(branches: &'a voxel::tree::Branches, path: &mut Vec<usize>) -> Option<&'a voxel::T> {
+    for (i, node) in branches.as_flat_array().iter().enumerate() {
+      path.push(i);
+      if let Some(ref voxel) = node.data {
+        return Some(voxel)
+      }
+      if let voxel_data::tree::Inner::Branches(ref branches) = node.next {
+        if let Some(voxel) = find(branches, path) {
+          return Some(voxel)
+        }
+      }
+      path.pop();
+    }
+    None
+  }
+
+  let mut path = Vec::new();
+  let voxel =
+    match find(&tree.contents, &mut path) {
+      None => return Ok(None),
+      Some(voxel) => voxel,
+    };
+
+  for lg_size in 0 .. 32 {
+    let (x, y, z) = octant(path[0]);
+    let mut bounds = voxel::bounds::new(x - 1, y - 1, z - 1, lg_size);
+    for &i in &path[1..] {
+      let (x, y, z) = octant(i);
+      bounds = voxel::bounds::new(2 * bounds.x + x, 2 * bounds.y + y, 2 * bounds.z + z, bounds.lg_size - 1);
+    }
+    if tree.get(&bounds).map(|found| found as *const voxel::T) == Some(voxel as *const voxel::T) {
+      return Ok(Some(lg_size))
+    }
+  }
+  Err(String::from("its voxels aren't where they're expected to be"))
+}
+
+fn shift(x: i32, lg: i16) -> i32 {
+  if lg >= 0 { x << lg } else { x >> -lg }
+}
+
+/// The region containing a voxel's low corner.
+pub fn of_voxel(bounds: &voxel::bounds::T) -> Id {
+  let lg = bounds.lg_size - LG_SIZE;
+  Point3::new(shift(bounds.x, lg), shift(bounds.y, lg), shift(bounds.z, lg))
+}
+
+/// The regions (inclusive) overlapping a box of unit voxels.
+fn of_aabb(aabb: &Aabb3<i32>) -> (Id, Id) {
+  let low = of_voxel(&voxel::bounds::new(aabb.min.x, aabb.min.y, aabb.min.z, 0));
+  let high = of_voxel(&voxel::bounds::new(aabb.max.x - 1, aabb.max.y - 1, aabb.max.z - 1, 0));
+  (low, high)
+}
+
+impl T {
+  /// The world's directory.
+  pub fn directory(&self) -> &Path {
+    &self.directory
+  }
+
+  fn path_of(&self, region: &Id) -> PathBuf {
+    path_in(&self.directory, region)
+  }
+
+  /// Make sure the saved edits in every region a voxel overlaps are in the terrain.
+  pub fn ensure_loaded_voxel(&self, terrain: &terrain::T, bounds: &voxel::bounds::T) {
+    if bounds.lg_size <= LG_SIZE {
+      self.ensure_loaded_region(terrain, &of_voxel(bounds));
+      return
+    }
+    let size = 1 << (bounds.lg_size - LG_SIZE);
+    let low = of_voxel(bounds);
+    self.ensure_loaded_regions(terrain, &low, &Point3::new(low.x + size - 1, low.y + size - 1, low.z + size - 1));
+  }
+
+  /// Make sure the saved edits in every region overlapping a box of unit voxels are in the terrain.
+  pub fn ensure_loaded(&self, terrain: &terrain::T, aabb: &Aabb3<i32>) {
+    if aabb.max.x <= aabb.min.x || aabb.max.y <= aabb.min.y || aabb.max.z <= aabb.min.z {
+      return
+    }
+    let (low, high) = of_aabb(aabb);
+    self.ensure_loaded_regions(terrain, &low, &high);
+  }
+
+  fn ensure_loaded_regions(&self, terrain: &terrain::T, low: &Id, high: &Id) {
+    for x in low.x .. high.x + 1 {
+    for y in low.y .. high.y + 1 {
+    for z in low.z .. high.z + 1 {
+      self.ensure_loaded_region(terrain, &Point3::new(x, y, z));
+    }}}
+  }
+
+  fn ensure_loaded_region(&self, terrain: &terrain::T, region: &Id) {
+    let mut loaded = self.loaded.lock().unwrap();
+    if loaded.contains(region) {
+      return
+    }
+    loaded.insert(*region);
+
+    let path = self.path_of(region);
+    match self.read(&path) {
+      Ok(None) => {},
+      Ok(Some(voxels)) => {
+        debug!("Loaded {} edited voxels from {:?}", voxels.len(), path);
+        let mut tree = terrain.voxels.write().unwrap();
+        let mut edited = self.edited.lock().unwrap();
+        let edited = edited.entry(*region).or_insert_with(fnv_set::new);
+        for (bounds, voxel) in voxels {
+          tree.get_mut_or_create(&bounds).data = Some(voxel);
+          edited.insert(bounds);
+        }
+      },
+      Err(err) => {
+        error!("Couldn't load {:?}; it will be regenerated, but not saved over: {}", path, err);
+        self.unreadable.lock().unwrap().insert(*region);
+      },
+    }
+  }
+
+  fn read(&self, path: &Path) -> Result<Option<Vec<(voxel::bounds::T, voxel::T)>>, save_file::Error> {
+    match save_file::read(path, save_file::Kind::Region) {
+      Err(save_file::Error::Io(ref err)) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(err),
+      Ok(voxels) => Ok(Some(voxels)),
+    }
+  }
+
+  /// Note that a voxel has been edited, so it needs to be saved.
+  pub fn mark_dirty(&self, bounds: &voxel::bounds::T) {
+    let region = of_voxel(bounds);
+    self.edited.lock().unwrap().entry(region).or_insert_with(fnv_set::new).insert(*bounds);
+    self.dirty.lock().unwrap().insert(region);
+  }
+
+  /// Save every region with unsaved edits.
+  pub fn save_dirty(&self, terrain: &terrain::T) {
+    let _writing = self.writing.lock().unwrap();
+    let dirty: Vec<Id> = self.dirty.lock().unwrap().drain().collect();
+    if dirty.is_empty() {
+      return
+    }
+    info!("Saving {} regions", dirty.len());
+    if let Err(err) = std::fs::create_dir_all(&self.directory) {
+      error!("Couldn't create {:?}: {}", self.directory, err);
+      self.dirty.lock().unwrap().extend(dirty);
+      return
+    }
+
+    for region in dirty {
+      if self.unreadable.lock().unwrap().contains(&region) {
+        warn!("Not saving {:?} over an unreadable file", region);
+        // Still unsaved, so it mustn't be evicted.
+        self.dirty.lock().unwrap().insert(region);
+        continue
+      }
+      let voxels =
+        match self.edited_voxels(terrain, &region) {
+          // Evicted since it was marked dirty, which saved it.
+          None => continue,
+          Some(voxels) => voxels,
+        };
+      let path = self.path_of(&region);
+      if let Err(err) = save_file::write(&path, &voxels) {
+        error!("Couldn't save {:?}: {}", path, err);
+        // Try again next time.
+        self.dirty.lock().unwrap().insert(region);
+      }
+    }
+  }
+
+  /// The current contents of every edited voxel in a region, or `None` if it isn't loaded.
+  fn edited_voxels(&self, terrain: &terrain::T, region: &Id) -> Option<Vec<(voxel::bounds::T, voxel::T)>> {
+    let tree = terrain.voxels.read().unwrap();
+    let edited = self.edited.lock().unwrap();
+    edited.get(region).map(|edited| contents(&tree, edited))
+  }
+
+  /// The regions whose saved edits are in the terrain.
+  pub fn loaded(&self) -> Vec<Id> {
+    self.loaded.lock().unwrap().iter().cloned().collect()
+  }
+
+  /// Drop a region from the terrain, saving its edits first if they haven't been.
+  /// It's regenerated, and its edits read back in, the next time it's needed.
+  /// Nothing else should be editing the region meanwhile.
+  /// Returns roughly how many bytes were freed, or `None` if its edits couldn't be saved, so it has to stay.
+  pub fn evict(&self, terrain: &terrain::T, region: &Id) -> Option<usize> {
+    let _writing = self.writing.lock().unwrap();
+    let mut loaded = self.loaded.lock().unwrap();
+    if !loaded.contains(region) {
+      return Some(0)
+    }
+    let mut tree = terrain.voxels.write().unwrap();
+    let mut edited = self.edited.lock().unwrap();
+    let mut dirty = self.dirty.lock().unwrap();
+
+    if dirty.contains(region) {
+      if self.unreadable.lock().unwrap().contains(region) {
+        return None
+      }
+      let voxels = edited.get(region).map_or_else(Vec::new, |edited| contents(&tree, edited));
+      let path = self.path_of(region);
+      if let Err(err) = save_file::write(&path, &voxels) {
+        error!("Couldn't save {:?}, so it can't be evicted: {}", path, err);
+        return None
+      }
+      dirty.remove(region);
+    }
+
+    edited.remove(region);
+    loaded.remove(region);
+    Some(terrain::forget(&mut tree, &voxel::bounds::new(region.x, region.y, region.z, LG_SIZE)))
+  }
+}
+
+/// The contents of some voxels in `tree`.
+fn contents(tree: &voxel::tree::T, voxels: &fnv_set::T<voxel::bounds::T>) -> Vec<(voxel::bounds::T, voxel::T)> {
+  voxels.iter()
+    .filter_map(|bounds| tree.get(bounds).map(|voxel| (*bounds, *voxel)))
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+  use cgmath::{Point3};
+  use collision::{Aabb3};
+  use std;
+  use std::io::{Read, Write};
+  use std::path::{PathBuf};
+  use time;
+
+  use common::voxel;
+
+  use lock_order;
+  use terrain;
+
+  use super::*;
+
+  fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("playform-regions-test-{}", time::precise_time_ns()))
+  }
+
+  fn new_terrain() -> terrain::T {
+    terrain::T::new(terrain::Seed::new(0), lock_order::TERRAIN_VOXELS)
+  }
+
+  fn edit(terrain: &terrain::T, regions: &T, bounds: &voxel::bounds::T, material: voxel::Material) {
+    terrain.voxels.write().unwrap().get_mut_or_create(bounds).data = Some(voxel::Volume(material));
+    regions.mark_dirty(bounds);
+  }
+
+  fn material(terrain: &terrain::T, bounds: &voxel::bounds::T) -> Option<voxel::Material> {
+    match terrain.voxels.read().unwrap().get(bounds) {
+      Some(&voxel::Volume(material)) => Some(material),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn negative_coordinates() {
+    let size = 1 << LG_SIZE;
+    assert_eq!(of_voxel(&voxel::bounds::new(0, 0, 0, 0)), Point3::new(0, 0, 0));
+    assert_eq!(of_voxel(&voxel::bounds::new(size - 1, 0, 0, 0)), Point3::new(0, 0, 0));
+    assert_eq!(of_voxel(&voxel::bounds::new(-1, -size, -size - 1, 0)), Point3::new(-1, -1, -2));
+    // Voxels bigger and smaller than a unit.
+    assert_eq!(of_voxel(&voxel::bounds::new(-1, 1, 0, 1)), Point3::new(-1, 0, 0));
+    assert_eq!(of_voxel(&voxel::bounds::new(-1, 0, 0, LG_SIZE + 1)), Point3::new(-2, 0, 0));
+    assert_eq!(of_voxel(&voxel::bounds::new(-1, 1, 0, -2)), Point3::new(-1, 0, 0));
+
+    // The high corner is exclusive.
+    let aabb = Aabb3::new(Point3::new(-size - 1, -1, 0), Point3::new(1, 0, size));
+    assert_eq!(of_aabb(&aabb), (Point3::new(-2, -1, 0), Point3::new(0, -1, 0)));
+  }
+
+  #[test]
+  fn save_and_load() {
+    let directory = temp_dir();
+    let bounds = [
+      voxel::bounds::new(-1, -40, 3, 0),
+      voxel::bounds::new(5, 6, 7, 0),
+    ];
+
+    {
+      let terrain = new_terrain();
+      let regions = new(&directory);
+      for bounds in &bounds {
+        edit(&terrain, &regions, bounds, voxel::Material::Marble);
+      }
+      regions.save_dirty(&terrain);
+    }
+
+    let terrain = new_terrain();
+    let regions = new(&directory);
+    regions.ensure_loaded(&terrain, &Aabb3::new(Point3::new(-8, -40, 0), Point3::new(8, 8, 8)));
+    let loaded: Vec<_> = bounds.iter().map(|bounds| material(&terrain, bounds)).collect();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(loaded, vec!(Some(voxel::Material::Marble), Some(voxel::Material::Marble)));
+  }
+
+  #[test]
+  fn unreadable_region_is_kept() {
+    let directory = temp_dir();
+    let regions = new(&directory);
+    let region = Point3::new(0, 0, 0);
+    let path = regions.path_of(&region);
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::File::create(&path).unwrap().write_all(b"not a region").unwrap();
+
+    let terrain = new_terrain();
+    regions.ensure_loaded(&terrain, &Aabb3::new(Point3::new(0, 0, 0), Point3::new(1, 1, 1)));
+    edit(&terrain, &regions, &voxel::bounds::new(0, 0, 0, 0), voxel::Material::Stone);
+    regions.save_dirty(&terrain);
+    let evicted = regions.evict(&terrain, &region);
+
+    let mut contents = Vec::new();
+    std::fs::File::open(&path).unwrap().read_to_end(&mut contents).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(&contents[..], &b"not a region"[..]);
+    // The edit can't be saved anywhere, so the region has to stay loaded.
+    assert_eq!(evicted, None);
+    assert_eq!(regions.loaded(), vec!(region));
+  }
+
+  #[test]
+  fn import_old_terrain_file() {
+    let directory = temp_dir();
+    std::fs::create_dir_all(&directory).unwrap();
+    let old = directory.join(OLD_TERRAIN_FILE);
+    let world = directory.join("world");
+    let bounds = [
+      voxel::bounds::new(-70, 3, -1, 0),
+      voxel::bounds::new(2, -1, 40, 0),
+      voxel::bounds::new(-1, 0, 0, 3),
+    ];
+
+    {
+      let mut tree = voxel::tree::new();
+      for bounds in &bounds {
+        tree.get_mut_or_create(bounds).data = Some(voxel::Volume(voxel::Material::Bark));
+      }
+      let mut file = std::fs::File::create(&old).unwrap();
+      bincode::serialize_into(&mut file, &tree, bincode::Infinite).unwrap();
+    }
+
+    let imported = import_old_terrain(&old, &world);
+    let renamed = !old.exists() && directory.join("default.terrain.imported").exists();
+    let info = world_info(&world, 1234);
+
+    let terrain = new_terrain();
+    let regions = new(&world);
+    for bounds in &bounds {
+      regions.ensure_loaded_voxel(&terrain, bounds);
+    }
+    let loaded: Vec<_> = bounds.iter().map(|bounds| material(&terrain, bounds)).collect();
+
+    // Importing again would be ambiguous.
+    std::fs::copy(directory.join("default.terrain.imported"), &old).unwrap();
+    let again = import_old_terrain(&old, &world);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    imported.unwrap();
+    assert!(renamed);
+    assert_eq!(info.unwrap().seed, OLD_SEED);
+    assert_eq!(loaded, vec!(Some(voxel::Material::Bark); 3));
+    assert!(again.is_err());
+  }
+}
@@ -5,83 +5,160 @@ use bincode;
 use stopwatch;
 use thread_scoped;
 use time;
-use voxel_data;
 
 use common;
 use common::closure_series;
+use common::protocol;
 use common::socket::ReceiveSocket;
+use common::trace;
 
+use admin;
 use client_recv_thread::apply_client_update;
+use config;
+use evict_terrain::evict_terrain;
+use metrics;
+use player;
+use regions;
 use server;
+use spawn;
+use gaia_pool;
 use update_gaia;
 use update_gaia::update_gaia;
 use update_world::update_world;
 
-mod terrain {
-  pub use ::terrain::*;
-}
+/// The longest we'll spend applying queued edits when shutting down, before giving up on the rest.
+const DRAIN_TIMEOUT_NS: u64 = 5_000_000_000;
+/// How long an idle terrain thread waits before checking for more work.
+const IDLE_SLEEP_MS: u64 = 1;
+/// How often to check whether terrain is over its memory budget. Checking walks the whole voxel tree.
+const EVICTION_INTERVAL_NS: u64 = 10_000_000_000;
+
+/// Run a server with the given settings until `quit_signal` is set, carrying out any commands given to `admin`.
+/// Before returning, queued edits are applied, the world is saved, and then clients are told the server is shutting
+/// down; the caller should only close sockets after that.
+/// Returns an error, without starting, if the settings or the saved world can't be used.
+pub fn run(listen_url: &str, config: &config::T, admin: &admin::T, quit_signal: &Mutex<bool>) -> Result<(), String> {
+  try!(config.validate().map_err(|err| format!("Invalid configuration: {}", err)));
+
+  let old_terrain = std::path::Path::new(regions::OLD_TERRAIN_FILE);
+  if old_terrain.exists() {
+    try!(regions::import_old_terrain(old_terrain, &config.world));
+  }
+
+  let world_info = try!(regions::world_info(&config.world, config.seed).map_err(|err| {
+    format!("Couldn't open the world: {}", err)
+  }));
+  info!("World seed is {}", world_info.seed);
+  try!(regions::check_all(&config.world).map_err(|err| {
+    format!("Refusing to start with regions this server can't load:\n{}", err)
+  }));
 
-const SAVE_TERRAIN: bool = false;
+  let spawn_points = try!(spawn::load_points(&config.world).map_err(|err| {
+    format!("Couldn't load spawn points: {}", err)
+  }));
 
-#[allow(missing_docs)]
-pub fn run(listen_url: &str, quit_signal: &Mutex<bool>) {
-  let gaia_updates = Mutex::new(std::collections::VecDeque::new());
+  let gaia = gaia_pool::new();
 
   let listen_socket = ReceiveSocket::new(listen_url.as_ref(), None);
   let listen_socket = Mutex::new(listen_socket);
 
-  let server = server::new();
+  let server = server::new(config, world_info.seed, spawn_points);
   let server = &server;
 
-  let terrain_path = std::path::Path::new("default.terrain");
-
-  println!("Loading terrain from {}", terrain_path.to_str().unwrap());
-  load_terrain(&server.terrain_loader.terrain, &terrain_path);
-
   let mut threads = Vec::new();
 
   unsafe {
-    threads.push(thread_scoped::scoped(|| {
-      while !*quit_signal.lock().unwrap() {
-        info!("Outstanding gaia updates: {}", gaia_updates.lock().unwrap().len());
-        std::thread::sleep(std::time::Duration::from_secs(1));
-      }
+    let server = &server;
+    let gaia = &gaia;
+    threads.push(thread_scoped::scoped(move || {
+      metrics::run_sampler(server, gaia, quit_signal);
 
       stopwatch::clone()
     }))
   }
 
+  if config.metrics_port != 0 {
+    unsafe {
+      let server = &server;
+      threads.push(thread_scoped::scoped(move || {
+        metrics::serve(&server.metrics, server.config.metrics_port, quit_signal);
+
+        stopwatch::clone()
+      }))
+    }
+  }
+
   unsafe {
     let server = &server;
-    let gaia_updates = &gaia_updates;
-    let listen_socket = &listen_socket;
+    let gaia = &gaia;
     threads.push(thread_scoped::scoped(move || {
-      closure_series::new(vec!(
-        quit_upon(&quit_signal),
-        consider_world_update(&server, |up| { gaia_updates.lock().unwrap().push_back(up) }),
-        network_listen(&listen_socket, server, |up| { gaia_updates.lock().unwrap().push_back(up) }),
-        consider_gaia_update(&server, || { gaia_updates.lock().unwrap().pop_front() } ),
-      ))
-      .until_quit();
+      let mut last_save = time::precise_time_ns();
+      let mut last_eviction = last_save;
+      while !*quit_signal.lock().unwrap() {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let now = time::precise_time_ns();
+        if now - last_save >= server.config.autosave_seconds * 1_000_000_000 {
+          trace::time("autosave", || {
+            server.regions.save_dirty(&server.terrain_loader.terrain);
+            player::save_all(server);
+          });
+          last_save = now;
+        }
+        if now - last_eviction >= EVICTION_INTERVAL_NS {
+          evict_terrain(server, gaia);
+          last_eviction = now;
+        }
+      }
 
       stopwatch::clone()
-    }));
+    }))
   }
+
   unsafe {
     let server = &server;
-    let gaia_updates = &gaia_updates;
-    let quit_signal = &quit_signal;
-    let listen_socket = &listen_socket;
+    let gaia = &gaia;
     threads.push(thread_scoped::scoped(move || {
-      closure_series::new(vec!(
-        quit_upon(&quit_signal),
-        consider_world_update(&server, |up| { gaia_updates.lock().unwrap().push_back(up) }),
-        network_listen(&listen_socket, server, |up| { gaia_updates.lock().unwrap().push_back(up) }),
-      ))
-      .until_quit();
+      admin::run(server, admin, gaia, quit_signal);
 
       stopwatch::clone()
-    }));
+    }))
+  }
+
+  for _ in 0 .. 2 {
+    unsafe {
+      let server = &server;
+      let gaia = &gaia;
+      let quit_signal = &quit_signal;
+      let listen_socket = &listen_socket;
+      threads.push(thread_scoped::scoped(move || {
+        closure_series::new(vec!(
+          quit_upon(&quit_signal),
+          print_stopwatch_upon(admin),
+          consider_world_update(&server, |up| { gaia.push(up) }),
+          network_listen(&listen_socket, server, |up| { gaia.push(up) }),
+        ))
+        .until_quit();
+
+        stopwatch::clone()
+      }));
+    }
+  }
+
+  for _ in 0 .. config.terrain_threads {
+    unsafe {
+      let server = &server;
+      let gaia = &gaia;
+      threads.push(thread_scoped::scoped(move || {
+        closure_series::new(vec!(
+          quit_upon(&quit_signal),
+          print_stopwatch_upon(admin),
+          consider_gaia_update(&server, gaia),
+        ))
+        .until_quit();
+
+        stopwatch::clone()
+      }));
+    }
   }
 
   for thread in threads {
@@ -89,21 +166,59 @@ pub fn run(listen_url: &str, quit_signal: &Mutex<bool>) {
     stopwatch.print();
   }
 
+  let reason = admin.shutdown_reason().unwrap_or_else(|| String::from("The server is shutting down"));
+  info!("Shutting down: {}", reason);
+
+  trace::time("drain_edits", || {
+    drain_edits(server, &gaia);
+  });
+
   info!("Voxel takes {} bytes", std::mem::size_of::<common::voxel::T>());
 
   println!(
     "Terrain is using {} MB",
-    tree_ram_usage(&server.terrain_loader.terrain.voxels.lock().unwrap()) as f32 / (1 << 20) as f32,
+    server.terrain_loader.terrain.ram_usage() as f32 / (1 << 20) as f32,
   );
 
-  if SAVE_TERRAIN {
-    println!("Saving terrain to {}", terrain_path.to_str().unwrap());
-    stopwatch::time("save_terrain", || {
-      save_terrain(&server.terrain_loader.terrain, &terrain_path);
-    });
+  println!("Saving terrain to {}", config.world.display());
+  trace::time("save_terrain", || {
+    server.regions.save_dirty(&server.terrain_loader.terrain);
+  });
+  player::save_all(server);
+
+  // Only now that nothing else will be sent, e.g. by drained edits.
+  for (_, client) in server.clients.lock().unwrap().iter_mut() {
+    client.send(protocol::ServerToClient::ShuttingDown { reason: reason.clone() });
   }
 
   stopwatch::clone().print();
+
+  match trace::write() {
+    Ok(None) => {},
+    Ok(Some(path)) => info!("Wrote a trace to {}", path.display()),
+    Err(err) => warn!("Couldn't write a trace: {}", err),
+  }
+
+  Ok(())
+}
+
+/// Apply the edits still queued for gaia, for up to `DRAIN_TIMEOUT_NS`. Queued loads are dropped,
+/// since nobody's around to see them.
+fn drain_edits(server: &server::T, gaia: &gaia_pool::T) {
+  let start = time::precise_time_ns();
+  let mut applied = 0;
+  while let Some(up) = gaia.pop_edit() {
+    if time::precise_time_ns() - start >= DRAIN_TIMEOUT_NS {
+      let (_, remaining) = gaia.queued();
+      warn!("Gave up on {} queued edits after applying {}", remaining + 1, applied);
+      return
+    }
+    update_gaia(server, up);
+    applied += 1;
+  }
+  if applied > 0 {
+    info!("Applied {} queued edits", applied);
+  }
 }
 
 fn quit_upon(signal: &Mutex<bool>) -> closure_series::Closure {
@@ -116,6 +231,19 @@ fn quit_upon(signal: &Mutex<bool>) -> closure_series::Closure {
   }
 }
 
+/// Print this thread's stopwatch whenever an admin asks for one.
+fn print_stopwatch_upon(admin: &admin::T) -> closure_series::Closure {
+  let mut seen = admin.stopwatch_requests();
+  box move || {
+    let requests = admin.stopwatch_requests();
+    if requests != seen {
+      seen = requests;
+      stopwatch::clone().print();
+    }
+    closure_series::Continue
+  }
+}
+
 fn consider_world_update<'a, ToGaia>(
   server: &'a server::T,
   mut to_gaia: ToGaia,
@@ -124,10 +252,12 @@ fn consider_world_update<'a, ToGaia>(
 {
   box move || {
     if server.update_timer.lock().unwrap().update(time::precise_time_ns()) > 0 {
+      let start = time::precise_time_ns();
       update_world(
         server,
         &mut to_gaia,
       );
+      server.metrics.record_tick(time::precise_time_ns() - start);
       closure_series::Restart
     } else {
       closure_series::Continue
@@ -155,71 +285,16 @@ fn network_listen<'a, ToGaia>(
   }
 }
 
-fn consider_gaia_update<'a, Get>(
+fn consider_gaia_update<'a>(
   server: &'a server::T,
-  mut get_update: Get,
-) -> closure_series::Closure<'a> where
-  Get: FnMut() -> Option<update_gaia::Message> + 'a,
-{
+  gaia: &'a gaia_pool::T,
+) -> closure_series::Closure<'a> {
   box move || {
-    match get_update() {
-      Some(up) => {
-        update_gaia(server, up);
-        closure_series::Restart
-      },
-      None => closure_series::Continue,
-    }
-  }
-}
-
-fn load_terrain(terrain: &terrain::T, path: &std::path::Path) {
-  let mut file =
-    match std::fs::File::open(path) {
-      Err(err) => {
-        warn!("Error opening terrain file: {:?}", err);
-        return
-      },
-      Ok(file) => file,
-    };
-  let loaded =
-    bincode::deserialize_from(
-      &mut file,
-      bincode::Infinite,
-    );
-  let loaded =
-    match loaded {
-      Ok(loaded) => loaded,
-      Err(err) => {
-        warn!("Error loading terrain: {:?}", err);
-        return
-      },
-    };
-  *terrain.voxels.lock().unwrap() = loaded;
-}
-
-fn save_terrain(terrain: &terrain::T, path: &std::path::Path) {
-  let mut file = std::fs::File::create(path).unwrap();
-  bincode::serialize_into(
-    &mut file,
-    &*terrain.voxels.lock().unwrap(),
-    bincode::Infinite,
-  ).unwrap();
-}
-
-fn tree_ram_usage(tree: &common::voxel::tree::T) -> usize {
-  fn tree_ram_usage_inner(branches: &common::voxel::tree::Branches, size: &mut usize) {
-    *size += std::mem::size_of_val(branches);
-    for node in branches.as_flat_array() {
-      match node.next {
-        voxel_data::tree::Inner::Empty => {},
-        voxel_data::tree::Inner::Branches(ref branches) => {
-          tree_ram_usage_inner(branches, size);
-        },
-      }
+    if gaia.work(server) {
+      closure_series::Restart
+    } else {
+      std::thread::sleep(std::time::Duration::from_millis(IDLE_SLEEP_MS));
+      closure_series::Continue
     }
   }
-
-  let mut r = 0;
-  tree_ram_usage_inner(&tree.contents, &mut r);
-  r
 }
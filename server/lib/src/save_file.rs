@@ -0,0 +1,317 @@
+//! The on-disk format shared by everything the server saves.
+//! Each file starts with a header identifying it and saying which version of the format wrote it;
+//! older files are upgraded through a chain of migrations when they're read.
+
+use bincode;
+use cgmath::{Point3};
+use serde;
+use std;
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use terrain;
+
+/// Every save file starts with these bytes.
+pub const MAGIC: [u8; 8] = *b"PLAYFORM";
+/// The version of the format written by this build.
+/// Bump it and add a migration whenever a saved type's layout changes,
+/// e.g. `voxel::Material`, `voxel::T`, or the types saved in regions.
+pub const FORMAT_VERSION: u32 = 3;
+
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Headers are smaller than this, so this is all `check` needs to read.
+const MAX_HEADER_LENGTH: u64 = 64;
+
+/// What a file holds, for migrations that only change one kind of file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+  WorldInfo,
+  Region,
+  Player,
+}
+
+/// Upgrades a file body from one version to the next.
+type Migration = fn(Kind, Vec<u8>) -> Result<Vec<u8>, String>;
+
+/// `MIGRATIONS[i]` upgrades a body from version `i + 1` to version `i + 2`.
+/// Before version 1, the whole world was one `default.terrain` file; `regions::import_old_terrain` splits that into
+/// region files in the current format, before anything else is read.
+const MIGRATIONS: [Migration; 2] = [
+  from_headerless,
+  precise_player_positions,
+];
+
+/// Version 1 files had no header, but their bodies are laid out the same as version 2's.
+/// Since there's no header to go on, `read` only takes a file to be version 1 if all of it parses as what's expected.
+fn from_headerless(_: Kind, body: Vec<u8>) -> Result<Vec<u8>, String> {
+  Ok(body)
+}
+
+/// Version 2 saved player positions as `f32`s; version 3 saves them as `f64`s.
+/// The position is the first thing in a saved player.
+fn precise_player_positions(kind: Kind, body: Vec<u8>) -> Result<Vec<u8>, String> {
+  if kind != Kind::Player {
+    return Ok(body)
+  }
+  let mut rest = &body[..];
+  let position: Point3<f32> =
+    try!(bincode::deserialize_from(&mut rest, bincode::Infinite).map_err(|err| format!("{:?}", err)));
+  let position = Point3::new(position.x as f64, position.y as f64, position.z as f64);
+  let mut upgraded = try!(bincode::serialize(&position, bincode::Infinite).map_err(|err| format!("{:?}", err)));
+  upgraded.extend_from_slice(rest);
+  Ok(upgraded)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Header {
+  pub magic             : [u8; 8],
+  pub format_version    : u32,
+  /// The terrain generator version the saved data was laid on top of.
+  pub generator_version : u32,
+}
+
+#[derive(Debug)]
+pub enum Error {
+  Io(io::Error),
+  /// The file was written by a newer version of the server.
+  FromTheFuture(u32),
+  /// The file's data belongs on terrain generated differently than we'd generate it.
+  WrongGenerator(u32),
+  Corrupt(String),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Error::Io(ref err) => write!(f, "{}", err),
+      Error::FromTheFuture(version) =>
+        write!(f, "saved in format version {}, but this server only understands up to {}", version, FORMAT_VERSION),
+      Error::WrongGenerator(version) =>
+        write!(
+          f,
+          "saved with terrain generator version {}, but this server has version {}",
+          version, terrain::GENERATOR_VERSION,
+        ),
+      Error::Corrupt(ref err) => write!(f, "corrupt: {}", err),
+    }
+  }
+}
+
+fn header() -> Header {
+  Header {
+    magic             : MAGIC,
+    format_version    : FORMAT_VERSION,
+    generator_version : terrain::GENERATOR_VERSION,
+  }
+}
+
+/// Is `name` safe to use as a file name, i.e. can't escape the directory it's saved in?
+pub fn is_valid_name(name: &str) -> bool {
+  !name.is_empty() &&
+  name.len() <= MAX_NAME_LENGTH &&
+  name.chars().all(|c| {
+    match c {
+      'a' ... 'z' | 'A' ... 'Z' | '0' ... '9' | '-' | '_' => true,
+      _ => false,
+    }
+  })
+}
+
+/// Write a file so that a crash leaves either the old contents or the new ones, never a mixture.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+  let mut tmp = path.as_os_str().to_owned();
+  tmp.push(".tmp");
+  let tmp = PathBuf::from(tmp);
+  {
+    let mut file = try!(std::fs::File::create(&tmp));
+    try!(file.write_all(contents));
+    try!(file.sync_all());
+  }
+  try!(std::fs::rename(&tmp, path));
+  // Make sure the rename itself is on disk.
+  if let Some(parent) = path.parent() {
+    if let Ok(directory) = std::fs::File::open(parent) {
+      try!(directory.sync_all());
+    }
+  }
+  Ok(())
+}
+
+/// Atomically save `contents` to `path` in the current format.
+pub fn write<T: serde::Serialize>(path: &Path, contents: &T) -> io::Result<()> {
+  let to_io_error = |err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err));
+  let mut bytes = try!(bincode::serialize(&header(), bincode::Infinite).map_err(&to_io_error));
+  bytes.extend(try!(bincode::serialize(contents, bincode::Infinite).map_err(&to_io_error)));
+  write_atomically(path, &bytes)
+}
+
+fn has_header(bytes: &[u8]) -> bool {
+  bytes.len() >= MAGIC.len() && bytes[.. MAGIC.len()] == MAGIC[..]
+}
+
+/// Read and check the header at the start of `bytes`, and return it along with the rest of the file.
+/// Files without a header are taken to be from version 1; it's up to the caller to make sure they parse as one.
+fn split_header(bytes: &[u8]) -> Result<(Header, &[u8]), Error> {
+  if !has_header(bytes) {
+    let header =
+      Header {
+        magic             : MAGIC,
+        format_version    : 1,
+        generator_version : terrain::GENERATOR_VERSION,
+      };
+    return Ok((header, bytes))
+  }
+
+  let mut body = bytes;
+  let header: Header =
+    try!(
+      bincode::deserialize_from(&mut body, bincode::Infinite)
+      .map_err(|err| Error::Corrupt(format!("bad header: {:?}", err)))
+    );
+  if header.format_version > FORMAT_VERSION {
+    return Err(Error::FromTheFuture(header.format_version))
+  }
+  if header.format_version == 0 {
+    return Err(Error::Corrupt(String::from("format version 0")))
+  }
+  if header.generator_version != terrain::GENERATOR_VERSION {
+    return Err(Error::WrongGenerator(header.generator_version))
+  }
+  Ok((header, body))
+}
+
+fn read_bytes(path: &Path, limit: u64) -> Result<Vec<u8>, Error> {
+  let file = try!(std::fs::File::open(path).map_err(Error::Io));
+  let mut bytes = Vec::new();
+  try!(file.take(limit).read_to_end(&mut bytes).map_err(Error::Io));
+  Ok(bytes)
+}
+
+/// Check that a file holding a `T` could be read by this server. Only the header is read, unless there isn't one,
+/// in which case the whole file has to be read to tell an old file from garbage.
+pub fn check<T: serde::de::DeserializeOwned>(path: &Path, kind: Kind) -> Result<Header, Error> {
+  let bytes = try!(read_bytes(path, MAX_HEADER_LENGTH));
+  if has_header(&bytes) {
+    return split_header(&bytes).map(|(header, _)| header)
+  }
+  let _: T = try!(read(path, kind));
+  split_header(&bytes).map(|(header, _)| header)
+}
+
+/// Read a file saved by `write`, in this format version or any earlier one.
+pub fn read<T: serde::de::DeserializeOwned>(path: &Path, kind: Kind) -> Result<T, Error> {
+  let bytes = try!(read_bytes(path, std::u64::MAX));
+  parse(&bytes, kind)
+}
+
+/// Parse the contents of a file saved by `write`, migrating it if necessary.
+/// All of it has to parse, so a file with no header (or a stray file) can't be mistaken for something it isn't.
+fn parse<T: serde::de::DeserializeOwned>(bytes: &[u8], kind: Kind) -> Result<T, Error> {
+  let (header, body) = try!(split_header(bytes));
+
+  let mut body = body.to_vec();
+  for version in header.format_version .. FORMAT_VERSION {
+    debug!("Migrating from format version {} to {}", version, version + 1);
+    body = try!(MIGRATIONS[version as usize - 1](kind, body).map_err(Error::Corrupt));
+  }
+
+  let mut rest = &body[..];
+  let contents =
+    try!(
+      bincode::deserialize_from(&mut rest, bincode::Infinite)
+      .map_err(|err| Error::Corrupt(format!("{:?}", err)))
+    );
+  if !rest.is_empty() {
+    return Err(Error::Corrupt(format!("{} bytes left over", rest.len())))
+  }
+  Ok(contents)
+}
+
+#[cfg(test)]
+mod test {
+  use bincode;
+  use cgmath::{Point3};
+  use serde;
+  use std;
+  use time;
+
+  use terrain;
+
+  use super::*;
+
+  fn header_for(format_version: u32, generator_version: u32) -> Header {
+    Header {
+      magic             : MAGIC,
+      format_version    : format_version,
+      generator_version : generator_version,
+    }
+  }
+
+  fn with_header<T: serde::Serialize>(header: &Header, contents: &T) -> Vec<u8> {
+    let mut bytes = bincode::serialize(header, bincode::Infinite).unwrap();
+    bytes.extend(bincode::serialize(contents, bincode::Infinite).unwrap());
+    bytes
+  }
+
+  #[test]
+  fn header_round_trip() {
+    let path = std::env::temp_dir().join(format!("playform-save-file-test-{}", time::precise_time_ns()));
+    let contents = (Point3::new(1.0, -2.0, 3.5), vec!(4_u32, 5));
+    write(&path, &contents).unwrap();
+
+    let header = check::<(Point3<f64>, Vec<u32>)>(&path, Kind::Region);
+    let read = read::<(Point3<f64>, Vec<u32>)>(&path, Kind::Region);
+    std::fs::remove_file(&path).unwrap();
+
+    let header = header.unwrap();
+    assert_eq!(header.magic, MAGIC);
+    assert_eq!(header.format_version, FORMAT_VERSION);
+    assert_eq!(header.generator_version, terrain::GENERATOR_VERSION);
+    assert_eq!(read.unwrap(), contents);
+  }
+
+  #[test]
+  fn migration_chain() {
+    // A player from before headers, and before positions were `f64`s.
+    let old = (Point3::new(1.5_f32, -2.0, 3.25), 7_u32);
+    let headerless = bincode::serialize(&old, bincode::Infinite).unwrap();
+    let version_2 = with_header(&header_for(2, terrain::GENERATOR_VERSION), &old);
+    for bytes in &[headerless, version_2.clone()] {
+      let player: (Point3<f64>, u32) = parse(bytes, Kind::Player).unwrap();
+      assert_eq!(player, (Point3::new(1.5, -2.0, 3.25), 7));
+    }
+
+    // Positions are only upgraded in players.
+    let region: (Point3<f32>, u32) = parse(&version_2, Kind::Region).unwrap();
+    assert_eq!(region, old);
+  }
+
+  #[test]
+  fn headerless_garbage() {
+    match parse::<(Point3<f64>, u32)>(b"not a save file at all", Kind::Player) {
+      Err(Error::Corrupt(_)) => {},
+      r => panic!("expected a corrupt file, got {:?}", r),
+    }
+  }
+
+  #[test]
+  fn from_the_future() {
+    let bytes = with_header(&header_for(FORMAT_VERSION + 1, terrain::GENERATOR_VERSION), &0_u32);
+    match parse::<u32>(&bytes, Kind::Region) {
+      Err(Error::FromTheFuture(version)) => assert_eq!(version, FORMAT_VERSION + 1),
+      r => panic!("expected a file from the future, got {:?}", r),
+    }
+  }
+
+  #[test]
+  fn wrong_generator() {
+    let bytes = with_header(&header_for(FORMAT_VERSION, terrain::GENERATOR_VERSION + 1), &0_u32);
+    match parse::<u32>(&bytes, Kind::Region) {
+      Err(Error::WrongGenerator(version)) => assert_eq!(version, terrain::GENERATOR_VERSION + 1),
+      r => panic!("expected a file for another generator, got {:?}", r),
+    }
+  }
+}
@@ -1,29 +1,34 @@
-use cgmath::{Point3};
-use collision::{Aabb3};
+use cgmath::{Point2};
 use rand;
-use std::sync::Mutex;
 use time;
 
 use common::protocol;
 use common::fnv_map;
 use common::id_allocator;
 use common::interval_timer::IntervalTimer;
+use common::lock::Mutex;
 use common::socket::SendSocket;
 
+use config;
 use entity;
-use init_mobs::init_mobs;
+use lock_order;
 use lod;
+use metrics;
 use mob;
 use physics;
 use player;
+use regions;
+use spawn;
 use sun::Sun;
 use terrain_loader;
 
-const UPDATES_PER_SECOND: u64 = 30;
-const SUN_TICK_NS: u64 = 1600000;
-
 pub struct Client {
-  pub socket: SendSocket,
+  pub socket        : SendSocket,
+  /// Serialized bytes sent so far, for metrics.
+  pub bytes_sent    : u64,
+  pub messages_sent : u64,
+  /// Set once a send fails, e.g. because the client went away without saying so.
+  pub lost          : bool,
 }
 
 impl Client {
@@ -32,13 +37,21 @@ impl Client {
     use bincode::serialize;
     let msg = serialize(&msg, bincode::Infinite).unwrap();
     match self.socket.write(msg.as_ref()) {
-      Ok(()) => {},
-      Err(err) => warn!("Error sending to client: {:?}", err),
+      Ok(()) => {
+        self.bytes_sent += msg.len() as u64;
+        self.messages_sent += 1;
+      },
+      Err(err) => {
+        warn!("Error sending to client: {:?}", err);
+        self.lost = true;
+      },
     }
   }
 }
 
-// TODO: Audit for s/Mutex/RwLock.
+/// Everything the server shares between threads. Locks are taken in the order given by `lock_order`.
+/// These are `Mutex`es rather than `RwLock`s because nearly every use of them changes something:
+/// players, mobs, physics and the sun are updated every tick, and sending to a client writes to its socket.
 pub struct T {
   pub players           : Mutex<fnv_map::T<entity::id::Player, player::T>>,
   pub mobs              : Mutex<fnv_map::T<entity::id::Mob, mob::Mob>>,
@@ -52,57 +65,68 @@ pub struct T {
 
   pub physics           : Mutex<physics::T>,
   pub terrain_loader    : terrain_loader::T,
+  pub regions           : regions::T,
   pub rng               : Mutex<rand::StdRng>,
+  /// Columns players spawn around.
+  pub spawn_points      : Vec<Point2<f64>>,
+  /// Where the next player to die respawns.
+  pub respawn           : Mutex<spawn::Prepared>,
 
   pub clients           : Mutex<fnv_map::T<protocol::ClientId, Client>>,
 
   pub sun               : Mutex<Sun>,
   pub update_timer      : Mutex<IntervalTimer>,
+
+  pub metrics           : metrics::T,
+  pub config            : config::T,
 }
 
 #[allow(missing_docs)]
-pub fn new() -> T {
-  let world_width: u32 = 1 << 11;
-  let world_width = world_width as f32;
-  let physics =
-    physics::T::new(
-      Aabb3::new(
-        Point3 { x: -world_width, y: -512.0, z: -world_width },
-        Point3 { x: world_width, y: 512.0, z: world_width },
-      )
-    );
-
-  let server = T {
-    players           : Mutex::new(fnv_map::new()),
-    mobs              : Mutex::new(fnv_map::new()),
-
-    player_allocator  : Mutex::new(id_allocator::new()),
-    mob_allocator     : Mutex::new(id_allocator::new()),
-    terrain_allocator : Mutex::new(id_allocator::new()),
-    misc_allocator    : Mutex::new(id_allocator::new()),
-    owner_allocator   : Mutex::new(id_allocator::new()),
-    client_allocator  : Mutex::new(id_allocator::new()),
-
-    physics: Mutex::new(physics),
-    terrain_loader: terrain_loader::T::new(),
+pub fn new(config: &config::T, terrain_seed: u32, spawn_points: Vec<Point2<f64>>) -> T {
+  T {
+    players           : Mutex::new(lock_order::PLAYERS, fnv_map::new()),
+    mobs              : Mutex::new(lock_order::MOBS, fnv_map::new()),
+
+    player_allocator  : Mutex::new(lock_order::LEAF, id_allocator::new()),
+    mob_allocator     : Mutex::new(lock_order::LEAF, id_allocator::new()),
+    terrain_allocator : Mutex::new(lock_order::LEAF, id_allocator::new()),
+    misc_allocator    : Mutex::new(lock_order::LEAF, id_allocator::new()),
+    owner_allocator   : Mutex::new(lock_order::LEAF, id_allocator::new()),
+    client_allocator  : Mutex::new(lock_order::LEAF, id_allocator::new()),
+
+    physics: Mutex::new(lock_order::PHYSICS, physics::T::new()),
+    terrain_loader: terrain_loader::T::new(terrain_seed),
+    regions: regions::new(&config.world),
     rng: {
       let seed = [0];
       let seed: &[usize] = &seed;
-      Mutex::new(rand::SeedableRng::from_seed(seed))
+      Mutex::new(lock_order::LEAF, rand::SeedableRng::from_seed(seed))
     },
 
-    clients: Mutex::new(fnv_map::new()),
-    sun: Mutex::new(Sun::new(SUN_TICK_NS)),
+    spawn_points: spawn_points,
+    respawn: Mutex::new(lock_order::LEAF, spawn::prepared()),
+
+    clients: Mutex::new(lock_order::CLIENTS, fnv_map::new()),
+    sun: Mutex::new(lock_order::SUN, Sun::new(config.sun_tick_ns)),
 
     update_timer: {
       let now = time::precise_time_ns();
       let nanoseconds_per_second = 1000000000;
       Mutex::new(
-        IntervalTimer::new(nanoseconds_per_second / UPDATES_PER_SECOND, now)
+        lock_order::LEAF,
+        IntervalTimer::new(nanoseconds_per_second / config.updates_per_second, now)
       )
-    }
-  };
+    },
+
+    metrics: metrics::new(),
+    config: config.clone(),
+  }
+}
 
-  init_mobs(&server);
-  server
+/// Tell a client why it's being turned away, and forget about it.
+pub fn kick(server: &T, client_id: protocol::ClientId, reason: String) {
+  info!("Kicking {:?}: {}", client_id, reason);
+  server.clients.lock().unwrap().remove(&client_id).map(|mut client| {
+    client.send(protocol::ServerToClient::Kicked(reason));
+  });
 }
@@ -0,0 +1,190 @@
+//! Find somewhere safe to put a body entering the world.
+
+use cgmath::{Point2, Point3, Vector3};
+use collision::{Aabb3};
+use rand::Rng;
+use std;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+use common::voxel;
+use common::world_position;
+
+use server;
+
+/// How far from the requested column we look for open space.
+const SEARCH_RADIUS: i32 = 16;
+/// We look for the highest open space between these heights.
+const TOP: i32 = 128;
+const BOTTOM: i32 = -128;
+/// Where players spawn if the world doesn't say otherwise.
+const DEFAULT_POINT: [f64; 2] = [0.5, 4.5];
+const POINTS_FILE: &'static str = "spawn_points";
+
+/// Read a world's spawn points: one column per line, as an x and z coordinate separated by whitespace.
+/// Blank lines and lines starting with `#` are ignored. A world without any gets a single default one.
+pub fn load_points<P: AsRef<Path>>(directory: P) -> Result<Vec<Point2<f64>>, String> {
+  let path = directory.as_ref().join(POINTS_FILE);
+  let file =
+    match std::fs::File::open(&path) {
+      Err(ref err) if err.kind() == io::ErrorKind::NotFound =>
+        return Ok(vec!(Point2::new(DEFAULT_POINT[0], DEFAULT_POINT[1]))),
+      Err(err) => return Err(format!("Error opening {:?}: {}", path, err)),
+      Ok(file) => file,
+    };
+
+  let mut points = Vec::new();
+  for (i, line) in io::BufReader::new(file).lines().enumerate() {
+    let line = try!(line.map_err(|err| format!("Error reading {:?}: {}", path, err)));
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue
+    }
+    let coords: Vec<f64> =
+      try!(
+        line.split_whitespace()
+        .map(|coord| coord.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|err| format!("{:?} line {}: {}", path, i + 1, err))
+      );
+    if coords.len() != 2 {
+      return Err(format!("{:?} line {}: expected an x and z coordinate, got {:?}", path, i + 1, line))
+    }
+    points.push(Point2::new(coords[0], coords[1]));
+  }
+  if points.is_empty() {
+    return Err(format!("{:?} doesn't have any spawn points", path))
+  }
+  Ok(points)
+}
+
+/// Is the unit block at `p` empty enough to stand in? This matches what's solid to physics.
+fn is_open(server: &server::T, p: &Point3<i32>) -> bool {
+  let bounds = voxel::bounds::new(p.x, p.y, p.z, 0);
+  server.regions.ensure_loaded_voxel(&server.terrain_loader.terrain, &bounds);
+  match server.terrain_loader.terrain.load(&bounds) {
+    voxel::Volume(voxel::Material::Empty) => true,
+    _ => false,
+  }
+}
+
+/// The low corner of the highest open space in a column that fits a body of `size` blocks,
+/// resting on something solid.
+fn highest_open_space(server: &server::T, x: i32, z: i32, size: &Vector3<i32>) -> Option<Point3<i32>> {
+  let level_is_open = |y| {
+    (x .. x + size.x).all(|x| (z .. z + size.z).all(|z| is_open(server, &Point3::new(x, y, z))))
+  };
+
+  // How many fully-open levels we've seen in a row, coming down from the top.
+  let mut open = 0;
+  for y in (BOTTOM .. TOP).rev() {
+    if level_is_open(y) {
+      open += 1;
+      continue
+    }
+    if open >= size.y {
+      return Some(Point3::new(x, y + 1, z))
+    }
+    open = 0;
+  }
+  None
+}
+
+/// Find the highest open space that fits a body of `size`, in the column around `column`,
+/// or failing that in nearby columns. Returns the body's bounds.
+pub fn find(server: &server::T, column: &Point2<f64>, size: &Vector3<f32>) -> Option<world_position::Bounds> {
+  let blocks = Vector3::new(size.x.ceil() as i32, size.y.ceil() as i32, size.z.ceil() as i32);
+  let size = world_position::vector_of_f32(size);
+  // Center the body in the blocks it takes up.
+  let offset = Vector3::new((blocks.x as f64 - size.x) / 2.0, 0.0, (blocks.z as f64 - size.z) / 2.0);
+  let x = (column.x - size.x / 2.0).round() as i32;
+  let z = (column.y - size.z / 2.0).round() as i32;
+
+  // Search rings of columns, nearest first.
+  for r in 0 .. SEARCH_RADIUS + 1 {
+    for dx in -r .. r + 1 {
+    for dz in -r .. r + 1 {
+      if dx.abs() != r && dz.abs() != r {
+        continue
+      }
+      if let Some(low) = highest_open_space(server, x + dx, z + dz, &blocks) {
+        let min = Point3::new(low.x as f64, low.y as f64, low.z as f64) + offset;
+        return Some(Aabb3::new(min, min + size))
+      }
+    }}
+  }
+  None
+}
+
+fn random_point(server: &server::T) -> Point2<f64> {
+  *server.rng.lock().unwrap().choose(&server.spawn_points).unwrap()
+}
+
+/// The bounds of a body of `size` above `column`, higher than any terrain we'd look at.
+fn above_column(column: &Point2<f64>, size: &Vector3<f32>) -> world_position::Bounds {
+  let size = world_position::vector_of_f32(size);
+  let min = Point3::new(column.x - size.x / 2.0, TOP as f64, column.y - size.z / 2.0);
+  Aabb3::new(min, min + size)
+}
+
+/// Pick one of the world's spawn points, and find somewhere near it for a body of `size`.
+/// If there's no open space at all, the body is put above the highest terrain we'd look at.
+pub fn choose(server: &server::T, size: &Vector3<f32>) -> world_position::Bounds {
+  let column = random_point(server);
+  match find(server, &column, size) {
+    Some(bounds) => bounds,
+    None => {
+      warn!("No open space near spawn point {:?}", column);
+      above_column(&column, size)
+    },
+  }
+}
+
+/// Put a body of `size` above one of the world's spawn points, without looking at the terrain at all.
+pub fn above(server: &server::T, size: &Vector3<f32>) -> world_position::Bounds {
+  above_column(&random_point(server), size)
+}
+
+/// A spawn point found ahead of time, for players who die. Looking for one can generate terrain, which is too slow
+/// to do in the middle of a world update, so gaia finds a new one whenever the last one's been used.
+pub struct Prepared {
+  bounds    : Option<world_position::Bounds>,
+  /// Whether `bounds` hasn't been used yet.
+  fresh     : bool,
+  /// Whether gaia has been asked for a new one.
+  requested : bool,
+}
+
+#[allow(missing_docs)]
+pub fn prepared() -> Prepared {
+  Prepared {
+    bounds    : None,
+    fresh     : false,
+    requested : false,
+  }
+}
+
+impl Prepared {
+  /// The latest spawn point found, if any. It's used again until gaia finds a new one.
+  pub fn get(&mut self) -> Option<world_position::Bounds> {
+    self.fresh = false;
+    self.bounds
+  }
+
+  /// Should gaia be asked for a new spawn point? If so, it's assumed it will be.
+  pub fn should_request(&mut self) -> bool {
+    if self.fresh || self.requested {
+      return false
+    }
+    self.requested = true;
+    true
+  }
+
+  /// Gaia found a new spawn point.
+  pub fn set(&mut self, bounds: world_position::Bounds) {
+    self.bounds = Some(bounds);
+    self.fresh = true;
+    self.requested = false;
+  }
+}
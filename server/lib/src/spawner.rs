@@ -0,0 +1,219 @@
+//! Keep a population of mobs around the players, and clear out the ones nobody's near.
+
+use cgmath::{Point3, EuclideanSpace, InnerSpace, Vector3};
+use collision::{Aabb3};
+use rand::Rng;
+use std::f32::consts::PI;
+
+use common::protocol;
+use common::surroundings_loader;
+use common::voxel;
+use common::world_position;
+
+use combat;
+use entity;
+use mob;
+use mob_behavior;
+use server;
+use terrain::biome;
+
+/// The number of mobs we try to keep around each player.
+const MOBS_PER_PLAYER: usize = 3;
+/// Mobs are spawned this far from players. Players only fully load a few blocks around themselves,
+/// and mobs can only walk on fully-loaded terrain, so this can't be very far.
+const MIN_SPAWN_DISTANCE: f32 = 4.0;
+const MAX_SPAWN_DISTANCE: f32 = 7.0;
+/// How far above and below a player we look for somewhere to spawn.
+const SPAWN_HEIGHT_RANGE: i32 = 8;
+/// Mobs further than this from every player are liable to be despawned.
+const DESPAWN_DISTANCE: f32 = 32.0;
+/// How many consecutive world updates a mob has to be far away before it's despawned.
+const DESPAWN_TICKS: u32 = 30 * 10;
+
+/// The conditions under which a kind of mob can spawn.
+pub struct Rule {
+  /// What the mob does once spawned.
+  pub behavior   : mob::Behavior,
+  /// Only spawn while the sun is down.
+  pub night_only : bool,
+  /// The kinds of land this kind of mob can be spawned in.
+  pub biomes     : &'static [biome::Kind],
+  /// The materials this kind of mob can be spawned standing on.
+  pub ground     : &'static [voxel::Material],
+}
+
+/// Each world update, the first rule whose conditions hold is used for any spawns.
+pub const RULES: &'static [Rule] = &[
+  Rule {
+    behavior   : mob_behavior::hostile,
+    night_only : true,
+    biomes     : &[biome::Kind::Hills, biome::Kind::Mountains, biome::Kind::Caves],
+    ground     : &[voxel::Material::Terrain, voxel::Material::Stone, voxel::Material::Marble],
+  },
+  Rule {
+    behavior   : mob_behavior::mob_behavior,
+    night_only : false,
+    biomes     : &[biome::Kind::Hills],
+    ground     : &[voxel::Material::Terrain],
+  },
+];
+
+fn center(bounds: &world_position::Bounds) -> world_position::T {
+  (bounds.min + bounds.max.to_vec()) * 0.5
+}
+
+/// The material of a block, as far as standing on it is concerned.
+fn material_of(voxel: &voxel::T) -> voxel::Material {
+  match *voxel {
+    voxel::Volume(material) => material,
+    voxel::Surface(ref surface) => surface.corner,
+  }
+}
+
+/// Spawn and despawn mobs as necessary.
+pub fn update(server: &server::T) {
+  let players: Vec<world_position::T> =
+    server.players.lock().unwrap().values().map(|player| player.position).collect();
+
+  despawn_unobserved(server, &players);
+
+  let mobs: Vec<world_position::T> =
+    server.mobs.lock().unwrap().values().map(|mob| mob.position).collect();
+  let is_night = server.sun.lock().unwrap().is_night();
+
+  for player in &players {
+    let nearby =
+      mobs.iter()
+      .filter(|mob| world_position::offset(player, mob).magnitude() <= MAX_SPAWN_DISTANCE)
+      .count();
+    if nearby >= MOBS_PER_PLAYER {
+      continue
+    }
+
+    let column = {
+      let mut rng = server.rng.lock().unwrap();
+      let angle = rng.gen_range(0.0, 2.0 * PI);
+      let distance = rng.gen_range(MIN_SPAWN_DISTANCE, MAX_SPAWN_DISTANCE);
+      *player + world_position::vector_of_f32(&(Vector3::new(angle.cos(), 0.0, angle.sin()) * distance))
+    };
+
+    let feet = match find_ground(server, &column) {
+      None => continue,
+      Some(feet) => feet,
+    };
+
+    let ground = {
+      let below = voxel::bounds::new(feet.x, feet.y - 1, feet.z, 0);
+      server.regions.ensure_loaded_voxel(&server.terrain_loader.terrain, &below);
+      material_of(&server.terrain_loader.terrain.load(&below))
+    };
+
+    let biome = server.terrain_loader.terrain.biome(feet.x as f32 + 0.5, feet.z as f32 + 0.5);
+
+    let rule =
+      RULES.iter()
+      .filter(|rule| is_night || !rule.night_only)
+      .filter(|rule| rule.biomes.contains(&biome))
+      .find(|rule| rule.ground.contains(&ground));
+    match rule {
+      None => {},
+      Some(rule) => {
+        let low_corner = Point3::new(feet.x as f64, feet.y as f64, feet.z as f64);
+        let id = add_mob(server, low_corner, rule.behavior);
+        debug!("Spawned {:?} at {:?}", id, low_corner);
+      },
+    }
+  }
+}
+
+/// Find the highest block in the column around `position` that a mob could stand in, so mobs spawn on the surface
+/// rather than in caves below it.
+fn find_ground(server: &server::T, position: &world_position::T) -> Option<Point3<i32>> {
+  let x = position.x.floor() as i32;
+  let y = position.y.floor() as i32;
+  let z = position.z.floor() as i32;
+
+  let loaded = server.terrain_loader.loaded.read().unwrap();
+  let mut navigation = server.terrain_loader.navigation.lock().unwrap();
+  (-SPAWN_HEIGHT_RANGE .. SPAWN_HEIGHT_RANGE + 1)
+    .rev()
+    .map(|dy| Point3::new(x, y + dy, z))
+    .find(|p| navigation.is_standable(&loaded, p))
+}
+
+fn despawn_unobserved(server: &server::T, players: &[world_position::T]) {
+  let mut despawned = Vec::new();
+  {
+    let mut mobs = server.mobs.lock().unwrap();
+    for (&id, mob) in mobs.iter_mut() {
+      let observed =
+        players.iter().any(|player| world_position::offset(player, &mob.position).magnitude() <= DESPAWN_DISTANCE);
+      if observed {
+        mob.ticks_unobserved = 0;
+      } else {
+        mob.ticks_unobserved += 1;
+      }
+
+      if mob.ticks_unobserved >= DESPAWN_TICKS {
+        despawned.push(id);
+      }
+    }
+
+    for id in &despawned {
+      let mob = mobs.remove(id).unwrap();
+      debug!("Despawning {:?}", mob.entity_id);
+      release(server, mob);
+    }
+  }
+
+  let mut clients = server.clients.lock().unwrap();
+  for id in despawned {
+    for (_, client) in clients.iter_mut() {
+      client.send(protocol::ServerToClient::RemoveMob(id));
+    }
+  }
+}
+
+/// Free everything a mob holds in the world.
+pub fn release(server: &server::T, mob: mob::Mob) {
+  server.physics.lock().unwrap().remove_misc(mob.physics_id);
+
+  for position in mob.surroundings_loader.loaded_positions() {
+    server.terrain_loader.unload(
+      &server.physics,
+      &voxel::bounds::new(position.x, position.y, position.z, 0),
+      mob.owner_id,
+    );
+  }
+}
+
+/// Create a mob with its lower corner at `low_corner`.
+pub fn add_mob(
+  server: &server::T,
+  low_corner: world_position::T,
+  behavior: mob::Behavior,
+) -> entity::id::Mob {
+  let bounds = Aabb3::new(low_corner, low_corner + (&Vector3::new(1.0, 2.0, 1.0 as f64)));
+  let entity_id = server.mob_allocator.lock().unwrap().allocate();
+  let physics_id = server.misc_allocator.lock().unwrap().allocate();
+
+  let mob =
+    mob::Mob {
+      position            : center(&bounds),
+      speed               : Vector3::new(0.0, 0.0, 0.0),
+      behavior            : behavior,
+      path                : None,
+      ticks_unobserved    : 0,
+      health              : combat::Health::new(combat::MOB_HEALTH),
+      attack_cooldown     : 0,
+      stagger_ticks       : 0,
+      entity_id           : entity_id,
+      physics_id          : physics_id,
+      owner_id            : server.owner_allocator.lock().unwrap().allocate(),
+      surroundings_loader : surroundings_loader::new(8, Vec::new()),
+    };
+
+  server.physics.lock().unwrap().insert_misc(physics_id, &bounds);
+  server.mobs.lock().unwrap().insert(entity_id, mob);
+  entity_id
+}
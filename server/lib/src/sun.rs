@@ -5,6 +5,8 @@ use std;
 pub struct Sun {
   // The sun as portions of a 65536-degree circle.
   pub position: u16,
+  // While frozen, the sun stays where it is.
+  pub frozen: bool,
   pub timer: IntervalTimer,
   pub print_timer: IntervalTimer,
 }
@@ -13,6 +15,7 @@ impl Sun {
   pub fn new(tick_ns: u64) -> Sun {
     Sun {
       position: 0,
+      frozen: false,
       timer: IntervalTimer::new(tick_ns, time::precise_time_ns()),
       print_timer: IntervalTimer::new(2e9 as u64, time::precise_time_ns()),
     }
@@ -21,16 +24,13 @@ impl Sun {
   pub fn update(&mut self) -> Option<f32> {
     let ticks = self.timer.update(time::precise_time_ns());
 
-    if ticks == 0 {
+    if ticks == 0 || self.frozen {
       return None;
     }
 
     self.position = (std::num::Wrapping(self.position) + std::num::Wrapping(ticks as u16)).0;
 
-    // Fraction completed of a full cycle.
-    let fraction = (self.position as f32) / 65536.0;
-    // Longer day, shorter night.
-    let fraction = fraction * fraction;
+    let fraction = self.fraction();
 
     if self.print_timer.update(time::precise_time_ns()) > 0 {
       debug!("Sun is at {:.1}%.", fraction * 100.0);
@@ -38,4 +38,23 @@ impl Sun {
 
     Some(fraction)
   }
+
+  /// The sun as a [0, 1) portion of its cycle.
+  pub fn fraction(&self) -> f32 {
+    // Fraction completed of a full cycle.
+    let fraction = (self.position as f32) / 65536.0;
+    // Longer day, shorter night.
+    fraction * fraction
+  }
+
+  /// Move the sun to a [0, 1) portion of its cycle.
+  pub fn set_fraction(&mut self, fraction: f32) {
+    // Undo the scaling in `fraction`.
+    self.position = (fraction.max(0.0).sqrt() * 65536.0).min(65535.0) as u16;
+  }
+
+  /// Is the sun below the horizon?
+  pub fn is_night(&self) -> bool {
+    self.fraction() >= 0.5
+  }
 }
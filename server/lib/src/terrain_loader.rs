@@ -1,15 +1,17 @@
-use collision::{Aabb3};
-use std::sync::Mutex;
-use stopwatch;
 use time;
 
 use common::fnv_map;
 use common::id_allocator;
+use common::lock::{Mutex, RwLock};
+use common::trace;
 use common::voxel;
+use common::world_position;
 
 use entity;
 use in_progress_terrain;
+use lock_order;
 use lod;
+use navigation;
 use physics;
 use terrain;
 use update_gaia;
@@ -24,16 +26,20 @@ pub struct T {
   pub terrain             : terrain::T,
   pub in_progress_terrain : Mutex<in_progress_terrain::T>,
   pub lod_map             : Mutex<lod::Map>,
-  pub loaded              : Mutex<fnv_map::T<voxel::bounds::T, Vec<entity::id::Terrain>>>,
+  /// Mostly read, e.g. by every mob looking for a path, and only written when blocks load, unload or change.
+  pub loaded              : RwLock<fnv_map::T<voxel::bounds::T, Vec<entity::id::Terrain>>>,
+  /// Walkable graph derived from `loaded`.
+  pub navigation          : Mutex<navigation::T>,
 }
 
 impl T {
-  pub fn new() -> T {
+  pub fn new(seed: u32) -> T {
     T {
-      terrain             : terrain::T::new(terrain::Seed::new(0)),
-      in_progress_terrain : Mutex::new(in_progress_terrain::T::new()),
-      lod_map             : Mutex::new(lod::Map::new()),
-      loaded              : Mutex::new(fnv_map::new()),
+      terrain             : terrain::T::new(terrain::Seed::new(seed), lock_order::TERRAIN_VOXELS),
+      in_progress_terrain : Mutex::new(lock_order::IN_PROGRESS_TERRAIN, in_progress_terrain::T::new()),
+      lod_map             : Mutex::new(lock_order::LOD_MAP, lod::Map::new()),
+      loaded              : RwLock::new(lock_order::LOADED_TERRAIN, fnv_map::new()),
+      navigation          : Mutex::new(lock_order::NAVIGATION, navigation::new()),
     }
   }
 
@@ -109,6 +115,7 @@ impl T {
     lod_map             : &mut lod::Map,
     in_progress_terrain : &mut in_progress_terrain::T,
     loaded              : &mut fnv_map::T<voxel::bounds::T, Vec<entity::id::Terrain>>,
+    navigation          : &mut navigation::T,
   ) {
     let lod = lod::Full;
     let (_, change) = lod_map.insert(*position, lod, owner);
@@ -126,7 +133,7 @@ impl T {
           in_progress_terrain.remove(physics, position);
         }
         lod::Full => {
-          stopwatch::time("terrain_loader.load.unload", || {
+          trace::time("terrain_loader.load.unload", || {
             let ids = loaded.get(position).unwrap();
             let mut physics = physics.lock().unwrap();
             for id in ids {
@@ -137,7 +144,7 @@ impl T {
       }
     );
 
-    stopwatch::time("terrain_loader.load.physics", || {
+    trace::time("terrain_loader.load.physics", || {
       let mut physics = physics.lock().unwrap();
       let mut ids = Vec::with_capacity(block.bounds.len());
       for &(ref id, ref bounds) in &block.bounds {
@@ -147,6 +154,39 @@ impl T {
       let prev = loaded.insert(*position, ids);
       assert!(prev.is_none());
     });
+
+    navigation.invalidate(position);
+  }
+
+  /// Replace the physics of an already-loaded block, e.g. because a brush changed it.
+  /// `block` is only called if the block is loaded, so terrain ids aren't allocated for blocks nobody has.
+  pub fn update_block<Block>(
+    &self,
+    block    : Block,
+    position : &voxel::bounds::T,
+    physics  : &Mutex<physics::T>,
+  ) where Block: FnOnce() -> LoadedTerrain
+  {
+    let mut loaded = self.loaded.write().unwrap();
+    let ids =
+      match loaded.get_mut(position) {
+        None => return,
+        Some(ids) => ids,
+      };
+
+    let block = block();
+    {
+      let mut physics = physics.lock().unwrap();
+      for id in ids.drain(..) {
+        physics.remove_terrain(id);
+      }
+      for &(ref id, ref bounds) in &block.bounds {
+        physics.insert_terrain(*id, bounds);
+        ids.push(*id);
+      }
+    }
+
+    self.navigation.lock().unwrap().invalidate(position);
   }
 
   pub fn unload(
@@ -167,12 +207,14 @@ impl T {
           self.in_progress_terrain.lock().unwrap().remove(physics, position);
         }
         lod::Full => {
-          stopwatch::time("terrain_loader.unload", || {
-            match self.loaded.lock().unwrap().remove(position) {
+          trace::time("terrain_loader.unload", || {
+            let mut loaded = self.loaded.write().unwrap();
+            match loaded.remove(position) {
               None => {
                 // Unloaded before the load request completed.
               },
               Some(ids) => {
+                self.navigation.lock().unwrap().invalidate(position);
                 let mut physics = physics.lock().unwrap();
                 for id in ids {
                   physics.remove_terrain(id);
@@ -187,5 +229,5 @@ impl T {
 }
 
 pub struct LoadedTerrain {
-  pub bounds: Vec<(entity::id::Terrain, Aabb3<f32>)>,
+  pub bounds: Vec<(entity::id::Terrain, world_position::Bounds)>,
 }
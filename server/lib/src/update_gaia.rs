@@ -1,16 +1,24 @@
 /// Creator of the earth.
 
+use cgmath::{Point3};
 use collision::{Aabb3};
-use stopwatch;
+use std::cmp::{min, max};
 
-use common;
+use common::fnv_map;
 use common::protocol;
+use common::trace;
 use common::voxel;
+use common::world_position;
 
+use brush;
+use clipboard;
+use entity;
+use history;
+use inventory;
 use lod;
+use player;
 use server;
 use terrain_loader;
-use voxel_data;
 
 #[derive(Debug, Clone, Copy)]
 pub enum LoadDestination {
@@ -20,45 +28,340 @@ pub enum LoadDestination {
 
 pub enum Message {
   Load(u64, Vec<voxel::bounds::T>, LoadDestination),
-  Brush(voxel_data::brush::T<Box<voxel_data::mosaic::T<common::voxel::Material> + Send>>),
+  /// Add, subtract or paint with a brush aimed at a voxel, on behalf of a player, who collects and spends the
+  /// materials it changes. Like sculpting brushes, these are built here, so they're charged for with the inventory
+  /// as it is after every edit queued before them. Painting only changes the materials of the terrain it covers.
+  Brush(entity::id::Player, protocol::Brush, voxel::bounds::T),
+  /// Smooth or flatten the terrain around a voxel. These are built here, so they see every edit queued before them.
+  Sculpt(entity::id::Player, protocol::Brush, voxel::bounds::T),
+  /// Revert a player's most recent edit.
+  Undo(entity::id::Player),
+  /// Reapply a player's most recently undone edit.
+  Redo(entity::id::Player),
+  Clipboard(entity::id::Player, clipboard::Action),
+  /// Find somewhere for the next player who dies to respawn.
+  PrepareRespawn,
+  /// Find somewhere for a player joining for the first time, and add them there.
+  SpawnPlayer(Box<player::T>),
+}
+
+impl Message {
+  /// Does this change the terrain (or something else players would want kept), as opposed to just loading it?
+  pub fn is_edit(&self) -> bool {
+    match *self {
+      Message::Load(..) | Message::PrepareRespawn | Message::SpawnPlayer(..) => false,
+      _ => true,
+    }
+  }
 }
 
-// TODO: Consider adding terrain loads to a thread pool instead of having one monolithic separate thread.
 pub fn update_gaia(
   server: &server::T,
   update: Message,
 ) {
-  stopwatch::time("update_gaia", move || {
+  trace::time("update_gaia", move || {
     match update {
       Message::Load(time_requested, voxel_bounds, load_reason) => {
-        stopwatch::time("terrain.load", || {
+        trace::time("terrain.load", || {
           load(server, time_requested, voxel_bounds, load_reason);
         });
       },
-      Message::Brush(mut brush) => {
-        let mut updates = Vec::new();
-        server.terrain_loader.terrain.brush(
-          &mut brush,
-          |block, bounds| {
-            trace!("update bounds {:?}", bounds);
-            updates.push((*bounds, *block));
-          },
-        );
-
-        let mut clients = server.clients.lock().unwrap();
-        for (_, client) in clients.iter_mut() {
-          client.send(
-            protocol::ServerToClient::Voxels {
-              voxels : updates.clone(),
-              reason : protocol::VoxelReason::Updated,
+      Message::Brush(player_id, request, target) => {
+        if let Some(material) = shortfall(server, player_id, &brush::cost(&request)) {
+          debug!("{:?} doesn't have enough {:?} for {:?}", player_id, material, request);
+          return
+        }
+        let mut brush = brush::new(&mut *server.rng.lock().unwrap(), &request, &target);
+        server.regions.ensure_loaded(&server.terrain_loader.terrain, &brush.bounds);
+        let edit =
+          edit(server, player_id, |voxel_changed| {
+            match request.mode {
+              protocol::BrushMode::Paint => server.terrain_loader.terrain.paint(&mut brush, voxel_changed),
+              _ => server.terrain_loader.terrain.brush(&mut brush, voxel_changed),
             }
-          );
+          });
+        edit.map(|edit| with_history(server, player_id, |history| history.push(edit)));
+      },
+      Message::Sculpt(player_id, request, target) => {
+        server.regions.ensure_loaded(&server.terrain_loader.terrain, &brush::reach(&request, &target));
+        let (mut brush, cost) = brush::sculpt(&server.terrain_loader.terrain, &request, &target);
+        if let Some(material) = shortfall(server, player_id, &cost) {
+          debug!("{:?} doesn't have enough {:?} for {:?}", player_id, material, request);
+          return
         }
+        let edit =
+          edit(server, player_id, |voxel_changed| {
+            server.terrain_loader.terrain.brush(&mut brush, voxel_changed);
+          });
+        edit.map(|edit| with_history(server, player_id, |history| history.push(edit)));
+      },
+      Message::Undo(player_id) => {
+        let undone =
+          match with_history(server, player_id, |history| history.pop_undo()) {
+            None | Some(None) => return,
+            Some(Some(undone)) => undone,
+          };
+        match revert(server, player_id, &undone) {
+          None => with_history(server, player_id, |history| history.push_redone(undone)),
+          Some(edit) => with_history(server, player_id, |history| history.push_redo(edit)),
+        };
+      },
+      Message::Redo(player_id) => {
+        let undo =
+          match with_history(server, player_id, |history| history.pop_redo()) {
+            None | Some(None) => return,
+            Some(Some(undo)) => undo,
+          };
+        match revert(server, player_id, &undo) {
+          None => with_history(server, player_id, |history| history.push_redo(undo)),
+          Some(edit) => with_history(server, player_id, |history| history.push_redone(edit)),
+        };
+      },
+      Message::Clipboard(player_id, action) => {
+        update_clipboard(server, player_id, action);
+      },
+      Message::PrepareRespawn => {
+        let bounds = player::spawn_bounds(server);
+        server.respawn.lock().unwrap().set(bounds);
+      },
+      Message::SpawnPlayer(player) => {
+        player::spawn(server, *player);
       },
     };
   })
 }
 
+/// The first material in `cost` a player doesn't have enough of, if any.
+/// This is only an early check; what an edit actually changes is charged for by `edit`.
+fn shortfall(
+  server: &server::T,
+  player_id: entity::id::Player,
+  cost: &[(voxel::Material, u32)],
+) -> Option<voxel::Material> {
+  server.players.lock().unwrap().get(&player_id).and_then(|player| player.inventory.shortfall(cost))
+}
+
+/// Make a change to the terrain on behalf of a player, charge them for it, and propagate it everywhere it needs to
+/// go. `apply` makes the change, and calls its argument for every voxel it changes.
+/// Returns everything that changed, or `None` if the player couldn't afford it, in which case it's undone.
+fn edit<Apply>(
+  server: &server::T,
+  player_id: entity::id::Player,
+  apply: Apply,
+) -> Option<history::Edit> where
+  Apply: FnOnce(&mut FnMut(&voxel::bounds::T, &voxel::T, &voxel::T)),
+{
+  let mut changes = Vec::new();
+  let mut updates = Vec::new();
+  let mut delta: inventory::Delta = fnv_map::new();
+  apply(&mut |bounds, before, after| {
+    trace!("update bounds {:?}", bounds);
+    if bounds.lg_size == 0 {
+      inventory::record_change(&mut delta, before, after);
+    }
+    changes.push((*bounds, *before, *after));
+    updates.push((*bounds, *after));
+  });
+
+  if let Err(material) = update_inventory(server, player_id, &delta) {
+    debug!("{:?} doesn't have enough {:?} for an edit; undoing it", player_id, material);
+    // Loads may have seen the change already, so what's put back goes everywhere an edit would.
+    let undo: Vec<_> = changes.iter().map(|&(bounds, before, after)| (bounds, after, before)).collect();
+    let mut restored = Vec::new();
+    server.terrain_loader.terrain.restore(&undo, |bounds, _, after| restored.push((*bounds, *after)));
+    propagate(server, restored);
+    return None
+  }
+
+  for &(ref bounds, _, _) in &changes {
+    server.regions.mark_dirty(bounds);
+  }
+
+  propagate(server, updates);
+
+  Some(
+    history::Edit {
+      changes: changes,
+    }
+  )
+}
+
+/// Send changed voxels to server-side physics and every client.
+fn propagate(server: &server::T, updates: Vec<(voxel::bounds::T, voxel::T)>) {
+  // Keep server-side physics in line with the new terrain.
+  for &(ref bounds, ref block) in &updates {
+    if bounds.lg_size != 0 {
+      continue
+    }
+    server.terrain_loader.update_block(
+      || loaded_terrain(server, bounds, block),
+      bounds,
+      &server.physics,
+    );
+  }
+
+  let mut clients = server.clients.lock().unwrap();
+  for (_, client) in clients.iter_mut() {
+    client.send(
+      protocol::ServerToClient::Voxels {
+        voxels : updates.clone(),
+        reason : protocol::VoxelReason::Updated,
+      }
+    );
+  }
+}
+
+fn update_clipboard(server: &server::T, player_id: entity::id::Player, action: clipboard::Action) {
+  match action {
+    clipboard::Action::Copy => {
+      let selection =
+        match server.players.lock().unwrap().get(&player_id) {
+          None => return,
+          Some(player) => player.selection,
+        };
+      let (a, b) =
+        match (selection[0], selection[1]) {
+          (Some(a), Some(b)) => (a, b),
+          _ => {
+            debug!("{:?} tried to copy without a selection", player_id);
+            return
+          },
+        };
+      let region =
+        Aabb3::new(
+          Point3::new(min(a.x, b.x), min(a.y, b.y), min(a.z, b.z)),
+          Point3::new(max(a.x, b.x) + 1, max(a.y, b.y) + 1, max(a.z, b.z) + 1),
+        );
+
+      server.regions.ensure_loaded(&server.terrain_loader.terrain, &region);
+      match clipboard::copy(&server.terrain_loader.terrain, &region) {
+        Err(err) => warn!("{:?} couldn't copy: {}", player_id, err),
+        Ok(copied) => {
+          info!("{:?} copied {:?}", player_id, region);
+          server.players.lock().unwrap().get_mut(&player_id).map(|player| player.clipboard = Some(copied));
+        },
+      }
+    },
+    clipboard::Action::Paste(target, transform) => {
+      let copied =
+        match server.players.lock().unwrap().get(&player_id) {
+          None => return,
+          Some(player) => {
+            match player.clipboard {
+              None => {
+                debug!("{:?} tried to paste an empty clipboard", player_id);
+                return
+              },
+              Some(ref copied) => {
+                if let Some(material) = player.inventory.shortfall(&copied.cost()) {
+                  debug!("{:?} doesn't have enough {:?} to paste", player_id, material);
+                  return
+                }
+                copied.clone()
+              },
+            }
+          },
+        };
+
+      let mut brush = clipboard::paste(copied, &target, transform);
+      server.regions.ensure_loaded(&server.terrain_loader.terrain, &brush.bounds);
+      let edit =
+        edit(server, player_id, |voxel_changed| {
+          server.terrain_loader.terrain.brush(&mut brush, voxel_changed);
+        });
+      edit.map(|edit| with_history(server, player_id, |history| history.push(edit)));
+    },
+    clipboard::Action::Save(name) => {
+      let copied =
+        server.players.lock().unwrap().get(&player_id).and_then(|player| player.clipboard.clone());
+      match copied {
+        None => debug!("{:?} tried to save an empty clipboard", player_id),
+        Some(copied) => {
+          match clipboard::save(&copied, &name) {
+            Err(err) => warn!("{}", err),
+            Ok(()) => info!("{:?} saved clipboard {:?}", player_id, name),
+          }
+        },
+      }
+    },
+    clipboard::Action::Load(name) => {
+      match clipboard::load(&name) {
+        Err(err) => warn!("{}", err),
+        Ok(loaded) => {
+          info!("{:?} loaded clipboard {:?}", player_id, name);
+          server.players.lock().unwrap().get_mut(&player_id).map(|player| player.clipboard = Some(loaded));
+        },
+      }
+    },
+  }
+}
+
+fn with_history<F, R>(server: &server::T, player_id: entity::id::Player, f: F) -> Option<R> where
+  F: FnOnce(&mut history::T) -> R,
+{
+  server.players.lock().unwrap().get_mut(&player_id).map(|player| f(&mut player.history))
+}
+
+/// Put back what `undone` changed, on behalf of a player.
+/// Returns the change that was made, or `None` if the player can't afford it.
+fn revert(server: &server::T, player_id: entity::id::Player, undone: &history::Edit) -> Option<history::Edit> {
+  let inverse = undone.inverse();
+
+  // Undoing a dig gives back what was dug; don't let that be spent twice.
+  let mut delta: inventory::Delta = fnv_map::new();
+  for &(ref bounds, ref before, ref after) in &inverse.changes {
+    if bounds.lg_size == 0 {
+      inventory::record_change(&mut delta, before, after);
+    }
+  }
+  {
+    let players = server.players.lock().unwrap();
+    let inventory =
+      match players.get(&player_id) {
+        None => return None,
+        Some(player) => &player.inventory,
+      };
+    for (&material, &amount) in &delta {
+      if amount < 0 && (inventory.count(material) as i64) < -amount {
+        debug!("{:?} can't afford to revert an edit; needs {} {:?}", player_id, -amount, material);
+        return None
+      }
+    }
+  }
+
+  for &(ref bounds, _, _) in &inverse.changes {
+    server.regions.ensure_loaded_voxel(&server.terrain_loader.terrain, bounds);
+  }
+  edit(server, player_id, |voxel_changed| {
+    server.terrain_loader.terrain.restore(&inverse.changes, voxel_changed);
+  })
+}
+
+/// Credit and debit a player for the materials an edit changed, and let them know.
+/// If they can't afford it, nothing changes, and the material they're short of is returned.
+fn update_inventory(
+  server: &server::T,
+  player_id: entity::id::Player,
+  delta: &inventory::Delta,
+) -> Result<(), voxel::Material> {
+  let (client_id, contents) = {
+    let mut players = server.players.lock().unwrap();
+    let player =
+      match players.get_mut(&player_id) {
+        None => return Ok(()),
+        Some(player) => player,
+      };
+    try!(player.inventory.apply(delta));
+    (player.client_id, player.inventory.contents())
+  };
+
+  let mut clients = server.clients.lock().unwrap();
+  clients.get_mut(&client_id).map(|client| {
+    client.send(protocol::ServerToClient::UpdateInventory(contents));
+  });
+  Ok(())
+}
+
 #[inline(never)]
 fn load(
   server: &server::T,
@@ -66,51 +369,76 @@ fn load(
   voxel_bounds: Vec<voxel::bounds::T>,
   load_reason: LoadDestination,
 ) {
-  // TODO: Just lock `terrain` for the check and then the move;
-  // don't lock for the whole time where we're generating the block.
-  let mut lod_map = server.terrain_loader.lod_map.lock().unwrap();
-  let mut in_progress_terrain = server.terrain_loader.in_progress_terrain.lock().unwrap();
   match load_reason {
     LoadDestination::Local(owner) => {
+      // Generate the blocks before taking any of the loader's locks, so other workers aren't kept waiting.
+      for voxel_bounds in &voxel_bounds {
+        server.regions.ensure_loaded_voxel(&server.terrain_loader.terrain, voxel_bounds);
+        server.terrain_loader.terrain.load(voxel_bounds);
+      }
+
+      let mut lod_map = server.terrain_loader.lod_map.lock().unwrap();
+      let mut in_progress_terrain = server.terrain_loader.in_progress_terrain.lock().unwrap();
+      let mut loaded = server.terrain_loader.loaded.write().unwrap();
+      let mut navigation = server.terrain_loader.navigation.lock().unwrap();
       for voxel_bounds in voxel_bounds {
+        // This is just a lookup now. Reading it with `loaded` locked means any edit made since it was generated
+        // is either seen here, or is applied to physics by `update_block` after we're done.
         let block = server.terrain_loader.terrain.load(&voxel_bounds);
-        let bounds =
-          match block {
-            voxel::Volume(voxel::Material::Empty) => Vec::new(),
-            _ => {
-              let (low, high) = voxel_bounds.corners();
-              let id = server.terrain_allocator.lock().unwrap().allocate();
-              vec!((id, Aabb3::new(low, high)))
-            },
-          };
+        let block = loaded_terrain(server, &voxel_bounds, &block);
         // TODO: Check that this block isn't stale, i.e. should still be loaded.
         // Maybe this should just ping the original thread, same as we ping the client.
         terrain_loader::T::insert_block(
-          &terrain_loader::LoadedTerrain { bounds: bounds },
+          &block,
           &voxel_bounds,
           owner,
           &server.physics,
           &mut *lod_map,
           &mut *in_progress_terrain,
-          &mut *server.terrain_loader.loaded.lock().unwrap(),
+          &mut *loaded,
+          &mut *navigation,
         );
       }
     },
     LoadDestination::Client(id) => {
       let mut voxels = Vec::new();
       for voxel_bounds in voxel_bounds {
+        server.regions.ensure_loaded_voxel(&server.terrain_loader.terrain, &voxel_bounds);
         let voxel = server.terrain_loader.terrain.load(&voxel_bounds);
         voxels.push((voxel_bounds, voxel));
       }
 
       let mut clients = server.clients.lock().unwrap();
-      let client = clients.get_mut(&id).unwrap();
-      client.send(
-        protocol::ServerToClient::Voxels {
-          voxels : voxels,
-          reason : protocol::VoxelReason::Requested { at: time_requested },
-        }
-      );
+      match clients.get_mut(&id) {
+        // The client was kicked while this was queued.
+        None => {},
+        Some(client) => {
+          client.send(
+            protocol::ServerToClient::Voxels {
+              voxels : voxels,
+              reason : protocol::VoxelReason::Requested { at: time_requested },
+            }
+          );
+        },
+      }
     },
   }
 }
+
+/// The physics bounds of a block of terrain.
+fn loaded_terrain(
+  server: &server::T,
+  voxel_bounds: &voxel::bounds::T,
+  block: &voxel::T,
+) -> terrain_loader::LoadedTerrain {
+  let bounds =
+    match *block {
+      voxel::Volume(voxel::Material::Empty) => Vec::new(),
+      _ => {
+        let (low, high) = voxel_bounds.corners();
+        let id = server.terrain_allocator.lock().unwrap().allocate();
+        vec!((id, world_position::bounds_of_f32(&Aabb3::new(low, high))))
+      },
+    };
+  terrain_loader::LoadedTerrain { bounds: bounds }
+}
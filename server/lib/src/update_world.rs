@@ -1,15 +1,17 @@
 use cgmath::{Point3, Vector3};
 use std::ops::Neg;
-use stopwatch;
 
 use common::protocol;
 use common::surroundings_loader::LoadType;
+use common::trace;
 use common::voxel;
+use common::world_position;
 
 use lod;
 use mob;
 use player;
 use server;
+use spawner;
 use update_gaia;
 
 // TODO: Consider removing the IntervalTimer.
@@ -20,8 +22,12 @@ pub fn update_world<RequestBlock>(
 ) where
   RequestBlock: FnMut(update_gaia::Message),
 {
-  stopwatch::time("update_world", || {
-    stopwatch::time("update_world.player", || {
+  trace::time("update_world", || {
+    if server.respawn.lock().unwrap().should_request() {
+      request_block(update_gaia::Message::PrepareRespawn);
+    }
+
+    trace::time("update_world.player", || {
       let mut updates = Vec::new();
 
       for (_, player) in server.players.lock().unwrap().iter_mut() {
@@ -49,7 +55,7 @@ pub fn update_world<RequestBlock>(
       }
     });
 
-    stopwatch::time("update_world.mobs", || {
+    trace::time("update_world.mobs", || {
       for (_, mob) in server.mobs.lock().unwrap().iter_mut() {
         let position =
           Point3::new(
@@ -69,7 +75,13 @@ pub fn update_world<RequestBlock>(
           )
         }
 
-        {
+        if mob.attack_cooldown > 0 {
+          mob.attack_cooldown -= 1;
+        }
+
+        if mob.stagger_ticks > 0 {
+          mob.stagger_ticks -= 1;
+        } else {
           let behavior = mob.behavior;
           (behavior)(server, mob);
         }
@@ -87,9 +99,17 @@ pub fn update_world<RequestBlock>(
         if delta_p.z != 0.0 {
           translate_mob(server, mob, &Vector3::new(0.0, 0.0, delta_p.z));
         }
+
+        // friction, so knockback doesn't last forever.
+        mob.speed.x *= 0.7;
+        mob.speed.z *= 0.7;
       }
     });
 
+    trace::time("update_world.spawner", || {
+      spawner::update(server);
+    });
+
     server.sun.lock().unwrap().update().map(|fraction| {
       for (_, client) in server.clients.lock().unwrap().iter_mut() {
         client.send(protocol::ServerToClient::UpdateSun(fraction));
@@ -114,7 +134,7 @@ fn translate_mob(
     }
   }
 
-  mob.position += *delta_p;
+  mob.position += world_position::vector_of_f32(delta_p);
 
   for (_, client) in server.clients.lock().unwrap().iter_mut() {
     client.send(
@@ -1,10 +1,13 @@
 //! Cave biome. This is very experimental and really needs occlusion culling to support any render distance at all.
 
-use cgmath::{Point3, Vector3, InnerSpace};
+use cgmath::{Point3};
 use noise::{Seed, perlin3};
 
 use common::voxel;
 
+use biome;
+use generator;
+
 #[allow(missing_docs)]
 pub struct T {
   pub seed: Seed,
@@ -17,8 +20,8 @@ pub fn new(seed: Seed) -> T {
   }
 }
 
-impl voxel::field::T for T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+impl generator::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     let freq = |f: f64| {
       perlin3(&self.seed, &[(p.x as f64) * f, (p.y as f64) * f, (p.z as f64) * f])
     };
@@ -29,36 +32,13 @@ impl voxel::field::T for T {
     d as f32
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
-    // Use density differential in each dimension as an approximation of the normal.
-
-    let delta = 0.01;
-
-    macro_rules! differential(($d:ident) => {{
-      let high: f32 = {
-        let mut p = *p;
-        p.$d += delta;
-        voxel::field::T::density(self, &p)
-      };
-      let low: f32 = {
-        let mut p = *p;
-        p.$d -= delta;
-        voxel::field::T::density(self, &p)
-      };
-      high - low
-    }});
-
-    let v = Vector3::new(differential!(x), differential!(y), differential!(z));
-    // Negate because we're leaving the volume when density is decreasing.
-    let v = -v;
-    v.normalize()
+  fn biome(&self, _: f32, _: f32) -> biome::Kind {
+    biome::Kind::Caves
   }
-}
 
-impl voxel::mosaic::T<voxel::Material> for T {
-  fn material(&mut self, p: &Point3<f32>) -> Option<voxel::Material> {
+  fn material(&self, p: &Point3<f32>) -> Option<voxel::Material> {
     Some(
-      if voxel::field::T::density(self, p) >= 0.0 {
+      if generator::T::density(self, p) >= 0.0 {
         voxel::Material::Stone
       } else {
         voxel::Material::Empty
@@ -1,10 +1,13 @@
 //! Grass, hilly biome
 
-use cgmath::{Point3, Vector3, InnerSpace};
+use cgmath::{Point3};
 use noise::{Seed, Brownian2, Brownian3, perlin2, perlin3};
 
 use common::voxel;
 
+use biome;
+use generator;
+
 #[allow(missing_docs)]
 pub struct T {
   height: Brownian2<f64, fn (&Seed, &[f64; 2]) -> f64>,
@@ -41,13 +44,16 @@ pub fn new(seed: Seed) -> T {
 }
 
 impl T {
+  /// The heights of the hills and the mountains in the column at `x`, `z`.
+  fn heights(&self, x: f32, z: f32) -> (f32, f32) {
+    let height = self.height.apply(&self.seed, &[x as f64, z as f64]);
+    let mountain_height = 16.0 * self.mountains.apply(&self.seed, &[x as f64 - 32.0, z as f64 - 10.0]) - 32.0;
+    (height as f32, mountain_height as f32)
+  }
+
   fn mat_density(&self, p: &Point3<f32>) -> (f32, voxel::Material) {
-    let height = self.height.apply(&self.seed, &[p.x as f64, p.z as f64]);
-    let height = height as f32;
+    let (height, mountain_height) = self.heights(p.x, p.z);
     let heightmap_density = height - p.y;
-
-    let mountain_height = 16.0 * self.mountains.apply(&self.seed, &[p.x as f64 - 32.0, p.z as f64 - 10.0]) - 32.0;
-    let mountain_height = mountain_height as f32;
     let mountain_heightmap_density = mountain_height - p.y;
 
     let feature_density = self.features.apply(&self.seed, &[p.x as f64, p.y as f64, p.z as f64]) * 8.0;
@@ -62,47 +68,35 @@ impl T {
   }
 }
 
-impl voxel::field::T for T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+impl generator::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     let (d, _) = self.mat_density(p);
     d
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
-    // Use density differential in each dimension as an approximation of the normal.
-
-    let delta = 0.01;
-
-    macro_rules! differential(($d:ident) => {{
-      let high: f32 = {
-        let mut p = *p;
-        p.$d += delta;
-        voxel::field::T::density(self, &p)
-      };
-      let low: f32 = {
-        let mut p = *p;
-        p.$d -= delta;
-        voxel::field::T::density(self, &p)
-      };
-      high - low
-    }});
+  /// Wherever the mountains rise above the hills.
+  fn biome(&self, x: f32, z: f32) -> biome::Kind {
+    let (height, mountain_height) = self.heights(x, z);
+    if mountain_height > height {
+      biome::Kind::Mountains
+    } else {
+      biome::Kind::Hills
+    }
+  }
 
-    let v = Vector3::new(differential!(x), differential!(y), differential!(z));
-    // Negate because we're leaving the volume when density is decreasing.
-    let v = -v;
-    v.normalize()
+  fn material(&self, p: &Point3<f32>) -> Option<voxel::Material> {
+    let (_, mat) = generator::T::density_and_material(self, p);
+    mat
   }
-}
 
-impl voxel::mosaic::T<voxel::Material> for T {
-  fn material(&mut self, p: &Point3<f32>) -> Option<voxel::Material> {
+  fn density_and_material(&self, p: &Point3<f32>) -> (f32, Option<voxel::Material>) {
     let (d, mat) = self.mat_density(p);
-    Some(
+    let mat =
       if d >= 0.0 {
         mat
       } else {
         voxel::Material::Empty
-      }
-    )
+      };
+    (d, Some(mat))
   }
 }
@@ -1,10 +1,13 @@
 //! Grass, hilly biome
 
-use cgmath::{Point3, Vector3, InnerSpace};
+use cgmath::{Point3};
 use noise::{Seed, Brownian2, Brownian3, perlin2, perlin3};
 
 use common::voxel;
 
+use biome;
+use generator;
+
 #[allow(missing_docs)]
 pub struct T {
   height: Brownian2<f64, fn (&Seed, &[f64; 2]) -> f64>,
@@ -33,8 +36,8 @@ pub fn new(seed: Seed) -> T {
   }
 }
 
-impl voxel::field::T for T {
-  fn density(&mut self, p: &Point3<f32>) -> f32 {
+impl generator::T for T {
+  fn density(&self, p: &Point3<f32>) -> f32 {
     let height = self.height.apply(&self.seed, &[p.x as f64, p.z as f64]);
     let height = height as f32;
     let heightmap_density = height - p.y;
@@ -45,36 +48,13 @@ impl voxel::field::T for T {
     heightmap_density + feature_density
   }
 
-  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
-    // Use density differential in each dimension as an approximation of the normal.
-
-    let delta = 0.01;
-
-    macro_rules! differential(($d:ident) => {{
-      let high: f32 = {
-        let mut p = *p;
-        p.$d += delta;
-        voxel::field::T::density(self, &p)
-      };
-      let low: f32 = {
-        let mut p = *p;
-        p.$d -= delta;
-        voxel::field::T::density(self, &p)
-      };
-      high - low
-    }});
-
-    let v = Vector3::new(differential!(x), differential!(y), differential!(z));
-    // Negate because we're leaving the volume when density is decreasing.
-    let v = -v;
-    v.normalize()
+  fn biome(&self, _: f32, _: f32) -> biome::Kind {
+    biome::Kind::Hills
   }
-}
 
-impl voxel::mosaic::T<voxel::Material> for T {
-  fn material(&mut self, p: &Point3<f32>) -> Option<voxel::Material> {
+  fn material(&self, p: &Point3<f32>) -> Option<voxel::Material> {
     Some(
-      if voxel::field::T::density(self, p) >= 0.0 {
+      if generator::T::density(self, p) >= 0.0 {
         voxel::Material::Terrain
       } else {
         voxel::Material::Empty
@@ -4,3 +4,12 @@ pub mod caves;
 pub mod demo;
 pub mod hills;
 pub mod mountains;
+
+/// The kinds of land a generator lays out, for whatever depends on where it is, e.g. which mobs spawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum Kind {
+  Hills,
+  Mountains,
+  Caves,
+}
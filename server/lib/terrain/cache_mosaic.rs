@@ -1,10 +1,22 @@
+//! Remember recent samples of a generator. Each thread has its own caches, so threads never wait on each other.
+//! Terrain was generated through this before `lattice`; it's only kept as the baseline for `lattice`'s benchmarks.
+
 use cgmath;
 use fnv;
 use lru_cache;
 use std;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 
+use common::fnv_map;
 use common::voxel;
 
+use biome;
+use generator;
+
+/// The number of samples of each kind each thread remembers for each generator.
+const CACHE_SIZE: usize = 1 << 10;
+
 #[derive(Clone, PartialEq)]
 pub struct Key(cgmath::Point3<f32>);
 
@@ -21,72 +33,91 @@ impl std::hash::Hash for Key {
 
 pub type Cache<T> = lru_cache::LruCache<Key, T, std::hash::BuildHasherDefault<fnv::FnvHasher>>;
 
-pub struct T<Material> {
-  pub mosaic          : Box<voxel::mosaic::T<Material> + Send>,
-  pub density         : Cache<f32>,
-  pub normal          : Cache<cgmath::Vector3<f32>>,
-  pub mosaic_density  : Cache<f32>,
-  pub mosaic_material : Cache<Option<Material>>,
+/// One thread's caches for one generator.
+struct Caches {
+  density  : Cache<f32>,
+  normal   : Cache<cgmath::Vector3<f32>>,
+  material : Cache<Option<voxel::Material>>,
+}
+
+fn new_caches() -> Caches {
+  Caches {
+    density  : lru_cache::LruCache::with_hasher(CACHE_SIZE, Default::default()),
+    normal   : lru_cache::LruCache::with_hasher(CACHE_SIZE, Default::default()),
+    material : lru_cache::LruCache::with_hasher(CACHE_SIZE, Default::default()),
+  }
 }
 
-pub fn new<Material>(mosaic: Box<voxel::mosaic::T<Material> + Send>) -> T<Material> {
+thread_local!(
+  /// This thread's caches, by the id of the `T` they belong to.
+  static CACHES: RefCell<fnv_map::T<usize, Caches>> = RefCell::new(fnv_map::new())
+);
+
+static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+pub struct T {
+  /// Tells this generator's caches apart from other generators'.
+  id        : usize,
+  pub mosaic : Box<generator::T>,
+}
+
+pub fn new(mosaic: Box<generator::T>) -> T {
   T {
-    mosaic          : mosaic,
-    density         : lru_cache::LruCache::with_hasher(1 << 10, Default::default()),
-    normal          : lru_cache::LruCache::with_hasher(1 << 10, Default::default()),
-    mosaic_density  : lru_cache::LruCache::with_hasher(1 << 10, Default::default()),
-    mosaic_material : lru_cache::LruCache::with_hasher(1 << 10, Default::default()),
+    id     : NEXT_ID.fetch_add(1, Ordering::Relaxed),
+    mosaic : mosaic,
   }
 }
 
-fn get_or_init<T: Clone, F>(cache: &mut Cache<T>, k: Key, f: F) -> T where
-  F: FnOnce() -> T
-{
-  if let Some(x) = cache.get_mut(&k) {
-    return x.clone()
+impl Drop for T {
+  fn drop(&mut self) {
+    // Other threads' caches for this generator just age out of use.
+    let id = self.id;
+    CACHES.with(|caches| { caches.borrow_mut().remove(&id); });
   }
+}
 
-  let x = f();
-  cache.insert(k, x.clone());
-  x
+impl T {
+  /// Look `p` up in one of this thread's caches, and fill it in with `f` if it's not there.
+  /// The generator is only run with the caches unborrowed.
+  fn get_or_init<V: Clone, Select, F>(&self, select: Select, p: &cgmath::Point3<f32>, f: F) -> V where
+    Select: Fn(&mut Caches) -> &mut Cache<V>,
+    F: FnOnce() -> V,
+  {
+    let id = self.id;
+    let cached =
+      CACHES.with(|caches| {
+        let mut caches = caches.borrow_mut();
+        let caches = caches.entry(id).or_insert_with(new_caches);
+        select(caches).get_mut(&Key(*p)).map(|x| x.clone())
+      });
+    if let Some(x) = cached {
+      return x
+    }
+
+    let x = f();
+    CACHES.with(|caches| {
+      let mut caches = caches.borrow_mut();
+      let caches = caches.entry(id).or_insert_with(new_caches);
+      select(caches).insert(Key(*p), x.clone());
+    });
+    x
+  }
 }
 
-impl<Material> voxel::field::T for T<Material> {
-  fn density(&mut self, p: &cgmath::Point3<f32>) -> f32 {
-    let mosaic = &mut self.mosaic;
-    get_or_init(
-      &mut self.density,
-      Key(*p),
-      || voxel::field::T::density(mosaic, p),
-    )
+impl generator::T for T {
+  fn density(&self, p: &cgmath::Point3<f32>) -> f32 {
+    self.get_or_init(|caches| &mut caches.density, p, || generator::T::density(&*self.mosaic, p))
   }
 
-  fn normal(&mut self, p: &cgmath::Point3<f32>) -> cgmath::Vector3<f32> {
-    let mosaic = &mut self.mosaic;
-    get_or_init(
-      &mut self.normal,
-      Key(*p),
-      || voxel::field::T::normal(mosaic, p),
-    )
+  fn normal(&self, p: &cgmath::Point3<f32>) -> cgmath::Vector3<f32> {
+    self.get_or_init(|caches| &mut caches.normal, p, || generator::T::normal(&*self.mosaic, p))
   }
-}
 
-impl<Material> voxel::mosaic::T<Material> for T<Material> where Material: Clone {
-  fn density(&mut self, p: &cgmath::Point3<f32>) -> f32 {
-    let mosaic = &mut self.mosaic;
-    get_or_init(
-      &mut self.mosaic_density,
-      Key(*p),
-      || voxel::mosaic::T::density(mosaic, p),
-    )
+  fn material(&self, p: &cgmath::Point3<f32>) -> Option<voxel::Material> {
+    self.get_or_init(|caches| &mut caches.material, p, || generator::T::material(&*self.mosaic, p))
   }
 
-  fn material(&mut self, p: &cgmath::Point3<f32>) -> Option<Material> {
-    let mosaic = &mut self.mosaic;
-    get_or_init(
-      &mut self.mosaic_material,
-      Key(*p),
-      || voxel::mosaic::T::material(mosaic, p),
-    )
+  fn biome(&self, x: f32, z: f32) -> biome::Kind {
+    generator::T::biome(&*self.mosaic, x, z)
   }
 }
@@ -0,0 +1,71 @@
+//! Terrain generators: density fields with materials, sampled through `&self` so any number of threads
+//! can generate terrain from the same one at once.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+
+use common::voxel;
+
+use biome;
+
+/// A generator decides what's where in untouched terrain.
+pub trait T: Send + Sync {
+  /// Positive inside the terrain, negative outside it.
+  fn density(&self, p: &Point3<f32>) -> f32;
+
+  /// The outward surface normal at `p`.
+  fn normal(&self, p: &Point3<f32>) -> Vector3<f32> {
+    // Use density differential in each dimension as an approximation of the normal.
+
+    let delta = 0.01;
+
+    macro_rules! differential(($d:ident) => {{
+      let high: f32 = {
+        let mut p = *p;
+        p.$d += delta;
+        self.density(&p)
+      };
+      let low: f32 = {
+        let mut p = *p;
+        p.$d -= delta;
+        self.density(&p)
+      };
+      high - low
+    }});
+
+    let v = Vector3::new(differential!(x), differential!(y), differential!(z));
+    // Negate because we're leaving the volume when density is decreasing.
+    let v = -v;
+    v.normalize()
+  }
+
+  /// The material at `p`.
+  fn material(&self, p: &Point3<f32>) -> Option<voxel::Material>;
+
+  /// The kind of land in the column at `x`, `z`.
+  fn biome(&self, x: f32, z: f32) -> biome::Kind;
+
+  /// The density and material at `p`. Override this when they're cheaper to compute together.
+  fn density_and_material(&self, p: &Point3<f32>) -> (f32, Option<voxel::Material>) {
+    (self.density(p), self.material(p))
+  }
+}
+
+/// Lets a generator be used where `voxel_data` wants a mosaic, e.g. by `voxel::of_field`.
+/// Each thread can make its own, since they only borrow the generator.
+pub struct Sampler<'a, Generator: 'a + ?Sized>(pub &'a Generator);
+
+impl<'a, Generator: ?Sized + T> voxel::field::T for Sampler<'a, Generator> {
+  fn density(&mut self, p: &Point3<f32>) -> f32 {
+    T::density(self.0, p)
+  }
+
+  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+    T::normal(self.0, p)
+  }
+}
+
+impl<'a, Generator: ?Sized + T> voxel::mosaic::T<voxel::Material> for Sampler<'a, Generator> {
+  fn material(&mut self, p: &Point3<f32>) -> Option<voxel::Material> {
+    T::material(self.0, p)
+  }
+}
@@ -0,0 +1,349 @@
+//! Sample a generator on a lattice a tile at a time, so neighbouring voxels share the samples at their corners.
+//! Densities and materials are computed once for every lattice point in a tile, plus a point of padding on each side,
+//! and normals are derived from differences between lattice points.
+//! Each thread keeps its own recently-used tiles, so threads never wait on each other.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+use fnv;
+use lru_cache;
+use std;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use common::voxel;
+
+use generator;
+
+/// The number of lattice points along each side of a tile, not counting padding.
+const TILE_WIDTH: i32 = 8;
+/// The number of lattice points along each side of a tile, counting the padding on either side.
+const PADDED_WIDTH: i32 = TILE_WIDTH + 2;
+/// The number of tiles each thread remembers.
+const TILES_PER_THREAD: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct TileKey {
+  /// Which `T` this tile belongs to.
+  lattice    : usize,
+  /// Lattice points are `2^lg_spacing` apart.
+  lg_spacing : i16,
+  /// The tile's position, in units of tiles.
+  position   : Point3<i32>,
+}
+
+/// Samples of every lattice point in a tile and its padding.
+struct Tile {
+  densities : Vec<f32>,
+  materials : Vec<Option<voxel::Material>>,
+}
+
+impl Tile {
+  /// Index a point relative to the tile's first unpadded point; each coordinate is in [-1, TILE_WIDTH].
+  fn index(p: &Point3<i32>) -> usize {
+    (((p.x + 1) * PADDED_WIDTH + (p.y + 1)) * PADDED_WIDTH + (p.z + 1)) as usize
+  }
+}
+
+type Tiles = lru_cache::LruCache<TileKey, Rc<Tile>, std::hash::BuildHasherDefault<fnv::FnvHasher>>;
+
+thread_local!(
+  static TILES: RefCell<Tiles> = RefCell::new(lru_cache::LruCache::with_hasher(TILES_PER_THREAD, Default::default()))
+);
+
+static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// A generator, and the id its tiles are cached under.
+/// Ids are never reused, so tiles of a dropped `T` just age out of each thread's cache.
+pub struct T {
+  id            : usize,
+  pub generator : Box<generator::T>,
+}
+
+#[allow(missing_docs)]
+pub fn new(generator: Box<generator::T>) -> T {
+  T {
+    id        : NEXT_ID.fetch_add(1, Ordering::Relaxed),
+    generator : generator,
+  }
+}
+
+fn tile_of(x: i32) -> i32 {
+  if x >= 0 { x / TILE_WIDTH } else { (x + 1) / TILE_WIDTH - 1 }
+}
+
+/// Samples `T`'s lattice with points `2^lg_spacing` apart. These are cheap, so make one wherever one's needed;
+/// they only hold on to the most recently used tile.
+pub struct Sampler<'a> {
+  lattice    : &'a T,
+  lg_spacing : i16,
+  spacing    : f32,
+  last       : Option<(TileKey, Rc<Tile>)>,
+}
+
+#[allow(missing_docs)]
+pub fn sampler(lattice: &T, lg_spacing: i16) -> Sampler {
+  Sampler {
+    lattice    : lattice,
+    lg_spacing : lg_spacing,
+    spacing    : (lg_spacing as f32).exp2(),
+    last       : None,
+  }
+}
+
+impl<'a> Sampler<'a> {
+  /// The lattice point at `p`, if there is one.
+  fn lattice_point(&self, p: &Point3<f32>) -> Option<Point3<i32>> {
+    let on_lattice = |x: f32| {
+      let x = x / self.spacing;
+      if x.round() == x && x.abs() < (1 << 30) as f32 { Some(x as i32) } else { None }
+    };
+    match (on_lattice(p.x), on_lattice(p.y), on_lattice(p.z)) {
+      (Some(x), Some(y), Some(z)) => Some(Point3::new(x, y, z)),
+      _ => None,
+    }
+  }
+
+  /// The lattice point below and behind `p`, and how far `p` is past it in each dimension, in [0, 1).
+  fn lattice_cell(&self, p: &Point3<f32>) -> (Point3<i32>, Vector3<f32>) {
+    let p = Point3::new(p.x / self.spacing, p.y / self.spacing, p.z / self.spacing);
+    let low = Point3::new(p.x.floor(), p.y.floor(), p.z.floor());
+    (Point3::new(low.x as i32, low.y as i32, low.z as i32), p - low)
+  }
+
+  fn generate(&self, key: &TileKey) -> Tile {
+    let base = key.position;
+    let len = (PADDED_WIDTH * PADDED_WIDTH * PADDED_WIDTH) as usize;
+    let mut densities = Vec::with_capacity(len);
+    let mut materials = Vec::with_capacity(len);
+    for x in -1 .. TILE_WIDTH + 1 {
+    for y in -1 .. TILE_WIDTH + 1 {
+    for z in -1 .. TILE_WIDTH + 1 {
+      let p =
+        Point3::new(
+          (base.x * TILE_WIDTH + x) as f32 * self.spacing,
+          (base.y * TILE_WIDTH + y) as f32 * self.spacing,
+          (base.z * TILE_WIDTH + z) as f32 * self.spacing,
+        );
+      let (density, material) = generator::T::density_and_material(&*self.lattice.generator, &p);
+      densities.push(density);
+      materials.push(material);
+    }}}
+
+    Tile {
+      densities : densities,
+      materials : materials,
+    }
+  }
+
+  fn tile(&mut self, key: &TileKey) -> Rc<Tile> {
+    if let Some((ref last_key, ref tile)) = self.last {
+      if last_key == key {
+        return tile.clone()
+      }
+    }
+
+    let cached = TILES.with(|tiles| tiles.borrow_mut().get_mut(key).map(|tile| tile.clone()));
+    let tile =
+      match cached {
+        Some(tile) => tile,
+        None => {
+          let tile = Rc::new(self.generate(key));
+          TILES.with(|tiles| { tiles.borrow_mut().insert(*key, tile.clone()); });
+          tile
+        },
+      };
+    self.last = Some((*key, tile.clone()));
+    tile
+  }
+
+  /// The tile that `p` is an unpadded point of, and `p` relative to that tile.
+  fn locate(&self, p: &Point3<i32>) -> (TileKey, Point3<i32>) {
+    let position = Point3::new(tile_of(p.x), tile_of(p.y), tile_of(p.z));
+    let key =
+      TileKey {
+        lattice    : self.lattice.id,
+        lg_spacing : self.lg_spacing,
+        position   : position,
+      };
+    let local =
+      Point3::new(
+        p.x - position.x * TILE_WIDTH,
+        p.y - position.y * TILE_WIDTH,
+        p.z - position.z * TILE_WIDTH,
+      );
+    (key, local)
+  }
+
+  fn density_at(&mut self, p: &Point3<i32>) -> f32 {
+    let (key, local) = self.locate(p);
+    self.tile(&key).densities[Tile::index(&local)]
+  }
+
+  fn material_at(&mut self, p: &Point3<i32>) -> Option<voxel::Material> {
+    let (key, local) = self.locate(p);
+    self.tile(&key).materials[Tile::index(&local)]
+  }
+
+  /// The normal at a lattice point, from the differences between its neighbours' densities.
+  /// The neighbours are all in the same padded tile.
+  fn normal_at(&mut self, p: &Point3<i32>) -> Vector3<f32> {
+    let (key, local) = self.locate(p);
+    let tile = self.tile(&key);
+    let density = |dx, dy, dz| tile.densities[Tile::index(&Point3::new(local.x + dx, local.y + dy, local.z + dz))];
+    let v =
+      Vector3::new(
+        density(1, 0, 0) - density(-1, 0, 0),
+        density(0, 1, 0) - density(0, -1, 0),
+        density(0, 0, 1) - density(0, 0, -1),
+      );
+    // Negate because we're leaving the volume when density is decreasing.
+    -v
+  }
+}
+
+impl<'a> voxel::field::T for Sampler<'a> {
+  fn density(&mut self, p: &Point3<f32>) -> f32 {
+    match self.lattice_point(p) {
+      Some(p) => self.density_at(&p),
+      None => generator::T::density(&*self.lattice.generator, p),
+    }
+  }
+
+  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+    // Blend the normals at the corners of the lattice cell `p` is in.
+    let (low, t) = self.lattice_cell(p);
+    let mut v = Vector3::new(0.0, 0.0, 0.0);
+    for &dx in &[0, 1] {
+    for &dy in &[0, 1] {
+    for &dz in &[0, 1] {
+      let weight =
+        (if dx == 0 { 1.0 - t.x } else { t.x }) *
+        (if dy == 0 { 1.0 - t.y } else { t.y }) *
+        (if dz == 0 { 1.0 - t.z } else { t.z });
+      if weight == 0.0 {
+        continue
+      }
+      v = v + self.normal_at(&Point3::new(low.x + dx, low.y + dy, low.z + dz)) * weight;
+    }}}
+
+    if v.magnitude2() == 0.0 {
+      // Flat enough that the lattice can't tell which way is out.
+      return generator::T::normal(&*self.lattice.generator, p)
+    }
+    v.normalize()
+  }
+}
+
+impl<'a> voxel::mosaic::T<voxel::Material> for Sampler<'a> {
+  fn material(&mut self, p: &Point3<f32>) -> Option<voxel::Material> {
+    match self.lattice_point(p) {
+      Some(p) => self.material_at(&p),
+      None => generator::T::material(&*self.lattice.generator, p),
+    }
+  }
+}
+
+#[cfg(test)]
+mod bench {
+  use test::{Bencher, black_box};
+
+  use common::voxel;
+
+  use biome;
+  use cache_mosaic;
+  use generator;
+
+  /// The width, in unit voxels, of the cube of terrain each iteration generates.
+  /// Each iteration generates `WIDTH^3` blocks, so blocks per second is `WIDTH^3 / (ns/iter) * 1e9`.
+  const WIDTH: i32 = 16;
+
+  fn generate<Sample>(mut sample: Sample) where
+    Sample: FnMut(&voxel::bounds::T) -> voxel::T,
+  {
+    for x in 0 .. WIDTH {
+    for y in -WIDTH / 2 .. WIDTH / 2 {
+    for z in 0 .. WIDTH {
+      black_box(sample(&voxel::bounds::new(x, y, z, 0)));
+    }}}
+  }
+
+  /// Sample the generator through a per-point cache, as terrain was generated before the lattice.
+  #[bench]
+  fn cache_mosaic_blocks(b: &mut Bencher) {
+    b.iter(|| {
+      // Start each iteration cold, like `lattice_blocks`.
+      let cached = cache_mosaic::new(Box::new(biome::demo::new(::Seed::new(0))));
+      generate(|bounds| voxel::unwrap(voxel::of_field(&mut generator::Sampler(&cached), bounds)))
+    });
+  }
+
+  /// Sample the generator directly at every point, with nothing shared between points.
+  #[bench]
+  fn exact_blocks(b: &mut Bencher) {
+    let generator = biome::demo::new(::Seed::new(0));
+    b.iter(|| {
+      generate(|bounds| voxel::unwrap(voxel::of_field(&mut generator::Sampler(&generator), bounds)))
+    });
+  }
+
+  #[bench]
+  fn lattice_blocks(b: &mut Bencher) {
+    b.iter(|| {
+      // Start each iteration cold, so this measures generation rather than the tile cache.
+      let lattice = super::new(Box::new(biome::demo::new(::Seed::new(0))));
+      generate(|bounds| voxel::unwrap(voxel::of_field(&mut super::sampler(&lattice, bounds.lg_size), bounds)))
+    });
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use cgmath::{Point3};
+
+  use common::voxel;
+
+  use biome;
+  use generator;
+
+  use super::*;
+
+  #[test]
+  fn tiles_of_negative_points() {
+    for x in -3 * TILE_WIDTH .. 3 * TILE_WIDTH {
+      let tile = tile_of(x);
+      assert!(tile * TILE_WIDTH <= x && x < (tile + 1) * TILE_WIDTH, "{} isn't in tile {}", x, tile);
+    }
+  }
+
+  /// Lattice points are looked up in tiles, but they should be exactly what the generator would give.
+  #[test]
+  fn lattice_points_match_generator() {
+    let lattice = new(Box::new(biome::demo::new(::Seed::new(0))));
+    // Either side of tile edges, on both sides of zero.
+    let coords = [
+      -2 * TILE_WIDTH - 1, -TILE_WIDTH - 1, -TILE_WIDTH, -TILE_WIDTH + 1,
+      -1, 0, 1,
+      TILE_WIDTH - 1, TILE_WIDTH,
+    ];
+    for &lg_spacing in &[-1, 0, 2] {
+      let mut samples = sampler(&lattice, lg_spacing);
+      let spacing = (lg_spacing as f32).exp2();
+      for &x in &coords {
+      for &y in &coords {
+      for &z in &coords {
+        let p = Point3::new(x as f32 * spacing, y as f32 * spacing, z as f32 * spacing);
+        assert_eq!(
+          voxel::field::T::density(&mut samples, &p),
+          generator::T::density(&*lattice.generator, &p),
+          "{:?}", p,
+        );
+        assert_eq!(
+          voxel::mosaic::T::material(&mut samples, &p),
+          generator::T::material(&*lattice.generator, &p),
+          "{:?}", p,
+        );
+      }}}
+    }
+  }
+}
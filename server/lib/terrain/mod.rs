@@ -21,62 +21,123 @@ extern crate time;
 extern crate voxel_data;
 extern crate num;
 
+#[cfg(test)]
 mod cache_mosaic;
+mod lattice;
 
 pub mod biome;
+pub mod generator;
+pub mod sculpt;
+pub mod shape;
 pub mod tree;
 
 pub use noise::Seed;
 
-use std::sync::Mutex;
+/// Bump this whenever a change would make the same seed generate different terrain,
+/// since saved edits are laid on top of freshly generated terrain.
+/// Changes that only affect surface normals (e.g. how they're estimated) don't need a bump,
+/// since at worst they shade the seams around old edits slightly differently.
+pub const GENERATOR_VERSION: u32 = 1;
 
+use cgmath::Point3;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use common::fnv_map;
+use common::lock;
 use common::voxel;
 
 /// This struct contains and lazily generates the world's terrain.
 #[allow(missing_docs)]
 pub struct T {
-  pub mosaic: Mutex<cache_mosaic::T<voxel::Material>>,
-  pub voxels: Mutex<voxel::tree::T>,
+  pub generator: lattice::T,
+  /// Read far more often than it's written, e.g. by every ray cast and every load of a block that's already there.
+  pub voxels: lock::RwLock<voxel::tree::T>,
+  /// How many blocks `load` has generated so far.
+  generated: AtomicUsize,
 }
 
 impl T {
-  #[allow(missing_docs)]
-  pub fn new(terrain_seed: Seed) -> T {
+  /// `voxels_level` is where `voxels` goes in the lock hierarchy of whoever's using this.
+  pub fn new(terrain_seed: Seed, voxels_level: lock::Level) -> T {
     T {
-      mosaic: Mutex::new(cache_mosaic::new(Box::new(biome::demo::new(terrain_seed)))),
-      voxels: Mutex::new(voxel::tree::new()),
+      generator: lattice::new(Box::new(biome::demo::new(terrain_seed))),
+      voxels: lock::RwLock::new(voxels_level, voxel::tree::new()),
+      generated: AtomicUsize::new(0),
     }
   }
 
   /// Load the block of terrain at a given position.
-  // TODO: Allow this to be performed in such a way that self is only briefly locked.
+  /// Blocks are generated without `voxels` locked, so several threads can load at once.
   pub fn load(&self, bounds: &voxel::bounds::T) -> voxel::T {
-    let mut voxels = self.voxels.lock().unwrap();
+    if let Some(voxel) = self.voxels.read().unwrap().get(bounds) {
+      return *voxel
+    }
+
+    let generated = self.generate(bounds);
+    self.generated.fetch_add(1, Ordering::Relaxed);
+
+    let mut voxels = self.voxels.write().unwrap();
     let node = voxels.get_mut_or_create(bounds);
     match node.data {
       None => {
-        let mut mosaic = self.mosaic.lock().unwrap();
-        let voxel = voxel::unwrap(voxel::of_field(&mut *mosaic, bounds));
-        let r = voxel;
-        node.data = Some(voxel);
-        r
+        node.data = Some(generated);
+        generated
       },
+      // Someone else filled this in while we were generating it, maybe with an edit; theirs wins.
       Some(data) => {
         data
       },
     }
   }
 
+  /// Generate the block of terrain at a given position, ignoring any edits.
+  /// This doesn't take any locks, so it can run on any number of threads at once.
+  fn generate(&self, bounds: &voxel::bounds::T) -> voxel::T {
+    voxel::unwrap(voxel::of_field(&mut lattice::sampler(&self.generator, bounds.lg_size), bounds))
+  }
+
+  /// The kind of land in the column at `x`, `z`, as generated.
+  pub fn biome(&self, x: f32, z: f32) -> biome::Kind {
+    generator::T::biome(&*self.generator.generator, x, z)
+  }
+
+  /// How many blocks have been generated to be loaded, including any generated again after they were dropped.
+  pub fn blocks_generated(&self) -> usize {
+    self.generated.load(Ordering::Relaxed)
+  }
+
+  /// Roughly how many bytes of memory `voxels` is using.
+  pub fn ram_usage(&self) -> usize {
+    let voxels = self.voxels.read().unwrap();
+    let mut size = 0;
+    branches_ram_usage(&voxels.contents, &mut size);
+    size
+  }
+
   /// Apply a voxel brush to the terrain.
+  /// `voxel_changed` is given the bounds of each changed voxel, and its contents before and after the brush.
   pub fn brush<VoxelChanged, Mosaic>(
     &self,
     brush: &mut voxel::brush::T<Mosaic>,
     mut voxel_changed: VoxelChanged,
   ) where
-    VoxelChanged: FnMut(&voxel::T, &voxel::bounds::T),
+    VoxelChanged: FnMut(&voxel::bounds::T, &voxel::T, &voxel::T),
     Mosaic: voxel::mosaic::T<voxel::Material>,
   {
-    let mut voxels = self.voxels.lock().unwrap();
+    let mut voxels = self.voxels.write().unwrap();
+
+    // Remember the edited voxels the brush might overwrite.
+    // Anything not in here is untouched generated terrain, and can be regenerated.
+    let mut before = fnv_map::new();
+    for x in brush.bounds.min.x .. brush.bounds.max.x {
+    for y in brush.bounds.min.y .. brush.bounds.max.y {
+    for z in brush.bounds.min.z .. brush.bounds.max.z {
+      let bounds = voxel::bounds::new(x, y, z, 0);
+      if let Some(voxel) = voxels.get(&bounds) {
+        before.insert(bounds, *voxel);
+      }
+    }}}
+
     voxels.brush(
       brush,
       // TODO: Put a max size on this
@@ -84,11 +145,122 @@ impl T {
         if bounds.lg_size > 3 {
           None
         } else {
-          let mut mosaic = self.mosaic.lock().unwrap();
-          Some(voxel::unwrap(voxel::of_field(&mut *mosaic, bounds)))
+          Some(self.generate(bounds))
         }
       },
-      &mut voxel_changed,
+      &mut |after, bounds| {
+        let before =
+          match before.get(bounds) {
+            Some(voxel) => *voxel,
+            None => self.generate(bounds),
+          };
+        voxel_changed(bounds, &before, after);
+      },
     );
   }
+
+  /// Change the material of the solid unit voxels inside a brush, without changing their shape.
+  /// `voxel_changed` is called the same way as in `brush`.
+  pub fn paint<VoxelChanged, Mosaic>(
+    &self,
+    brush: &mut voxel::brush::T<Mosaic>,
+    mut voxel_changed: VoxelChanged,
+  ) where
+    VoxelChanged: FnMut(&voxel::bounds::T, &voxel::T, &voxel::T),
+    Mosaic: voxel::mosaic::T<voxel::Material>,
+  {
+    let mut voxels = self.voxels.write().unwrap();
+    for x in brush.bounds.min.x .. brush.bounds.max.x {
+    for y in brush.bounds.min.y .. brush.bounds.max.y {
+    for z in brush.bounds.min.z .. brush.bounds.max.z {
+      let center = Point3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+      if voxel::field::T::density(&mut brush.mosaic, &center) < 0.0 {
+        continue
+      }
+      let material =
+        match voxel::mosaic::T::material(&mut brush.mosaic, &center) {
+          None | Some(voxel::Material::Empty) => continue,
+          Some(material) => material,
+        };
+
+      let bounds = voxel::bounds::new(x, y, z, 0);
+      let node = voxels.get_mut_or_create(&bounds);
+      let before =
+        match node.data {
+          Some(voxel) => voxel,
+          None => self.generate(&bounds),
+        };
+      let after =
+        match before {
+          voxel::Volume(voxel::Material::Empty) => continue,
+          voxel::Volume(_) => voxel::Volume(material),
+          voxel::Surface(mut surface) => {
+            if surface.corner == voxel::Material::Empty {
+              continue
+            }
+            surface.corner = material;
+            voxel::Surface(surface)
+          },
+        };
+      if after == before {
+        continue
+      }
+
+      node.data = Some(after);
+      voxel_changed(&bounds, &before, &after);
+    }}}
+  }
+
+  /// Set voxels directly, e.g. to undo an edit. Each change is a voxel's bounds, what it's expected to hold,
+  /// and what to put there. Voxels that no longer hold what's expected (e.g. because they've been edited since)
+  /// are left alone. `voxel_changed` is called the same way as in `brush`.
+  pub fn restore<VoxelChanged>(
+    &self,
+    changes: &[(voxel::bounds::T, voxel::T, voxel::T)],
+    mut voxel_changed: VoxelChanged,
+  ) where
+    VoxelChanged: FnMut(&voxel::bounds::T, &voxel::T, &voxel::T),
+  {
+    let mut voxels = self.voxels.write().unwrap();
+    for &(ref bounds, ref expected, ref replacement) in changes {
+      let node = voxels.get_mut_or_create(bounds);
+      let before =
+        match node.data {
+          Some(voxel) => voxel,
+          None => self.generate(bounds),
+        };
+      if before != *expected || before == *replacement {
+        continue
+      }
+
+      node.data = Some(*replacement);
+      voxel_changed(bounds, &before, replacement);
+    }
+  }
+}
+
+fn branches_ram_usage(branches: &voxel::tree::Branches, size: &mut usize) {
+  *size += std::mem::size_of_val(branches);
+  for node in branches.as_flat_array() {
+    match node.next {
+      voxel_data::tree::Inner::Empty => {},
+      voxel_data::tree::Inner::Branches(ref branches) => {
+        branches_ram_usage(branches, size);
+      },
+    }
+  }
+}
+
+/// Forget everything in `voxels` inside `bounds`, so it's regenerated the next time it's needed.
+/// This takes the voxels already locked, so callers can e.g. save edits before they're forgotten.
+/// Returns roughly how many bytes were freed.
+pub fn forget(voxels: &mut voxel::tree::T, bounds: &voxel::bounds::T) -> usize {
+  let node = voxels.get_mut_or_create(bounds);
+  let mut freed = 0;
+  if let voxel_data::tree::Inner::Branches(ref branches) = node.next {
+    branches_ram_usage(branches, &mut freed);
+  }
+  node.next = voxel_data::tree::Inner::Empty;
+  node.data = None;
+  freed
 }
@@ -0,0 +1,289 @@
+//! Mosaics that reshape existing terrain, rather than adding a shape to it.
+//! They work from a snapshot of the terrain taken before the brush is applied.
+
+use cgmath::{Point3, Vector3, InnerSpace};
+use collision::{Aabb3};
+
+use common::voxel;
+
+/// The materials of the unit voxels in a region of terrain.
+pub struct Snapshot {
+  low       : Point3<i32>,
+  size      : Vector3<i32>,
+  materials : Vec<voxel::Material>,
+}
+
+impl Snapshot {
+  fn index(&self, p: &Point3<i32>) -> Option<usize> {
+    let p = Point3::new(p.x - self.low.x, p.y - self.low.y, p.z - self.low.z);
+    if p.x < 0 || p.y < 0 || p.z < 0 || p.x >= self.size.x || p.y >= self.size.y || p.z >= self.size.z {
+      return None
+    }
+    Some(((p.x * self.size.y + p.y) * self.size.z + p.z) as usize)
+  }
+
+  /// The material of the unit voxel at `p`. Anything outside the snapshot is empty.
+  pub fn material(&self, p: &Point3<i32>) -> voxel::Material {
+    match self.index(p) {
+      None => voxel::Material::Empty,
+      Some(i) => self.materials[i],
+    }
+  }
+
+  fn occupancy(&self, p: &Point3<i32>) -> f32 {
+    if self.material(p) == voxel::Material::Empty { -1.0 } else { 1.0 }
+  }
+
+  /// A material for a solid point near `p`: the voxel's own if it has one, or the first solid one below or beside it.
+  fn solid_material_near(&self, p: &Point3<i32>) -> voxel::Material {
+    let neighbors = [(0, 0, 0), (0, -1, 0), (1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1), (0, 1, 0)];
+    for &(dx, dy, dz) in &neighbors {
+      match self.material(&Point3::new(p.x + dx, p.y + dy, p.z + dz)) {
+        voxel::Material::Empty => {},
+        material => return material,
+      }
+    }
+    for dy in 2 .. self.size.y {
+      match self.material(&Point3::new(p.x, p.y - dy, p.z)) {
+        voxel::Material::Empty => {},
+        material => return material,
+      }
+    }
+    voxel::Material::Terrain
+  }
+}
+
+/// Take a snapshot of the unit voxels in `region`.
+pub fn snapshot(terrain: &::T, region: &Aabb3<i32>) -> Snapshot {
+  let size = region.max - region.min;
+  let mut materials = Vec::with_capacity((size.x * size.y * size.z) as usize);
+  for x in region.min.x .. region.max.x {
+  for y in region.min.y .. region.max.y {
+  for z in region.min.z .. region.max.z {
+    let material =
+      match terrain.load(&voxel::bounds::new(x, y, z, 0)) {
+        voxel::Volume(material) => material,
+        voxel::Surface(surface) => surface.corner,
+      };
+    materials.push(material);
+  }}}
+
+  Snapshot {
+    low       : region.min,
+    size      : size,
+    materials : materials,
+  }
+}
+
+fn voxel_of(p: &Point3<f32>) -> Point3<i32> {
+  Point3::new(p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32)
+}
+
+/// Trilinearly interpolate per-voxel values, which are taken to be at voxel centers.
+fn interpolate<Value>(value: Value, p: &Point3<f32>) -> f32 where
+  Value: Fn(&Point3<i32>) -> f32,
+{
+  let p = Point3::new(p.x - 0.5, p.y - 0.5, p.z - 0.5);
+  let low = voxel_of(&p);
+  let t = Vector3::new(p.x - low.x as f32, p.y - low.y as f32, p.z - low.z as f32);
+  let mut r = 0.0;
+  for &dx in &[0, 1] {
+  for &dy in &[0, 1] {
+  for &dz in &[0, 1] {
+    let weight =
+      (if dx == 0 { 1.0 - t.x } else { t.x }) *
+      (if dy == 0 { 1.0 - t.y } else { t.y }) *
+      (if dz == 0 { 1.0 - t.z } else { t.z });
+    r += weight * value(&Point3::new(low.x + dx, low.y + dy, low.z + dz));
+  }}}
+  r
+}
+
+/// Use density differential in each dimension as an approximation of the normal.
+fn normal<Density>(mut density: Density, p: &Point3<f32>) -> Vector3<f32> where
+  Density: FnMut(&Point3<f32>) -> f32,
+{
+  let delta = 0.01;
+
+  macro_rules! differential(($d:ident) => {{
+    let high: f32 = {
+      let mut p = *p;
+      p.$d += delta;
+      density(&p)
+    };
+    let low: f32 = {
+      let mut p = *p;
+      p.$d -= delta;
+      density(&p)
+    };
+    high - low
+  }});
+
+  let v = Vector3::new(differential!(x), differential!(y), differential!(z));
+  if v.magnitude2() == 0.0 {
+    return Vector3::new(0.0, 1.0, 0.0)
+  }
+  // Negate because we're leaving the volume when density is decreasing.
+  -v.normalize()
+}
+
+/// Blur the terrain inside a sphere, rounding off sharp edges and filling in small holes.
+pub mod smooth {
+  use cgmath::{Point3, Vector3, EuclideanSpace, InnerSpace};
+
+  use common::voxel;
+  use voxel_data;
+
+  use super::{Snapshot, interpolate, voxel_of};
+
+  #[allow(missing_docs)]
+  pub struct T {
+    snapshot : Snapshot,
+    /// The averaged occupancy of each voxel and its neighbors.
+    blurred  : Vec<f32>,
+    center   : Point3<f32>,
+    radius   : f32,
+  }
+
+  unsafe impl Send for T {}
+
+  /// Smooth the terrain in `snapshot` within `radius` of `center`.
+  /// The snapshot should extend at least one voxel past the sphere on every side.
+  pub fn new(snapshot: Snapshot, center: Point3<f32>, radius: f32) -> T {
+    let mut blurred = Vec::with_capacity(snapshot.materials.len());
+    for x in 0 .. snapshot.size.x {
+    for y in 0 .. snapshot.size.y {
+    for z in 0 .. snapshot.size.z {
+      let p = Point3::new(snapshot.low.x + x, snapshot.low.y + y, snapshot.low.z + z);
+      let mut total = 0.0;
+      for dx in -1 .. 2 {
+      for dy in -1 .. 2 {
+      for dz in -1 .. 2 {
+        total += snapshot.occupancy(&Point3::new(p.x + dx, p.y + dy, p.z + dz));
+      }}}
+      blurred.push(total / 27.0);
+    }}}
+
+    T {
+      snapshot : snapshot,
+      blurred  : blurred,
+      center   : center,
+      radius   : radius,
+    }
+  }
+
+  impl T {
+    fn blurred(&self, p: &Point3<i32>) -> f32 {
+      match self.snapshot.index(p) {
+        None => -1.0,
+        Some(i) => self.blurred[i],
+      }
+    }
+
+    /// How much of the blurred density to use at `p`; this fades out towards the edge of the sphere.
+    fn weight(&self, p: &Point3<f32>) -> f32 {
+      let d = (*p - self.center).magnitude() / self.radius;
+      if d >= 1.0 {
+        0.0
+      } else if d <= 0.5 {
+        1.0
+      } else {
+        2.0 * (1.0 - d)
+      }
+    }
+  }
+
+  impl voxel_data::field::T for T {
+    fn density(&mut self, p: &Point3<f32>) -> f32 {
+      let original = interpolate(|v| self.snapshot.occupancy(v), p);
+      let blurred = interpolate(|v| self.blurred(v), p);
+      let weight = self.weight(p);
+      weight * blurred + (1.0 - weight) * original
+    }
+
+    fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+      super::normal(|p| voxel_data::field::T::density(self, p), p)
+    }
+  }
+
+  impl voxel_data::mosaic::T<voxel::Material> for T {
+    fn material(&mut self, p: &Point3<f32>) -> Option<voxel::Material> {
+      if (*p - self.center).to_vec().magnitude2() >= self.radius * self.radius {
+        return None
+      }
+      if voxel_data::field::T::density(self, p) < 0.0 {
+        return Some(voxel::Material::Empty)
+      }
+      Some(self.snapshot.solid_material_near(&voxel_of(p)))
+    }
+  }
+}
+
+/// Level the terrain inside an upright cylinder to a given height.
+pub mod flatten {
+  use cgmath::{Point3, Vector3, InnerSpace};
+
+  use common::voxel;
+  use voxel_data;
+
+  use super::{Snapshot, interpolate, voxel_of};
+
+  #[allow(missing_docs)]
+  pub struct T {
+    snapshot : Snapshot,
+    center   : Point3<f32>,
+    radius   : f32,
+    /// The y coordinate to level the surface to.
+    height   : f32,
+  }
+
+  unsafe impl Send for T {}
+
+  /// Flatten the terrain in `snapshot` to `height`, within an upright cylinder centered on `center`
+  /// whose radius and half-height are `radius`.
+  pub fn new(snapshot: Snapshot, center: Point3<f32>, radius: f32, height: f32) -> T {
+    T {
+      snapshot : snapshot,
+      center   : center,
+      radius   : radius,
+      height   : height,
+    }
+  }
+
+  impl T {
+    fn contains(&self, p: &Point3<f32>) -> bool {
+      Vector3::new(p.x - self.center.x, 0.0, p.z - self.center.z).magnitude() < self.radius &&
+      (p.y - self.center.y).abs() < self.radius
+    }
+  }
+
+  impl voxel_data::field::T for T {
+    fn density(&mut self, p: &Point3<f32>) -> f32 {
+      if self.contains(p) {
+        self.height - p.y
+      } else {
+        interpolate(|v| self.snapshot.occupancy(v), p)
+      }
+    }
+
+    fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+      if self.contains(p) {
+        Vector3::new(0.0, 1.0, 0.0)
+      } else {
+        super::normal(|p| voxel_data::field::T::density(self, p), p)
+      }
+    }
+  }
+
+  impl voxel_data::mosaic::T<voxel::Material> for T {
+    fn material(&mut self, p: &Point3<f32>) -> Option<voxel::Material> {
+      if !self.contains(p) {
+        return None
+      }
+      if p.y >= self.height {
+        return Some(voxel::Material::Empty)
+      }
+      Some(self.snapshot.solid_material_near(&voxel_of(p)))
+    }
+  }
+}
@@ -0,0 +1,100 @@
+//! Simple solid shapes for brushes to be made of. Each is centered on the origin.
+
+use cgmath::{Point3, Vector3};
+use voxel_data::field;
+
+use tree;
+
+/// An axis-aligned cube.
+pub mod cube {
+  use cgmath::{Point3, Vector3};
+
+  use voxel_data::field;
+
+  #[allow(missing_docs)]
+  pub struct T {
+    pub half_width: f32,
+  }
+
+  impl field::T for T {
+    fn density(&mut self, p: &Point3<f32>) -> f32 {
+      self.half_width - p.x.abs().max(p.y.abs()).max(p.z.abs())
+    }
+
+    fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+      // Point out through whichever face is closest.
+      let (x, y, z) = (p.x.abs(), p.y.abs(), p.z.abs());
+      if x >= y && x >= z {
+        Vector3::new(p.x.signum(), 0.0, 0.0)
+      } else if y >= z {
+        Vector3::new(0.0, p.y.signum(), 0.0)
+      } else {
+        Vector3::new(0.0, 0.0, p.z.signum())
+      }
+    }
+  }
+}
+
+/// A cylinder standing upright along the y axis.
+pub mod cylinder {
+  use cgmath::{Point3, Vector3, InnerSpace};
+
+  use voxel_data::field;
+
+  #[allow(missing_docs)]
+  pub struct T {
+    pub radius      : f32,
+    pub half_height : f32,
+  }
+
+  impl field::T for T {
+    fn density(&mut self, p: &Point3<f32>) -> f32 {
+      let side = self.radius - Vector3::new(p.x, 0.0, p.z).magnitude();
+      let cap = self.half_height - p.y.abs();
+      side.min(cap)
+    }
+
+    fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+      let lateral = Vector3::new(p.x, 0.0, p.z);
+      let side = self.radius - lateral.magnitude();
+      let cap = self.half_height - p.y.abs();
+      if cap < side || lateral.magnitude2() == 0.0 {
+        Vector3::new(0.0, p.y.signum(), 0.0)
+      } else {
+        lateral.normalize()
+      }
+    }
+  }
+}
+
+/// Any of the shapes a brush can be.
+#[allow(missing_docs)]
+pub enum T {
+  Sphere(field::sphere::T),
+  Cube(cube::T),
+  Cylinder(cylinder::T),
+  Tree(tree::T),
+}
+
+unsafe impl Send for T {}
+
+impl field::T for T {
+  fn density(&mut self, p: &Point3<f32>) -> f32 {
+    match *self {
+      T::Sphere(ref mut s)   => field::T::density(s, p),
+      T::Cube(ref mut s)     => field::T::density(s, p),
+      T::Cylinder(ref mut s) => field::T::density(s, p),
+      T::Tree(ref mut s)     => field::T::density(s, p),
+    }
+  }
+
+  fn normal(&mut self, p: &Point3<f32>) -> Vector3<f32> {
+    match *self {
+      T::Sphere(ref mut s)   => field::T::normal(s, p),
+      T::Cube(ref mut s)     => field::T::normal(s, p),
+      T::Cylinder(ref mut s) => field::T::normal(s, p),
+      T::Tree(ref mut s)     => field::T::normal(s, p),
+    }
+  }
+}
+
@@ -9,6 +9,7 @@ extern crate alloc_system;
 
 extern crate env_logger;
 extern crate nanomsg;
+#[macro_use]
 extern crate log;
 extern crate thread_scoped;
 
@@ -18,6 +19,9 @@ extern crate dummy_client_lib;
 extern crate server_lib;
 
 use std::borrow::Borrow;
+#[cfg(not(feature = "dummy-client"))]
+use std::env;
+use std::path::Path;
 use std::sync::Mutex;
 
 fn main() {
@@ -25,19 +29,32 @@ fn main() {
 
   let listen_url = String::from("ipc:///tmp/client.ipc");
   let server_url = String::from("ipc:///tmp/server.ipc");
+  #[cfg(not(feature = "dummy-client"))]
+  let player_name = env::var("USER").unwrap_or_else(|_| String::from("player"));
+
+  let config =
+    match server_lib::config::load(Path::new(server_lib::config::DEFAULT_PATH), false) {
+      Ok(config) => config,
+      Err(err) => panic!("{}", err),
+    };
 
   let quit_signal = Mutex::new(false);
+  // The bundled server doesn't have a console.
+  let admin = server_lib::admin::new();
 
   unsafe {
     let server_thread =
       thread_scoped::scoped(|| {
-        server_lib::run(server_url.borrow(), &quit_signal);
+        if let Err(err) = server_lib::run(server_url.borrow(), &config, &admin, &quit_signal) {
+          error!("Couldn't start the server: {}", err);
+          std::process::exit(1);
+        }
       });
 
     #[cfg(feature = "dummy-client")]
-    dummy_client_lib::run(listen_url.borrow(), server_url.borrow());
+    dummy_client_lib::run(listen_url.borrow(), server_url.borrow(), "dummy");
     #[cfg(not(feature = "dummy-client"))]
-    client_lib::run(listen_url.borrow(), server_url.borrow());
+    client_lib::run(listen_url.borrow(), server_url.borrow(), player_name.borrow());
     *quit_signal.lock().unwrap() = true;
     server_thread.join();
 